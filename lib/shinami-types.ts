@@ -18,12 +18,22 @@ export interface BuildSponsoredBetTxRequest {
         newProbs: number[];
         debitAmount: number;
         creditAmount: number;
+        nonce: number;
+        seq: number;
+        prevStateHash: number[];
+        userBalance: number;
+        makerBalance: number;
+        feeAmount: number;
+        protocol: string;
+        protocolFee: number;
+        protocolBalance: number;
+        referrer: string;
+        referrerFee: number;
+        referrerBalance: number;
+        b: number;
         timestampMs: number;
     };
     teeSignature: string;
-    // Balance data for vault updates
-    userNewBalance: string;
-    makerNewBalance: string;
 }
 
 /**
@@ -1,6 +1,7 @@
 // TEE and Smart Contract Configuration
 // Updated: 2026-01-17 for Federated Architecture
 
+import { bcs } from '@mysten/sui/bcs';
 import { VAULT_CONFIG, WORLD_CONFIG, USDC_CONFIG } from './config';
 
 export const TEE_CONFIG = {
@@ -18,6 +19,9 @@ export const TEE_CONFIG = {
 
     // PM Contract Package
     PM_PACKAGE: '0x327d01aa4fdc8cba53596b225510a6b5afc5d2266227654574fe6347a45d3973',
+
+    // Shared pm::BetRegistry created on publish (rejects replayed bet signatures)
+    BET_REGISTRY_ID: process.env.NEXT_PUBLIC_PM_BET_REGISTRY_ID || '',
 };
 
 // Alias for backward compatibility
@@ -62,6 +66,8 @@ export interface PlaceBetRequest {
     amount: number;
     maker: string;
     current_probs: number[];
+    nonce: number; // Client idempotency key, unique per user; reuse it when retrying
+    referrer?: string; // Wallet that referred the trade, paid a share of the fee
 }
 
 export interface PlaceBetResponse {
@@ -71,11 +77,71 @@ export interface PlaceBetResponse {
     outcome: number;
     debit_amount: number;
     credit_amount: number;
+    user: string;
+    nonce: number;
+    seq: number; // Per-pool sequence number of this bet
+    prev_state_hash: number[]; // Hash of the pool state the bet was priced from
+    user_balance: number; // User's vault balance after the bet
+    maker_balance: number; // Maker's vault balance after the bet
+    fee_amount: number; // Total fee; credit_amount is the liquidity providers' share
+    protocol: string; // Protocol fee recipient ('' if none)
+    protocol_fee: number;
+    protocol_balance: number;
+    referrer: string; // Referrer ('' if none)
+    referrer_fee: number;
+    referrer_balance: number;
+    b: number; // LMSR liquidity the bet was priced at (scaled by 10^6)
+}
+
+// State the next bet of a pool must be priced from (GET /pools/head)
+export interface PoolHead {
+    seq: number;
+    state_hash: number[];
+    probs: number[]; // Empty before the pool's first bet
+}
+
+// Sent once the submit_bet transaction executed; until then the bet is only pending
+export interface ConfirmBetRequest {
+    user: string;
+    nonce: number;
+    tx_digest: string;
 }
 
 export interface ResolveRequest {
     pool_id: number;
     winning_outcome: number;
+    batch_size?: number; // Winners per payout batch
+}
+
+// Bets, liquidity requests and resolutions must be signed by the wallet they name
+// (the resolver for resolutions): a personal-message signature over the request's
+// BCS bytes, sent next to the payload as `signature`.
+// BCS layouts below MUST match the Rust request structs field for field.
+const PlaceBetRequestBcs = bcs.struct('PlaceBetRequest', {
+    user: bcs.string(),
+    pool_id: bcs.u64(),
+    outcome: bcs.u8(),
+    amount: bcs.u64(),
+    maker: bcs.string(),
+    current_probs: bcs.vector(bcs.u64()),
+    nonce: bcs.u64(),
+    referrer: bcs.option(bcs.string()),
+});
+
+const ResolveRequestBcs = bcs.struct('ResolveRequest', {
+    pool_id: bcs.u64(),
+    winning_outcome: bcs.u8(),
+    batch_size: bcs.option(bcs.u64()),
+});
+
+// Message a wallet signs to authorize a bet
+export function placeBetMessage(request: PlaceBetRequest): Uint8Array {
+    return PlaceBetRequestBcs.serialize({ ...request, referrer: request.referrer ?? null }).toBytes();
+}
+
+// Message the resolver signs to authorize a resolution
+export function resolveMessage(request: ResolveRequest): Uint8Array {
+    return ResolveRequestBcs.serialize({ ...request, batch_size: request.batch_size ?? null }).toBytes();
 }
 
 export interface Payout {
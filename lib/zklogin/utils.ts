@@ -3,7 +3,7 @@
  */
 
 import { Ed25519Keypair } from '@mysten/sui/keypairs/ed25519';
-import { generateNonce, generateRandomness, jwtToAddress, genAddressSeed, getExtendedEphemeralPublicKey } from '@mysten/sui/zklogin';
+import { generateNonce, generateRandomness, jwtToAddress, genAddressSeed, getExtendedEphemeralPublicKey, getZkLoginSignature } from '@mysten/sui/zklogin';
 import { SuiClient } from '@mysten/sui/client';
 import { jwtDecode } from 'jwt-decode';
 import { ZKLOGIN_CONFIG, STORAGE_KEYS } from './config';
@@ -155,6 +155,25 @@ export function generateAddressSeed(
   ).toString();
 }
 
+/**
+ * Sign a personal message (e.g. a TEE request) as the zkLogin wallet: the
+ * ephemeral key signs, the proof ties it to the address
+ */
+export async function signPersonalMessageWithZkLogin(
+  message: Uint8Array,
+  ephemeralKeyPair: Ed25519Keypair,
+  proof: PartialZkLoginSignature,
+  addressSeed: string,
+  maxEpoch: number
+): Promise<string> {
+  const { signature: userSignature } = await ephemeralKeyPair.signPersonalMessage(message);
+  return getZkLoginSignature({
+    inputs: { ...proof, addressSeed },
+    maxEpoch,
+    userSignature,
+  });
+}
+
 /**
  * Storage utilities for session and local storage
  */
@@ -17,6 +17,10 @@ const EInvalidConfigVersion: u64 = 1;
 const EInvalidCap: u64 = 2;
 const EInvalidOwner: u64 = 3;
 
+// Tag and version of domain intent headers, see `IntentHeader`.
+const INTENT_DOMAIN: vector<u8> = b"nautilus-intent";
+const INTENT_VERSION: u8 = 1;
+
 // PCR0: Enclave image file
 // PCR1: Enclave Kernel
 // PCR2: Enclave application
@@ -56,6 +60,24 @@ public struct IntentMessage<T: drop> has copy, drop {
     payload: T,
 }
 
+// Header of a domain intent message: tells the app, chain and scope a message
+// is signed for, where `IntentMessage` only has an app-local scope. Matches
+// `IntentHeader` in the server's `intent.rs`, which registers app ids and scopes.
+public struct IntentHeader has copy, drop {
+    domain: vector<u8>,
+    version: u8,
+    app_id: u16,
+    chain_id: vector<u8>, // Sui chain identifier, 4 bytes
+    scope: u8,
+}
+
+// An intent message with a domain header.
+public struct DomainIntentMessage<T: drop> has copy, drop {
+    header: IntentHeader,
+    timestamp_ms: u64,
+    payload: T,
+}
+
 /// Create a new `Cap` using a `witness` T from a module.
 public fun new_cap<T: drop>(_: T, ctx: &mut TxContext): Cap<T> {
     Cap {
@@ -111,6 +133,36 @@ public fun verify_signature<T, P: drop>(
     return ed25519::ed25519_verify(signature, &enclave.pk, &payload)
 }
 
+public fun verify_domain_signature<T, P: drop>(
+    enclave: &Enclave<T>,
+    header: IntentHeader,
+    timestamp_ms: u64,
+    payload: P,
+    signature: &vector<u8>,
+): bool {
+    let intent_message = DomainIntentMessage { header, timestamp_ms, payload };
+    let payload = bcs::to_bytes(&intent_message);
+    return ed25519::ed25519_verify(signature, &enclave.pk, &payload)
+}
+
+public fun create_intent_header(app_id: u16, chain_id: vector<u8>, scope: u8): IntentHeader {
+    IntentHeader {
+        domain: INTENT_DOMAIN,
+        version: INTENT_VERSION,
+        app_id,
+        chain_id,
+        scope,
+    }
+}
+
+public fun create_domain_intent_message<P: drop>(
+    header: IntentHeader,
+    timestamp_ms: u64,
+    payload: P,
+): DomainIntentMessage<P> {
+    DomainIntentMessage { header, timestamp_ms, payload }
+}
+
 public fun update_pcrs<T: drop>(
     config: &mut EnclaveConfig<T>,
     cap: &Cap<T>,
@@ -224,3 +276,27 @@ fun test_serde() {
     let bytes = bcs::to_bytes(&signing_payload);
     assert!(bytes == x"0020b1d110960100000d53616e204672616e636973636f0d00000000000000", 0);
 }
+
+#[test]
+fun test_domain_serde() {
+    // serialization should be consistent with rust test see `fn test_domain_serde` in `src/nautilus-server/src/intent.rs`.
+    let app_weather = 1;
+    let timestamp = 1744038900000;
+    let message = DomainIntentMessage {
+        header: create_intent_header(app_weather, x"4c78adac", 0),
+        timestamp_ms: timestamp,
+        payload: SigningPayload {
+            location: b"San Francisco".to_string(),
+            temperature: 13,
+        },
+    };
+    let bytes = bcs::to_bytes(&message);
+    assert!(
+        bytes == x"0f6e617574696c75732d696e74656e74010100044c78adac0020b1d110960100000d53616e204672616e636973636f0d00000000000000",
+        0,
+    );
+
+    let app_pm = 5;
+    let header = create_intent_header(app_pm, x"35834a8a", 5);
+    assert!(bcs::to_bytes(&header) == x"0f6e617574696c75732d696e74656e740105000435834a8a05", 1);
+}
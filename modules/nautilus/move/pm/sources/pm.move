@@ -3,16 +3,51 @@
 /// This contract verifies TEE signatures before updating market state.
 /// TEE calculates LMSR pricing and signs responses.
 module pm::pm {
-    use enclave::enclave::{Self, Enclave, Cap};
+    use enclave::enclave::{Self, Enclave, Cap, IntentHeader};
+    use std::bcs;
+    use std::hash;
     use std::string::String;
     use std::vector;
+    use sui::clock::Clock;
+    use sui::event;
+    use sui::table::{Self, Table};
+
+    #[test_only]
+    use enclave::enclave::EnclaveConfig;
+    #[test_only]
+    use sui::test_scenario::{Self, Scenario};
+
+    // App id of PM in the intent registry - must match Rust APP_PM
+    const APP_PM: u16 = 5;
+    // Sui chain the enclave signs for - must match its SUI_CHAIN_ID (testnet)
+    const CHAIN_ID: vector<u8> = x"4c78adac";
 
     // Intent scopes - must match Rust
     const INTENT_PLACE_BET: u8 = 0;
     const INTENT_RESOLVE: u8 = 1;
+    const INTENT_PAYOUT_BATCH: u8 = 2;
+    const INTENT_BATCH_ROOT: u8 = 5;
+
+    // How long a signed bet can be submitted - must match Rust BET_TTL_MS
+    const BET_TTL_MS: u64 = 120_000;
+
+    // State hash of a pool before its first trade - must match Rust GENESIS_STATE_HASH
+    const GENESIS_STATE_HASH: vector<u8> = x"0000000000000000000000000000000000000000000000000000000000000000";
 
     // Error codes
     const EInvalidSignature: u64 = 1;
+    const EBatchAlreadySettled: u64 = 2;
+    const EBatchLengthMismatch: u64 = 3;
+    const EBetAlreadySubmitted: u64 = 4;
+    const EBetExpired: u64 = 5;
+    const EOutOfOrder: u64 = 6;
+    const EStaleState: u64 = 7;
+    const EUnknownBatchRoot: u64 = 8;
+    const ENotInBatch: u64 = 9;
+
+    // Merkle hash prefixes - must match Rust merkle.rs
+    const MERKLE_LEAF_PREFIX: u8 = 0;
+    const MERKLE_NODE_PREFIX: u8 = 1;
 
     /// One-Time Witness for creating EnclaveConfig
     public struct PM has drop {}
@@ -26,6 +61,56 @@ module pm::pm {
         outcome: u8,
         debit_amount: u64,
         credit_amount: u64,
+        user: String,
+        nonce: u64,
+        seq: u64,
+        prev_state_hash: vector<u8>,
+        user_balance: u64,
+        maker_balance: u64,
+        fee_amount: u64,
+        protocol: String,
+        protocol_fee: u64,
+        protocol_balance: u64,
+        referrer: String,
+        referrer_fee: u64,
+        referrer_balance: u64,
+        b: u64,
+    }
+
+    /// Pool state after a bet - Match Rust PoolTransition. Its BCS hash is the pool state hash.
+    public struct PoolTransition has copy, drop {
+        pool_id: u64,
+        seq: u64,
+        probs: vector<u64>,
+    }
+
+    /// Last applied bet of a pool
+    public struct PoolHead has copy, drop, store {
+        seq: u64,
+        state_hash: vector<u8>,
+    }
+
+    /// Key of a submitted bet: the client nonce is unique per user
+    public struct BetKey has copy, drop, store {
+        user: String,
+        nonce: u64,
+    }
+
+    /// Shared registry of submitted bets (replay protection per signed bet)
+    /// and of each pool's last applied bet (bets apply strictly in sequence)
+    public struct BetRegistry has key {
+        id: UID,
+        submitted: Table<BetKey, bool>,
+        pool_heads: Table<u64, PoolHead>,
+    }
+
+    /// Emitted when a bet is accepted; the host relays its digest to the TEE's /confirm
+    public struct BetSubmitted has copy, drop {
+        user: String,
+        nonce: u64,
+        pool_id: u64,
+        outcome: u8,
+        shares: u64,
     }
 
     /// Payout info for a winner - Match Rust
@@ -43,6 +128,40 @@ module pm::pm {
         total_payout: u64,
     }
 
+    /// One signed chunk of a pool's payouts - Match Rust PayoutBatch
+    public struct PayoutBatch has copy, drop {
+        pool_id: u64,
+        winning_outcome: u8,
+        batch_index: u64,
+        total_batches: u64,
+        payouts: vector<Payout>,
+        batch_payout: u64,
+    }
+
+    /// Key of a settled payout batch
+    public struct BatchKey has copy, drop, store {
+        pool_id: u64,
+        batch_index: u64,
+    }
+
+    /// Merkle root over a batch of signed messages - Match Rust BatchRoot
+    public struct BatchRoot has copy, drop {
+        root: vector<u8>,
+        leaf_count: u64,
+    }
+
+    /// Shared set of batch roots whose enclave signature has been checked
+    public struct VerifiedRoots has key {
+        id: UID,
+        roots: Table<vector<u8>, bool>,
+    }
+
+    /// Shared registry of settled payout batches (replay protection per batch)
+    public struct Settlement has key {
+        id: UID,
+        settled: Table<BatchKey, bool>,
+    }
+
     // ============================================================
     // INIT - Creates EnclaveConfig
     // ============================================================
@@ -60,6 +179,22 @@ module pm::pm {
         
         // Transfer cap to sender
         transfer::public_transfer(cap, ctx.sender());
+
+        transfer::share_object(Settlement {
+            id: object::new(ctx),
+            settled: table::new(ctx),
+        });
+
+        transfer::share_object(BetRegistry {
+            id: object::new(ctx),
+            submitted: table::new(ctx),
+            pool_heads: table::new(ctx),
+        });
+
+        transfer::share_object(VerifiedRoots {
+            id: object::new(ctx),
+            roots: table::new(ctx),
+        });
     }
 
     // ============================================================
@@ -69,8 +204,12 @@ module pm::pm {
     /// Submit a bet with TEE-signed proof
     /// Frontend calls this after receiving signed response from TEE
     /// After verification, frontend should call vault and world contracts
+    /// Each (user, nonce) can only be submitted once, within BET_TTL_MS of signing,
+    /// and only as the next bet of its pool (seq and prev_state_hash must follow on).
     public entry fun submit_bet<T>(
         enclave: &Enclave<T>,
+        registry: &mut BetRegistry,
+        clock: &Clock,
         // PlaceBetResponse fields
         shares: u64,
         new_probs: vector<u64>,
@@ -78,12 +217,25 @@ module pm::pm {
         outcome: u8,
         debit_amount: u64,
         credit_amount: u64,
+        user: String,
+        nonce: u64,
+        seq: u64,
+        prev_state_hash: vector<u8>,
+        user_balance: u64,
+        maker_balance: u64,
+        fee_amount: u64,
+        protocol: String,
+        protocol_fee: u64,
+        protocol_balance: u64,
+        referrer: String,
+        referrer_fee: u64,
+        referrer_balance: u64,
+        b: u64,
         // Signature data
         timestamp_ms: u64,
         sig: vector<u8>,
         _ctx: &mut TxContext,
     ) {
-        // Reconstruct the response struct
         let response = PlaceBetResponse {
             shares,
             new_probs,
@@ -91,20 +243,188 @@ module pm::pm {
             outcome,
             debit_amount,
             credit_amount,
+            user,
+            nonce,
+            seq,
+            prev_state_hash,
+            user_balance,
+            maker_balance,
+            fee_amount,
+            protocol,
+            protocol_fee,
+            protocol_balance,
+            referrer,
+            referrer_fee,
+            referrer_balance,
+            b,
         };
 
         // Verify TEE signature
-        let verified = enclave.verify_signature(
-            INTENT_PLACE_BET,
+        let verified = enclave.verify_domain_signature(
+            pm_header(INTENT_PLACE_BET),
             timestamp_ms,
             response,
             &sig,
         );
         assert!(verified, EInvalidSignature);
+        accept_bet(registry, clock, &response, timestamp_ms);
 
         // Signature verified!
-        // The frontend can now safely call vault::set_withdrawable_balance
-        // and world::update_prob with the verified data
+        // The frontend can now safely call vault::set_withdrawable_balance with the
+        // signed user, maker, protocol and referrer balances, and world::update_prob
+        // with the verified data
+    }
+
+    // ============================================================
+    // BATCHED BETS - One root signature per batch
+    // ============================================================
+
+    /// Check the TEE signature over a batch root once; bets of the batch are then
+    /// submitted with `submit_bet_in_batch` and an inclusion proof
+    public entry fun verify_batch_root<T>(
+        enclave: &Enclave<T>,
+        roots: &mut VerifiedRoots,
+        root: vector<u8>,
+        leaf_count: u64,
+        timestamp_ms: u64,
+        sig: vector<u8>,
+        _ctx: &mut TxContext,
+    ) {
+        if (table::contains(&roots.roots, root)) {
+            return
+        };
+        let verified = enclave.verify_domain_signature(
+            pm_header(INTENT_BATCH_ROOT),
+            timestamp_ms,
+            BatchRoot { root, leaf_count },
+            &sig,
+        );
+        assert!(verified, EInvalidSignature);
+        table::add(&mut roots.roots, root, true);
+    }
+
+    /// Submit a bet signed as part of a batch: the bet's intent message must be a
+    /// leaf of a root checked by `verify_batch_root`. `siblings` and
+    /// `sibling_on_left` are the proof steps from the leaf up. Same replay, TTL and
+    /// sequencing rules as `submit_bet`.
+    public entry fun submit_bet_in_batch(
+        roots: &VerifiedRoots,
+        registry: &mut BetRegistry,
+        clock: &Clock,
+        // PlaceBetResponse fields
+        shares: u64,
+        new_probs: vector<u64>,
+        pool_id: u64,
+        outcome: u8,
+        debit_amount: u64,
+        credit_amount: u64,
+        user: String,
+        nonce: u64,
+        seq: u64,
+        prev_state_hash: vector<u8>,
+        user_balance: u64,
+        maker_balance: u64,
+        fee_amount: u64,
+        protocol: String,
+        protocol_fee: u64,
+        protocol_balance: u64,
+        referrer: String,
+        referrer_fee: u64,
+        referrer_balance: u64,
+        b: u64,
+        // Batch data
+        timestamp_ms: u64,
+        root: vector<u8>,
+        siblings: vector<vector<u8>>,
+        sibling_on_left: vector<bool>,
+        _ctx: &mut TxContext,
+    ) {
+        let response = PlaceBetResponse {
+            shares,
+            new_probs,
+            pool_id,
+            outcome,
+            debit_amount,
+            credit_amount,
+            user,
+            nonce,
+            seq,
+            prev_state_hash,
+            user_balance,
+            maker_balance,
+            fee_amount,
+            protocol,
+            protocol_fee,
+            protocol_balance,
+            referrer,
+            referrer_fee,
+            referrer_balance,
+            b,
+        };
+
+        assert!(table::contains(&roots.roots, root), EUnknownBatchRoot);
+        let leaf = bcs::to_bytes(
+            &enclave::create_domain_intent_message(pm_header(INTENT_PLACE_BET), timestamp_ms, response),
+        );
+        assert!(merkle_root(leaf, &siblings, &sibling_on_left) == root, ENotInBatch);
+        accept_bet(registry, clock, &response, timestamp_ms);
+    }
+
+    /// Domain header PM messages of `scope` are signed under
+    fun pm_header(scope: u8): IntentHeader {
+        enclave::create_intent_header(APP_PM, CHAIN_ID, scope)
+    }
+
+    /// Root reached from `leaf` by following its proof steps
+    fun merkle_root(leaf: vector<u8>, siblings: &vector<vector<u8>>, sibling_on_left: &vector<bool>): vector<u8> {
+        assert!(vector::length(siblings) == vector::length(sibling_on_left), ENotInBatch);
+        let mut node = vector[MERKLE_LEAF_PREFIX];
+        vector::append(&mut node, leaf);
+        let mut hash = hash::sha2_256(node);
+        let mut i = 0;
+        while (i < vector::length(siblings)) {
+            let sibling = *vector::borrow(siblings, i);
+            let mut node = vector[MERKLE_NODE_PREFIX];
+            if (*vector::borrow(sibling_on_left, i)) {
+                vector::append(&mut node, sibling);
+                vector::append(&mut node, hash);
+            } else {
+                vector::append(&mut node, hash);
+                vector::append(&mut node, sibling);
+            };
+            hash = hash::sha2_256(node);
+            i = i + 1;
+        };
+        hash
+    }
+
+    /// Record a bet whose signature has been checked: each (user, nonce) once,
+    /// within BET_TTL_MS of signing and only as the next bet of its pool
+    fun accept_bet(registry: &mut BetRegistry, clock: &Clock, response: &PlaceBetResponse, timestamp_ms: u64) {
+        assert!(clock.timestamp_ms() <= timestamp_ms + BET_TTL_MS, EBetExpired);
+        let key = BetKey { user: response.user, nonce: response.nonce };
+        assert!(!table::contains(&registry.submitted, key), EBetAlreadySubmitted);
+
+        let pool_id = response.pool_id;
+        let seq = response.seq;
+        let head = pool_head(registry, pool_id);
+        assert!(seq == head.seq + 1, EOutOfOrder);
+        assert!(response.prev_state_hash == head.state_hash, EStaleState);
+        let state_hash = hash::sha2_256(bcs::to_bytes(&PoolTransition { pool_id, seq, probs: response.new_probs }));
+
+        table::add(&mut registry.submitted, key, true);
+        if (table::contains(&registry.pool_heads, pool_id)) {
+            *table::borrow_mut(&mut registry.pool_heads, pool_id) = PoolHead { seq, state_hash };
+        } else {
+            table::add(&mut registry.pool_heads, pool_id, PoolHead { seq, state_hash });
+        };
+        event::emit(BetSubmitted {
+            user: response.user,
+            nonce: response.nonce,
+            pool_id,
+            outcome: response.outcome,
+            shares: response.shares,
+        });
     }
 
     /// Resolve market with TEE-signed proof
@@ -142,8 +462,8 @@ module pm::pm {
         };
 
         // Verify TEE signature
-        let verified = enclave.verify_signature(
-            INTENT_RESOLVE,
+        let verified = enclave.verify_domain_signature(
+            pm_header(INTENT_RESOLVE),
             timestamp_ms,
             response,
             &sig,
@@ -151,6 +471,78 @@ module pm::pm {
         assert!(verified, EInvalidSignature);
     }
 
+    /// Settle one payout batch with TEE-signed proof.
+    /// Each (pool_id, batch_index) can only be settled once.
+    public entry fun settle_payout_batch<T>(
+        enclave: &Enclave<T>,
+        settlement: &mut Settlement,
+        // PayoutBatch fields
+        pool_id: u64,
+        winning_outcome: u8,
+        batch_index: u64,
+        total_batches: u64,
+        payout_users: vector<String>,
+        payout_amounts: vector<u64>,
+        batch_payout: u64,
+        // Signature data
+        timestamp_ms: u64,
+        sig: vector<u8>,
+        _ctx: &mut TxContext,
+    ) {
+        let len = vector::length(&payout_users);
+        assert!(len == vector::length(&payout_amounts), EBatchLengthMismatch);
+
+        let key = BatchKey { pool_id, batch_index };
+        assert!(!table::contains(&settlement.settled, key), EBatchAlreadySettled);
+
+        let mut payouts = vector::empty<Payout>();
+        let mut i = 0;
+        while (i < len) {
+            let user = *vector::borrow(&payout_users, i);
+            let amount = *vector::borrow(&payout_amounts, i);
+            vector::push_back(&mut payouts, Payout { user, amount });
+            i = i + 1;
+        };
+
+        let batch = PayoutBatch {
+            pool_id,
+            winning_outcome,
+            batch_index,
+            total_batches,
+            payouts,
+            batch_payout,
+        };
+
+        let verified = enclave.verify_domain_signature(
+            pm_header(INTENT_PAYOUT_BATCH),
+            timestamp_ms,
+            batch,
+            &sig,
+        );
+        assert!(verified, EInvalidSignature);
+
+        table::add(&mut settlement.settled, key, true);
+    }
+
+    /// Last applied bet of a pool (sequence 0 and the genesis hash before its first bet)
+    public fun pool_head(registry: &BetRegistry, pool_id: u64): PoolHead {
+        if (table::contains(&registry.pool_heads, pool_id)) {
+            *table::borrow(&registry.pool_heads, pool_id)
+        } else {
+            PoolHead { seq: 0, state_hash: GENESIS_STATE_HASH }
+        }
+    }
+
+    /// Whether a bet with this (user, nonce) has already been submitted
+    public fun is_bet_submitted(registry: &BetRegistry, user: String, nonce: u64): bool {
+        table::contains(&registry.submitted, BetKey { user, nonce })
+    }
+
+    /// Whether a payout batch has already been settled
+    public fun is_batch_settled(settlement: &Settlement, pool_id: u64, batch_index: u64): bool {
+        table::contains(&settlement.settled, BatchKey { pool_id, batch_index })
+    }
+
     // ============================================================
     // TEST HELPERS
     // ============================================================
@@ -159,4 +551,293 @@ module pm::pm {
     public fun init_for_testing(ctx: &mut TxContext) {
         init(PM {}, ctx);
     }
+
+    // ============================================================
+    // TESTS
+    // ============================================================
+    // Signatures below are of the Rust enclave's Ed25519 key from secret key [1; 32],
+    // over testnet domain messages signed at TEST_TIMESTAMP_MS (see `test_bet`).
+
+    #[test_only]
+    const TEST_ENCLAVE_PK: vector<u8> = x"8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c";
+    #[test_only]
+    const TEST_TIMESTAMP_MS: u64 = 1744038900000;
+    #[test_only]
+    // `test_bet(1, 1, GENESIS_STATE_HASH)` under PlaceBet
+    const BET_SIG: vector<u8> = x"1d4e9a47fca3077e5e682c8c774224712ecc774acd3ba8ed6506aaf948fffd77b15bdf7150df7e45747f6d736a8f510947895c638d8df6753e51182c5038f606";
+    #[test_only]
+    // The same bet signed for mainnet
+    const BET_MAINNET_SIG: vector<u8> = x"47f76c4d5a2acb16fa5f604a8bcd71c507de910879f7823ec60fb1abf8568c06c7ce66c0120e66cdeece1eaae89478db338712d221ae95ada7538b4225a2b806";
+    #[test_only]
+    // Root of a batch holding only `test_bet(2, 1, GENESIS_STATE_HASH)`
+    const BATCH_ROOT: vector<u8> = x"dc62ae979dd68227ff178d36e58b287bc3fd0cf20c87d24114371dd2ad60529f";
+    #[test_only]
+    const BATCH_ROOT_SIG: vector<u8> = x"4f9b86e80d354eee57d49d4362ce0dd50b181e562b5472aeee4caad1931a92c90099370c912fd8258ae89fa5ea02f2b62d3b733c40bc20c7819d4e265823d90c";
+    #[test_only]
+    // Batch 0 of 1 of pool 1, paying 1000 to 0xuser1
+    const PAYOUT_BATCH_SIG: vector<u8> = x"d7f3885c73cc4fb409b9940e4588d9b641972777dd9f8fbe54247ac30aea68705a941d60ada36ccef668640df67137adb8c5e929da35c1072939737d0540000b";
+
+    #[test_only]
+    /// Publish, register the test enclave and set the clock to TEST_TIMESTAMP_MS
+    fun setup(): (Scenario, Clock) {
+        let mut scenario = test_scenario::begin(@0x1);
+        init_for_testing(scenario.ctx());
+        scenario.next_tx(@0x1);
+        let config = scenario.take_shared<EnclaveConfig<PM>>();
+        enclave::register_enclave_debug(&config, TEST_ENCLAVE_PK, scenario.ctx());
+        test_scenario::return_shared(config);
+        scenario.next_tx(@0x1);
+        let mut clock = sui::clock::create_for_testing(scenario.ctx());
+        clock.set_for_testing(TEST_TIMESTAMP_MS);
+        (scenario, clock)
+    }
+
+    #[test_only]
+    fun finish(scenario: Scenario, clock: Clock) {
+        clock.destroy_for_testing();
+        scenario.end();
+    }
+
+    #[test_only]
+    /// A bet of 0xuser1 on pool 1, as signed in the vectors above
+    fun test_bet(nonce: u64, seq: u64, prev_state_hash: vector<u8>): PlaceBetResponse {
+        PlaceBetResponse {
+            shares: 1000,
+            new_probs: vector[6000, 4000],
+            pool_id: 1,
+            outcome: 0,
+            debit_amount: 500_000,
+            credit_amount: 5000,
+            user: b"0xuser1".to_string(),
+            nonce,
+            seq,
+            prev_state_hash,
+            user_balance: 1_500_000,
+            maker_balance: 9_000_000,
+            fee_amount: 10_000,
+            protocol: b"".to_string(),
+            protocol_fee: 0,
+            protocol_balance: 0,
+            referrer: b"".to_string(),
+            referrer_fee: 0,
+            referrer_balance: 0,
+            b: 1_000_000,
+        }
+    }
+
+    #[test_only]
+    fun submit(scenario: &mut Scenario, clock: &Clock, bet: PlaceBetResponse, sig: vector<u8>) {
+        let enclave = scenario.take_shared<Enclave<PM>>();
+        let mut registry = scenario.take_shared<BetRegistry>();
+        let PlaceBetResponse {
+            shares, new_probs, pool_id, outcome, debit_amount, credit_amount, user, nonce, seq, prev_state_hash,
+            user_balance, maker_balance, fee_amount, protocol, protocol_fee, protocol_balance, referrer, referrer_fee,
+            referrer_balance, b,
+        } = bet;
+        submit_bet(
+            &enclave, &mut registry, clock, shares, new_probs, pool_id, outcome, debit_amount, credit_amount, user,
+            nonce, seq, prev_state_hash, user_balance, maker_balance, fee_amount, protocol, protocol_fee,
+            protocol_balance, referrer, referrer_fee, referrer_balance, b, TEST_TIMESTAMP_MS, sig, scenario.ctx(),
+        );
+        test_scenario::return_shared(enclave);
+        test_scenario::return_shared(registry);
+    }
+
+    #[test_only]
+    fun submit_in_batch(scenario: &mut Scenario, clock: &Clock, bet: PlaceBetResponse, root: vector<u8>) {
+        let roots = scenario.take_shared<VerifiedRoots>();
+        let mut registry = scenario.take_shared<BetRegistry>();
+        let PlaceBetResponse {
+            shares, new_probs, pool_id, outcome, debit_amount, credit_amount, user, nonce, seq, prev_state_hash,
+            user_balance, maker_balance, fee_amount, protocol, protocol_fee, protocol_balance, referrer, referrer_fee,
+            referrer_balance, b,
+        } = bet;
+        submit_bet_in_batch(
+            &roots, &mut registry, clock, shares, new_probs, pool_id, outcome, debit_amount, credit_amount, user,
+            nonce, seq, prev_state_hash, user_balance, maker_balance, fee_amount, protocol, protocol_fee,
+            protocol_balance, referrer, referrer_fee, referrer_balance, b, TEST_TIMESTAMP_MS, root, vector[],
+            vector[], scenario.ctx(),
+        );
+        test_scenario::return_shared(roots);
+        test_scenario::return_shared(registry);
+    }
+
+    #[test_only]
+    fun verify_root(scenario: &mut Scenario, leaf_count: u64, sig: vector<u8>) {
+        let enclave = scenario.take_shared<Enclave<PM>>();
+        let mut roots = scenario.take_shared<VerifiedRoots>();
+        verify_batch_root(&enclave, &mut roots, BATCH_ROOT, leaf_count, TEST_TIMESTAMP_MS, sig, scenario.ctx());
+        test_scenario::return_shared(enclave);
+        test_scenario::return_shared(roots);
+    }
+
+    #[test_only]
+    fun settle(scenario: &mut Scenario, payout_amounts: vector<u64>, batch_payout: u64) {
+        let enclave = scenario.take_shared<Enclave<PM>>();
+        let mut settlement = scenario.take_shared<Settlement>();
+        settle_payout_batch(
+            &enclave, &mut settlement, 1, 0, 0, 1, vector[b"0xuser1".to_string()], payout_amounts, batch_payout,
+            TEST_TIMESTAMP_MS, PAYOUT_BATCH_SIG, scenario.ctx(),
+        );
+        test_scenario::return_shared(enclave);
+        test_scenario::return_shared(settlement);
+    }
+
+    #[test]
+    fun test_submit_signed_bet() {
+        let (mut scenario, clock) = setup();
+        submit(&mut scenario, &clock, test_bet(1, 1, GENESIS_STATE_HASH), BET_SIG);
+        scenario.next_tx(@0x1);
+        let registry = scenario.take_shared<BetRegistry>();
+        assert!(is_bet_submitted(&registry, b"0xuser1".to_string(), 1), 0);
+        test_scenario::return_shared(registry);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EInvalidSignature)]
+    fun test_submit_bet_rejects_altered_bet() {
+        let (mut scenario, clock) = setup();
+        let mut bet = test_bet(1, 1, GENESIS_STATE_HASH);
+        bet.shares = 2000;
+        submit(&mut scenario, &clock, bet, BET_SIG);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EInvalidSignature)]
+    fun test_submit_bet_rejects_other_chain() {
+        let (mut scenario, clock) = setup();
+        submit(&mut scenario, &clock, test_bet(1, 1, GENESIS_STATE_HASH), BET_MAINNET_SIG);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EBetExpired)]
+    fun test_submit_bet_rejects_expired() {
+        let (mut scenario, mut clock) = setup();
+        clock.set_for_testing(TEST_TIMESTAMP_MS + BET_TTL_MS + 1);
+        submit(&mut scenario, &clock, test_bet(1, 1, GENESIS_STATE_HASH), BET_SIG);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EBetAlreadySubmitted)]
+    fun test_submit_bet_rejects_replay() {
+        let (mut scenario, clock) = setup();
+        submit(&mut scenario, &clock, test_bet(1, 1, GENESIS_STATE_HASH), BET_SIG);
+        scenario.next_tx(@0x1);
+        submit(&mut scenario, &clock, test_bet(1, 1, GENESIS_STATE_HASH), BET_SIG);
+        finish(scenario, clock);
+    }
+
+    #[test]
+    fun test_bets_apply_in_sequence() {
+        let (mut scenario, clock) = setup();
+        let mut registry = scenario.take_shared<BetRegistry>();
+        accept_bet(&mut registry, &clock, &test_bet(1, 1, GENESIS_STATE_HASH), TEST_TIMESTAMP_MS);
+        let state_hash = hash::sha2_256(bcs::to_bytes(&PoolTransition { pool_id: 1, seq: 1, probs: vector[6000, 4000] }));
+        accept_bet(&mut registry, &clock, &test_bet(2, 2, state_hash), TEST_TIMESTAMP_MS);
+        assert!(pool_head(&registry, 1).seq == 2, 0);
+        test_scenario::return_shared(registry);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EOutOfOrder)]
+    fun test_bet_rejects_skipped_seq() {
+        let (mut scenario, clock) = setup();
+        let mut registry = scenario.take_shared<BetRegistry>();
+        accept_bet(&mut registry, &clock, &test_bet(1, 2, GENESIS_STATE_HASH), TEST_TIMESTAMP_MS);
+        test_scenario::return_shared(registry);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EStaleState)]
+    fun test_bet_rejects_stale_state() {
+        let (mut scenario, clock) = setup();
+        let mut registry = scenario.take_shared<BetRegistry>();
+        accept_bet(&mut registry, &clock, &test_bet(1, 1, GENESIS_STATE_HASH), TEST_TIMESTAMP_MS);
+        // Priced from genesis again instead of the first bet's state
+        accept_bet(&mut registry, &clock, &test_bet(2, 2, GENESIS_STATE_HASH), TEST_TIMESTAMP_MS);
+        test_scenario::return_shared(registry);
+        finish(scenario, clock);
+    }
+
+    #[test]
+    fun test_submit_bet_in_batch() {
+        let (mut scenario, clock) = setup();
+        verify_root(&mut scenario, 1, BATCH_ROOT_SIG);
+        scenario.next_tx(@0x1);
+        submit_in_batch(&mut scenario, &clock, test_bet(2, 1, GENESIS_STATE_HASH), BATCH_ROOT);
+        scenario.next_tx(@0x1);
+        let registry = scenario.take_shared<BetRegistry>();
+        assert!(is_bet_submitted(&registry, b"0xuser1".to_string(), 2), 0);
+        test_scenario::return_shared(registry);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EInvalidSignature)]
+    fun test_batch_root_rejects_bad_signature() {
+        let (mut scenario, clock) = setup();
+        verify_root(&mut scenario, 2, BATCH_ROOT_SIG);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EUnknownBatchRoot)]
+    fun test_batch_bet_rejects_unverified_root() {
+        let (mut scenario, clock) = setup();
+        submit_in_batch(&mut scenario, &clock, test_bet(2, 1, GENESIS_STATE_HASH), BATCH_ROOT);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = ENotInBatch)]
+    fun test_batch_bet_rejects_bet_outside_batch() {
+        let (mut scenario, clock) = setup();
+        verify_root(&mut scenario, 1, BATCH_ROOT_SIG);
+        scenario.next_tx(@0x1);
+        submit_in_batch(&mut scenario, &clock, test_bet(3, 1, GENESIS_STATE_HASH), BATCH_ROOT);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EInvalidSignature)]
+    fun test_resolve_rejects_bad_signature() {
+        let (mut scenario, clock) = setup();
+        let enclave = scenario.take_shared<Enclave<PM>>();
+        resolve_market(
+            &enclave, true, 1, 0, vector[b"0xuser1".to_string()], vector[1000], 1000, TEST_TIMESTAMP_MS,
+            PAYOUT_BATCH_SIG, scenario.ctx(),
+        );
+        test_scenario::return_shared(enclave);
+        finish(scenario, clock);
+    }
+
+    #[test]
+    fun test_settle_payout_batch() {
+        let (mut scenario, clock) = setup();
+        settle(&mut scenario, vector[1000], 1000);
+        scenario.next_tx(@0x1);
+        let settlement = scenario.take_shared<Settlement>();
+        assert!(is_batch_settled(&settlement, 1, 0), 0);
+        test_scenario::return_shared(settlement);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EBatchAlreadySettled)]
+    fun test_settle_rejects_settled_batch() {
+        let (mut scenario, clock) = setup();
+        settle(&mut scenario, vector[1000], 1000);
+        scenario.next_tx(@0x1);
+        settle(&mut scenario, vector[1000], 1000);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EBatchLengthMismatch)]
+    fun test_settle_rejects_length_mismatch() {
+        let (mut scenario, clock) = setup();
+        settle(&mut scenario, vector[1000, 1000], 1000);
+        finish(scenario, clock);
+    }
+
+    #[test, expected_failure(abort_code = EInvalidSignature)]
+    fun test_settle_rejects_bad_signature() {
+        let (mut scenario, clock) = setup();
+        settle(&mut scenario, vector[2000], 2000);
+        finish(scenario, clock);
+    }
 }
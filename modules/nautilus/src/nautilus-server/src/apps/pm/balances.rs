@@ -0,0 +1,217 @@
+//! Enclave-side vault balances for PM
+//!
+//! Deposits and withdrawals happen on chain in the vault contract. The host relays
+//! each resulting event signed by the vault relayer key (`VAULT_RELAYER_PK`, hex
+//! Ed25519 public key); the enclave checks the signature, applies every event id
+//! once and keeps a balance per user. The same key signs the other on-chain facts
+//! the host reports, bet confirmations and pool syncs. A bet is only signed if the bettor can cover
+//! its debit, and the signed response carries both post-trade balances so the
+//! chain can set them directly.
+//!
+//! Amounts are scaled by 10^6 like the rest of the app.
+
+use super::pending::PendingBet;
+use super::{PlaceBetRequest, PlaceBetResponse, ResolveResponse};
+use crate::EnclaveError;
+use fastcrypto::ed25519::{Ed25519PublicKey, Ed25519Signature};
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::traits::{ToFromBytes, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum VaultEventKind {
+    Deposit = 0,
+    Withdraw = 1,
+}
+
+/// A vault deposit or withdrawal as emitted on chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultEvent {
+    pub event_id: String,          // Transaction digest and event index, unique per event
+    pub kind: VaultEventKind,
+    pub user: String,
+    pub amount: u64,
+}
+
+/// A vault event with the relayer's hex signature over its BCS encoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedVaultEvent {
+    pub event: VaultEvent,
+    pub signature: String,
+}
+
+/// Load the relayer key from `VAULT_RELAYER_PK`. Vault events are refused when unset.
+pub fn relayer_pk_from_env() -> Option<Ed25519PublicKey> {
+    let pk_hex = std::env::var("VAULT_RELAYER_PK").ok()?;
+    Hex::decode(&pk_hex)
+        .ok()
+        .and_then(|bytes| Ed25519PublicKey::from_bytes(&bytes).ok())
+}
+
+/// Check the relayer signature of an event
+pub fn verify_vault_event(pk: &Ed25519PublicKey, signed: &SignedVaultEvent) -> Result<(), EnclaveError> {
+    verify_relayer_signature(pk, &signed.event, &signed.signature)
+}
+
+/// Check a hex relayer signature over the BCS encoding of `message`
+pub fn verify_relayer_signature<T: Serialize>(
+    pk: &Ed25519PublicKey,
+    message: &T,
+    signature: &str,
+) -> Result<(), EnclaveError> {
+    let msg = bcs::to_bytes(message)
+        .map_err(|e| EnclaveError::GenericError(format!("Encode error: {e}")))?;
+    let sig_bytes = Hex::decode(signature)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid signature hex: {e}")))?;
+    let sig = Ed25519Signature::from_bytes(&sig_bytes)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid signature: {e}")))?;
+    pk.verify(&msg, &sig)
+        .map_err(|_| EnclaveError::GenericError("Bad vault relayer signature".into()))
+}
+
+/// Balance of every user plus the vault events already applied
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Balances {
+    balances: HashMap<String, u64>,
+    applied_events: HashSet<String>,
+}
+
+impl Balances {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn balance(&self, user: &str) -> u64 {
+        self.balances.get(user).copied().unwrap_or(0)
+    }
+
+    pub fn credit(&mut self, user: &str, amount: u64) -> u64 {
+        let balance = self.balances.entry(user.to_string()).or_insert(0);
+        *balance += amount;
+        *balance
+    }
+
+    /// Refuse an `amount` the user's balance cannot cover
+    pub fn check_funds(&self, user: &str, amount: u64) -> Result<(), EnclaveError> {
+        let available = self.balance(user);
+        if available < amount {
+            return Err(EnclaveError::GenericError(format!(
+                "Insufficient balance for {user}: {available} available, {amount} required"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Take `amount` from `user`, refusing if the balance cannot cover it
+    pub fn debit(&mut self, user: &str, amount: u64) -> Result<u64, EnclaveError> {
+        self.check_funds(user, amount)?;
+        let available = self.balance(user);
+        self.balances.insert(user.to_string(), available - amount);
+        Ok(available - amount)
+    }
+
+    /// Apply a vault event once, returning the user's new balance. A withdrawal
+    /// already happened on chain, so it is applied even if it exceeds the balance.
+    pub fn apply_event(&mut self, event: &VaultEvent) -> Result<u64, EnclaveError> {
+        if !self.applied_events.insert(event.event_id.clone()) {
+            return Err(EnclaveError::GenericError(format!(
+                "Vault event {} was already applied",
+                event.event_id
+            )));
+        }
+        Ok(match event.kind {
+            VaultEventKind::Deposit => self.credit(&event.user, event.amount),
+            VaultEventKind::Withdraw => {
+                let balance = self.balance(&event.user).saturating_sub(event.amount);
+                self.balances.insert(event.user.clone(), balance);
+                balance
+            }
+        })
+    }
+
+    /// Debit the bettor, credit the protocol and referrer fees and fill the
+    /// post-trade balances into the response. The stake and the LPs' fee share stay
+    /// in the pool until resolution.
+    pub fn settle_bet(&mut self, req: &PlaceBetRequest, response: &mut PlaceBetResponse) -> Result<(), EnclaveError> {
+        self.debit(&req.user, response.debit_amount)?;
+        if response.protocol_fee > 0 {
+            self.credit(&response.protocol, response.protocol_fee);
+        }
+        if response.referrer_fee > 0 {
+            self.credit(&response.referrer, response.referrer_fee);
+        }
+        response.user_balance = self.balance(&req.user);
+        response.maker_balance = self.balance(&req.maker);
+        response.protocol_balance = self.balance(&response.protocol);
+        response.referrer_balance = self.balance(&response.referrer);
+        Ok(())
+    }
+
+    /// Undo the balance changes of bets that will never execute on chain
+    pub fn refund(&mut self, bets: &[PendingBet]) {
+        for bet in bets {
+            self.credit(&bet.user, bet.cost);
+            self.take_back(&bet.protocol, bet.protocol_fee);
+            self.take_back(&bet.referrer, bet.referrer_fee);
+        }
+    }
+
+    fn take_back(&mut self, user: &str, amount: u64) {
+        if amount > 0 {
+            let balance = self.balance(user).saturating_sub(amount);
+            self.balances.insert(user.to_string(), balance);
+        }
+    }
+
+    /// Credit the winners and liquidity providers of a resolution
+    pub fn pay_out(&mut self, resolution: &ResolveResponse) {
+        for payout in &resolution.payouts {
+            self.credit(&payout.user, payout.amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastcrypto::ed25519::Ed25519KeyPair;
+    use fastcrypto::traits::{KeyPair, Signer};
+
+    fn event(event_id: &str, kind: VaultEventKind, amount: u64) -> VaultEvent {
+        VaultEvent {
+            event_id: event_id.to_string(),
+            kind,
+            user: "0xuser1".to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_events_apply_once() {
+        let mut balances = Balances::new();
+        assert_eq!(balances.apply_event(&event("0xa:0", VaultEventKind::Deposit, 5_000_000)).unwrap(), 5_000_000);
+        assert!(balances.apply_event(&event("0xa:0", VaultEventKind::Deposit, 5_000_000)).is_err());
+        assert_eq!(balances.apply_event(&event("0xb:0", VaultEventKind::Withdraw, 2_000_000)).unwrap(), 3_000_000);
+
+        assert!(balances.debit("0xuser1", 4_000_000).is_err());
+        assert_eq!(balances.debit("0xuser1", 3_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_relayer_signature() {
+        let kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
+        let event = event("0xa:0", VaultEventKind::Deposit, 1_000_000);
+        let sig = kp.sign(&bcs::to_bytes(&event).unwrap());
+        let mut signed = SignedVaultEvent {
+            event,
+            signature: Hex::encode(sig),
+        };
+        assert!(verify_vault_event(kp.public(), &signed).is_ok());
+
+        signed.event.amount += 1;
+        assert!(verify_vault_event(kp.public(), &signed).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Trading fees for PM
+//!
+//! Each pool charges a taker fee in basis points of the amount staked on a bet
+//! (bets are the only trades: shares are held to resolution). The fee is
+//! split between the pool's liquidity providers, the protocol and, when the trade
+//! names one, a referrer; the protocol and referrer shares are basis points of the
+//! fee and the providers keep the rest (including the referrer share of unreferred
+//! trades).
+//! The protocol recipient is fixed when a pool's fees are set, from
+//! `PM_PROTOCOL_ADDRESS`.
+
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+
+/// Basis points in one
+pub const BPS: u64 = 10_000;
+
+/// Highest taker fee a pool can charge (10%)
+pub const MAX_TAKER_BPS: u64 = 1_000;
+
+/// Load the protocol fee recipient from `PM_PROTOCOL_ADDRESS`
+pub fn protocol_address_from_env() -> Option<String> {
+    std::env::var("PM_PROTOCOL_ADDRESS").ok().filter(|address| !address.is_empty())
+}
+
+/// Fee settings of a pool
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FeeConfig {
+    pub taker_bps: u64,            // Fee charged on each trade, in bps of the amount
+    pub protocol_share_bps: u64,   // Protocol's share of the fee, in bps of the fee
+    pub referrer_share_bps: u64,   // Referrer's share of the fee, in bps of the fee
+    pub protocol: String,          // Protocol fee recipient (empty without a protocol share)
+}
+
+/// How the fee of one trade is divided
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FeeSplit {
+    pub total: u64,
+    pub maker: u64,                // Liquidity providers' share
+    pub protocol: u64,
+    pub referrer: u64,
+}
+
+impl FeeConfig {
+    pub fn validate(&self) -> Result<(), EnclaveError> {
+        if self.taker_bps > MAX_TAKER_BPS {
+            return Err(EnclaveError::GenericError(format!(
+                "Taker fee {} bps exceeds the {MAX_TAKER_BPS} bps cap",
+                self.taker_bps
+            )));
+        }
+        if self.protocol_share_bps + self.referrer_share_bps > BPS {
+            return Err(EnclaveError::GenericError(
+                "Protocol and referrer shares exceed the whole fee".into(),
+            ));
+        }
+        if self.protocol_share_bps > 0 && self.protocol.is_empty() {
+            return Err(EnclaveError::GenericError(
+                "A protocol share needs a protocol recipient".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fee on a bet staking `amount`. Computed in u128: `amount` is client-chosen.
+    pub fn split(&self, amount: u64, referred: bool) -> FeeSplit {
+        let total = bps_of(amount, self.taker_bps);
+        let protocol = bps_of(total, self.protocol_share_bps);
+        let referrer = if referred { bps_of(total, self.referrer_share_bps) } else { 0 };
+        FeeSplit {
+            total,
+            maker: total - protocol - referrer,
+            protocol,
+            referrer,
+        }
+    }
+}
+
+/// `bps` basis points of `amount`, at most `amount` for a validated config
+fn bps_of(amount: u64, bps: u64) -> u64 {
+    (amount as u128 * bps.min(BPS) as u128 / BPS as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FeeConfig {
+        FeeConfig {
+            taker_bps: 200,
+            protocol_share_bps: 2_500,
+            referrer_share_bps: 1_000,
+            protocol: "0xprotocol".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fee_split() {
+        let split = config().split(1_000_000, true);
+        assert_eq!(split, FeeSplit { total: 20_000, maker: 13_000, protocol: 5_000, referrer: 2_000 });
+
+        // The maker keeps the referrer share of unreferred trades
+        let split = config().split(1_000_000, false);
+        assert_eq!((split.maker, split.referrer), (15_000, 0));
+
+        assert_eq!(FeeConfig::default().split(1_000_000, true), FeeSplit::default());
+
+        // No overflow on any amount
+        let split = config().split(u64::MAX, true);
+        assert_eq!(split.total, (u64::MAX as u128 * 200 / BPS as u128) as u64);
+        assert_eq!(split.maker + split.protocol + split.referrer, split.total);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config().validate().is_ok());
+        assert!(FeeConfig { taker_bps: MAX_TAKER_BPS + 1, ..config() }.validate().is_err());
+        assert!(FeeConfig { referrer_share_bps: 8_000, ..config() }.validate().is_err());
+        assert!(FeeConfig { protocol: String::new(), ..config() }.validate().is_err());
+    }
+}
@@ -0,0 +1,676 @@
+//! Hash-chained trade ledger for PM
+//!
+//! Every bet, confirmation, sync, vault event, liquidity change and resolution is appended as an entry that commits to
+//! the hash of the previous entry. The enclave signs the ledger head every
+//! `LEDGER_CHECKPOINT_INTERVAL` entries (and on demand via /ledger/head).
+//! `replay` rebuilds the position store from an exported ledger and checks that
+//! it reproduces every signed response. Key changes are entries too: a rotation
+//! records the old key's handoff and every boot records the key it started
+//! with, so each signature is checked against the key current at its entry.
+
+use super::balances::{Balances, VaultEvent};
+use super::fees::FeeConfig;
+use super::maker::{LiquiditySchedule, MakerBook};
+use super::pending::{expire_and_roll_back, PendingBet, PendingBets};
+use super::sequence::PoolSequencer;
+use super::state::{PositionRepository, PositionStore};
+use super::{compute_resolution, quote_bet, IntentScope, PlaceBetRequest, PlaceBetResponse};
+use crate::common::{EnclavePublicKey, ProcessedDataResponse, SignatureScheme};
+use crate::intent::{ChainId, DomainIntentMessage};
+use crate::rotation::{verify_handoff, SignedHandoff};
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Sign the ledger head every this many entries
+pub const LEDGER_CHECKPOINT_INTERVAL: usize = 100;
+
+/// Hash of the (empty) entry before the first one
+pub const GENESIS_HASH: [u8; 32] = [0u8; 32];
+
+/// A state transition recorded in the ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LedgerEvent {
+    /// A bet was priced, and signed unless it was left for a batch root
+    BetPlaced {
+        request: PlaceBetRequest,
+        response: Box<PlaceBetResponse>,
+        signature: Option<String>,
+    },
+    /// A pool was resolved and its payouts signed
+    Resolved {
+        pool_id: u64,
+        winning_outcome: u8,
+        signature: String,
+    },
+    /// A placed bet executed on chain and became a position
+    Confirmed {
+        user: String,
+        nonce: u64,
+        tx_digest: String,
+    },
+    /// A pool's sequence state was set to its on-chain state
+    Synced {
+        pool_id: u64,
+        seq: u64,
+        probs: Vec<u64>,
+    },
+    /// A relayed vault deposit or withdrawal was applied
+    Vault { event: VaultEvent },
+    /// A provider moved part of its balance into a pool's liquidity
+    LiquidityAdded {
+        pool_id: u64,
+        provider: String,
+        amount: u64,
+        outcomes: u8,
+        nonce: u64,
+    },
+    /// A provider burned LP shares for its part of a pool's subsidy and fees
+    LiquidityRemoved {
+        pool_id: u64,
+        provider: String,
+        shares: u64,
+        nonce: u64,
+    },
+    /// A maker changed how its pool's `b` follows volume
+    ScheduleSet {
+        pool_id: u64,
+        maker: String,
+        schedule: LiquiditySchedule,
+        nonce: u64,
+    },
+    /// A maker changed its pool's fee settings
+    FeesSet {
+        pool_id: u64,
+        maker: String,
+        fees: FeeConfig,
+        nonce: u64,
+    },
+    /// The enclave booted, fresh or from a snapshot, with this signing key
+    EnclaveStarted {
+        public_key: Vec<u8>,
+        scheme: SignatureScheme,
+    },
+    /// The signing key was rotated; signed by the retired key
+    KeyRotated { handoff: SignedHandoff },
+}
+
+/// One ledger entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub seq: u64,
+    pub timestamp_ms: u64,
+    pub prev_hash: Vec<u8>,
+    pub event: LedgerEvent,
+}
+
+impl LedgerEntry {
+    /// SHA-256 over the BCS encoding of the entry
+    pub fn hash(&self) -> [u8; 32] {
+        let bytes = bcs::to_bytes(self).expect("should not fail");
+        Sha256::digest(bytes).digest
+    }
+}
+
+/// Ledger head signed by the enclave
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerHead {
+    pub entry_count: u64,
+    pub head_hash: Vec<u8>,
+}
+
+/// Entries plus signed checkpoints, as served by /ledger
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LedgerExport {
+    pub entries: Vec<LedgerEntry>,
+    pub checkpoints: Vec<ProcessedDataResponse<DomainIntentMessage<LedgerHead>>>,
+}
+
+impl LedgerExport {
+    /// Head of the exported entries, not re-checking their hash chain
+    pub fn head(&self) -> LedgerHead {
+        LedgerHead {
+            entry_count: self.entries.len() as u64,
+            head_hash: self.entries.last().map(|entry| entry.hash()).unwrap_or(GENESIS_HASH).to_vec(),
+        }
+    }
+
+    /// Whether these entries start with the `head.entry_count` entries `head` was taken over
+    pub fn extends(&self, head: &LedgerHead) -> bool {
+        match head.entry_count {
+            0 => true,
+            n => self
+                .entries
+                .get(n as usize - 1)
+                .is_some_and(|entry| entry.hash().to_vec() == head.head_hash),
+        }
+    }
+}
+
+/// Append-only ledger held in the TEE
+#[derive(Debug, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+    checkpoints: Vec<ProcessedDataResponse<DomainIntentMessage<LedgerHead>>>,
+    head_hash: Option<[u8; 32]>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rebuild from an export, re-checking the hash chain
+    pub fn from_export(export: LedgerExport) -> Result<Self, EnclaveError> {
+        let head_hash = verify_chain(&export.entries)?;
+        Ok(Self {
+            entries: export.entries,
+            checkpoints: export.checkpoints,
+            head_hash,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Append an event chained to the current head
+    pub fn append(&mut self, event: LedgerEvent, timestamp_ms: u64) -> &LedgerEntry {
+        let entry = LedgerEntry {
+            seq: self.entries.len() as u64,
+            timestamp_ms,
+            prev_hash: self.head_hash.unwrap_or(GENESIS_HASH).to_vec(),
+            event,
+        };
+        self.head_hash = Some(entry.hash());
+        self.entries.push(entry);
+        self.entries.last().expect("just pushed")
+    }
+
+    pub fn head(&self) -> LedgerHead {
+        LedgerHead {
+            entry_count: self.entries.len() as u64,
+            head_hash: self.head_hash.unwrap_or(GENESIS_HASH).to_vec(),
+        }
+    }
+
+    pub fn add_checkpoint(&mut self, checkpoint: ProcessedDataResponse<DomainIntentMessage<LedgerHead>>) {
+        self.checkpoints.push(checkpoint);
+    }
+
+    pub fn export(&self) -> LedgerExport {
+        LedgerExport {
+            entries: self.entries.clone(),
+            checkpoints: self.checkpoints.clone(),
+        }
+    }
+}
+
+/// Check seq numbers and prev-hash links, returning the head hash
+pub fn verify_chain(entries: &[LedgerEntry]) -> Result<Option<[u8; 32]>, EnclaveError> {
+    let mut head_hash: Option<[u8; 32]> = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.seq != i as u64 {
+            return Err(EnclaveError::GenericError(format!(
+                "Ledger entry {i} has seq {}",
+                entry.seq
+            )));
+        }
+        if entry.prev_hash != head_hash.unwrap_or(GENESIS_HASH) {
+            return Err(EnclaveError::GenericError(format!(
+                "Ledger entry {i} does not chain to the previous entry"
+            )));
+        }
+        head_hash = Some(entry.hash());
+    }
+    Ok(head_hash)
+}
+
+/// Result of a successful replay
+pub struct ReplayReport {
+    pub entry_count: u64,
+    pub checkpoint_count: u64,
+    pub head_hash: String,
+    pub store: PositionStore,
+}
+
+/// Verify a hex signature over the PM `DomainIntentMessage` of `intent` on `chain_id`
+fn verify_intent<T: Serialize>(
+    pk: &EnclavePublicKey,
+    data: T,
+    timestamp_ms: u64,
+    intent: IntentScope,
+    chain_id: ChainId,
+    signature: &str,
+) -> Result<(), EnclaveError> {
+    let msg = bcs::to_bytes(&DomainIntentMessage { header: intent.header(chain_id), timestamp_ms, data })
+        .map_err(|e| EnclaveError::GenericError(format!("Encode error: {e}")))?;
+    let sig_bytes = Hex::decode(signature)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid signature hex: {e}")))?;
+    pk.verify(&msg, &sig_bytes)
+        .map_err(|e| EnclaveError::GenericError(format!("{intent:?}: {e}")))
+}
+
+/// Mark a liquidity, fee or schedule request's (signer, nonce) used, refusing a reuse
+fn use_request_nonce(used: &mut HashSet<(String, u64)>, signer: &str, nonce: u64) -> Result<(), EnclaveError> {
+    if !used.insert((signer.to_string(), nonce)) {
+        return Err(EnclaveError::GenericError(format!("nonce {nonce} of {signer} was already used")));
+    }
+    Ok(())
+}
+
+/// Rebuild the position store from an exported ledger, recomputing every bet and
+/// resolution and checking each recorded signature and checkpoint against the
+/// enclave key current at that point. The ledger starts with the first of
+/// `trusted`; each `EnclaveStarted` key must be in `trusted` (registered from its
+/// attestation), while rotated keys are followed through their signed handoffs.
+/// Signatures must be for `chain_id`.
+pub fn replay(
+    export: &LedgerExport,
+    trusted: &[EnclavePublicKey],
+    chain_id: ChainId,
+) -> Result<ReplayReport, EnclaveError> {
+    let head_hash = verify_chain(&export.entries)?;
+    let mut pk = trusted
+        .first()
+        .cloned()
+        .ok_or_else(|| EnclaveError::GenericError("No trusted enclave key".into()))?;
+    // Key current after each number of entries, for the checkpoints
+    let mut keys_at = vec![pk.clone()];
+    let mut store = PositionStore::new();
+    let mut nonces = HashSet::new();
+    let mut request_nonces = HashSet::new();
+    let mut pending = PendingBets::new();
+    let mut sequencer = PoolSequencer::new();
+    let mut balances = Balances::new();
+    let mut makers = MakerBook::new();
+
+    for entry in &export.entries {
+        let seq = entry.seq;
+        match &entry.event {
+            LedgerEvent::BetPlaced { request, response, signature } => {
+                let dropped = expire_and_roll_back(&mut pending, &mut sequencer, entry.timestamp_ms);
+                balances.refund(&dropped);
+                makers.refund(&dropped);
+                let pool = makers
+                    .pool_for_bet(request)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                let tip = sequencer
+                    .check_fresh(request.pool_id, &request.current_probs, pool.outcomes as usize)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                balances
+                    .check_funds(&request.user, request.amount)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                let mut recomputed = quote_bet(request, &tip, pool);
+                makers
+                    .check_bet(request, &recomputed)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                balances
+                    .settle_bet(request, &mut recomputed)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                makers.add_bet(request, &recomputed);
+                if bcs::to_bytes(&recomputed).ok() != bcs::to_bytes(response).ok() {
+                    return Err(EnclaveError::GenericError(format!(
+                        "Entry {seq}: bet response does not match its request"
+                    )));
+                }
+                if let Some(signature) = signature {
+                    verify_intent(&pk, recomputed, entry.timestamp_ms, IntentScope::PlaceBet, chain_id, signature)
+                        .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                }
+                if !nonces.insert((request.user.clone(), request.nonce)) {
+                    return Err(EnclaveError::GenericError(format!(
+                        "Entry {seq}: nonce {} of {} was already used",
+                        request.nonce, request.user
+                    )));
+                }
+                let head = sequencer.advance(request.pool_id, response.new_probs.clone());
+                pending.insert(PendingBet {
+                    user: request.user.clone(),
+                    nonce: request.nonce,
+                    pool_id: request.pool_id,
+                    outcome: request.outcome,
+                    shares: response.shares,
+                    cost: response.debit_amount,
+                    maker: request.maker.clone(),
+                    credit: response.credit_amount,
+                    protocol: response.protocol.clone(),
+                    protocol_fee: response.protocol_fee,
+                    referrer: response.referrer.clone(),
+                    referrer_fee: response.referrer_fee,
+                    seq: head.seq,
+                    placed_at_ms: entry.timestamp_ms,
+                });
+            }
+            LedgerEvent::Confirmed { user, nonce, .. } => {
+                let dropped = expire_and_roll_back(&mut pending, &mut sequencer, entry.timestamp_ms);
+                balances.refund(&dropped);
+                makers.refund(&dropped);
+                let bet = pending
+                    .take(user, *nonce, entry.timestamp_ms)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                sequencer
+                    .confirm(bet.pool_id, bet.seq)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                store.add_position(bet.user, bet.pool_id, bet.outcome, bet.shares, bet.cost)?;
+            }
+            LedgerEvent::Resolved { pool_id, winning_outcome, signature } => {
+                let dropped = pending.clear_pool(*pool_id);
+                balances.refund(&dropped);
+                makers.refund(&dropped);
+                let pool = makers.close(*pool_id);
+                let resolution = compute_resolution(&store, *pool_id, *winning_outcome, pool.as_ref())?;
+                store.clear_pool(*pool_id)?;
+                balances.pay_out(&resolution);
+                sequencer.clear_pending(*pool_id);
+                verify_intent(&pk, resolution, entry.timestamp_ms, IntentScope::Resolve, chain_id, signature)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+            LedgerEvent::Synced { pool_id, seq: pool_seq, probs } => {
+                let dropped = expire_and_roll_back(&mut pending, &mut sequencer, entry.timestamp_ms);
+                balances.refund(&dropped);
+                makers.refund(&dropped);
+                sequencer
+                    .sync(*pool_id, *pool_seq, probs.clone())
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+            LedgerEvent::Vault { event } => {
+                balances
+                    .apply_event(event)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+            LedgerEvent::LiquidityAdded { pool_id, provider, amount, outcomes, nonce } => {
+                use_request_nonce(&mut request_nonces, provider, *nonce)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                balances
+                    .debit(provider, *amount)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                makers
+                    .fund(*pool_id, provider, *amount, *outcomes)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+            LedgerEvent::LiquidityRemoved { pool_id, provider, shares, nonce } => {
+                use_request_nonce(&mut request_nonces, provider, *nonce)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                let released = makers
+                    .withdraw(*pool_id, provider, *shares)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                balances.credit(provider, released);
+            }
+            LedgerEvent::ScheduleSet { pool_id, maker, schedule, nonce } => {
+                use_request_nonce(&mut request_nonces, maker, *nonce)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                makers
+                    .set_schedule(*pool_id, maker, *schedule)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+            LedgerEvent::FeesSet { pool_id, maker, fees, nonce } => {
+                use_request_nonce(&mut request_nonces, maker, *nonce)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                makers
+                    .set_fees(*pool_id, maker, fees.clone())
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+            LedgerEvent::EnclaveStarted { public_key, scheme } => {
+                let started = EnclavePublicKey::from_bytes(*scheme, public_key)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+                if !trusted.contains(&started) {
+                    return Err(EnclaveError::GenericError(format!(
+                        "Entry {seq}: enclave key {} is not trusted",
+                        Hex::encode(public_key)
+                    )));
+                }
+                pk = started;
+            }
+            LedgerEvent::KeyRotated { handoff } => {
+                pk = verify_handoff(&pk, handoff, chain_id)
+                    .map_err(|e| EnclaveError::GenericError(format!("Entry {seq}: {e}")))?;
+            }
+        }
+        keys_at.push(pk.clone());
+    }
+
+    for checkpoint in &export.checkpoints {
+        let head = &checkpoint.response.data;
+        let expected = match head.entry_count {
+            0 => GENESIS_HASH.to_vec(),
+            n => export
+                .entries
+                .get(n as usize - 1)
+                .ok_or_else(|| EnclaveError::GenericError(format!("Checkpoint at {n} is past the ledger end")))?
+                .hash()
+                .to_vec(),
+        };
+        if head.head_hash != expected {
+            return Err(EnclaveError::GenericError(format!(
+                "Checkpoint at {} does not match the ledger",
+                head.entry_count
+            )));
+        }
+        verify_intent(
+            &keys_at[head.entry_count as usize],
+            head.clone(),
+            checkpoint.response.timestamp_ms,
+            IntentScope::LedgerHead,
+            chain_id,
+            &checkpoint.signature,
+        )?;
+    }
+
+    Ok(ReplayReport {
+        entry_count: export.entries.len() as u64,
+        checkpoint_count: export.checkpoints.len() as u64,
+        head_hash: Hex::encode(head_hash.unwrap_or(GENESIS_HASH)),
+        store,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::balances::VaultEventKind;
+    use crate::intent::{to_signed_domain_response, SUI_MAINNET, SUI_TESTNET};
+    use crate::common::{EnclaveKeyPair, IntentSigner};
+    use crate::rotation::EphemeralKeys;
+
+    fn bet(user: &str, outcome: u8, nonce: u64) -> PlaceBetRequest {
+        PlaceBetRequest {
+            user: user.to_string(),
+            pool_id: 1,
+            outcome,
+            amount: 1_000_000,
+            maker: "0xmaker".to_string(),
+            current_probs: vec![1250; 8],
+            nonce,
+            referrer: None,
+        }
+    }
+
+    fn signed_ledger<S: IntentSigner>(kp: &S) -> Ledger {
+        let mut ledger = Ledger::new();
+        let mut sequencer = PoolSequencer::new();
+        let mut balances = Balances::new();
+        let mut makers = MakerBook::new();
+        for (i, user) in ["0xuser1", "0xuser2", "0xmaker"].into_iter().enumerate() {
+            let event = VaultEvent {
+                event_id: format!("0xdeposit:{i}"),
+                kind: VaultEventKind::Deposit,
+                user: user.to_string(),
+                amount: 10_000_000,
+            };
+            balances.apply_event(&event).unwrap();
+            ledger.append(LedgerEvent::Vault { event }, 1744038800000);
+        }
+        balances.debit("0xmaker", 10_000_000).unwrap();
+        makers.fund(1, "0xmaker", 10_000_000, 8).unwrap();
+        let added = LedgerEvent::LiquidityAdded { pool_id: 1, provider: "0xmaker".to_string(), amount: 10_000_000, outcomes: 8, nonce: 1 };
+        ledger.append(added, 1744038800000);
+        let fees = FeeConfig { taker_bps: 100, referrer_share_bps: 2_000, ..FeeConfig::default() };
+        makers.set_fees(1, "0xmaker", fees.clone()).unwrap();
+        ledger.append(LedgerEvent::FeesSet { pool_id: 1, maker: "0xmaker".to_string(), fees, nonce: 2 }, 1744038800000);
+        let schedule = LiquiditySchedule { alpha_bps: 5_000 };
+        makers.set_schedule(1, "0xmaker", schedule).unwrap();
+        ledger.append(LedgerEvent::ScheduleSet { pool_id: 1, maker: "0xmaker".to_string(), schedule, nonce: 3 }, 1744038800000);
+        let referred = PlaceBetRequest { referrer: Some("0xref".to_string()), ..bet("0xuser2", 5, 1) };
+        for (i, mut request) in [bet("0xuser1", 3, 1), referred].into_iter().enumerate() {
+            let ts = 1744038900000 + i as u64;
+            let tip = sequencer.tip(1);
+            if !tip.probs.is_empty() {
+                request.current_probs = tip.probs.clone();
+            }
+            let mut response = quote_bet(&request, &tip, makers.account(1).unwrap());
+            makers.check_bet(&request, &response).unwrap();
+            balances.settle_bet(&request, &mut response).unwrap();
+            makers.add_bet(&request, &response);
+            let signed = to_signed_domain_response(kp, IntentScope::PlaceBet.header(SUI_TESTNET), response, ts);
+            sequencer.advance(1, signed.response.data.new_probs.clone());
+            let confirmed = LedgerEvent::Confirmed {
+                user: request.user.clone(),
+                nonce: request.nonce,
+                tx_digest: format!("digest{i}"),
+            };
+            ledger.append(
+                LedgerEvent::BetPlaced {
+                    request,
+                    response: Box::new(signed.response.data),
+                    signature: Some(signed.signature),
+                },
+                ts,
+            );
+            ledger.append(confirmed, ts + 10);
+        }
+
+        let mut store = PositionStore::new();
+        for entry in ledger.export().entries {
+            if let LedgerEvent::BetPlaced { request, response, .. } = entry.event {
+                store
+                    .add_position(request.user, request.pool_id, request.outcome, response.shares, response.debit_amount)
+                    .unwrap();
+            }
+        }
+        let ts = 1744038900100;
+        let resolution = to_signed_domain_response(kp, IntentScope::Resolve.header(SUI_TESTNET), compute_resolution(&store, 1, 3, makers.account(1)).unwrap(), ts);
+        ledger.append(
+            LedgerEvent::Resolved { pool_id: 1, winning_outcome: 3, signature: resolution.signature },
+            ts,
+        );
+        let head = to_signed_domain_response(kp, IntentScope::LedgerHead.header(SUI_TESTNET), ledger.head(), ts);
+        ledger.add_checkpoint(head);
+        ledger
+    }
+
+    #[test]
+    fn test_replay_reproduces_signed_responses() {
+        for scheme in [SignatureScheme::Ed25519, SignatureScheme::Secp256k1] {
+            let kp = EnclaveKeyPair::generate(scheme);
+            let export = signed_ledger(&kp).export();
+
+            let report = replay(&export, &[kp.public()], SUI_TESTNET).unwrap();
+            assert_eq!(report.entry_count, 11);
+            assert_eq!(report.checkpoint_count, 1);
+            assert!(report.store.get_positions_by_pool(1).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_replay_detects_tampering() {
+        let kp = EnclaveKeyPair::generate(SignatureScheme::Ed25519);
+
+        // Inflated shares no longer match the LMSR recomputation
+        let mut export = signed_ledger(&kp).export();
+        if let LedgerEvent::BetPlaced { response, .. } = &mut export.entries[6].event {
+            response.shares += 1;
+        }
+        assert!(replay(&export, &[kp.public()], SUI_TESTNET).is_err());
+
+        // Bets cannot claim a different place in the pool sequence
+        let mut export = signed_ledger(&kp).export();
+        if let LedgerEvent::BetPlaced { response, .. } = &mut export.entries[8].event {
+            response.seq = 1;
+        }
+        assert!(replay(&export, &[kp.public()], SUI_TESTNET).is_err());
+
+        // The fee split is recomputed too
+        let mut export = signed_ledger(&kp).export();
+        if let LedgerEvent::BetPlaced { response, .. } = &mut export.entries[8].event {
+            response.referrer_fee += response.credit_amount;
+            response.credit_amount = 0;
+        }
+        assert!(replay(&export, &[kp.public()], SUI_TESTNET).is_err());
+
+        // So is the liquidity it was priced at
+        let mut export = signed_ledger(&kp).export();
+        if let LedgerEvent::BetPlaced { response, .. } = &mut export.entries[6].event {
+            response.b *= 2;
+        }
+        assert!(replay(&export, &[kp.public()], SUI_TESTNET).is_err());
+
+        // Dropping an entry breaks the hash chain
+        let mut export = signed_ledger(&kp).export();
+        export.entries.remove(7);
+        assert!(replay(&export, &[kp.public()], SUI_TESTNET).is_err());
+
+        // A bet cannot be confirmed twice
+        let mut ledger = signed_ledger(&kp);
+        let confirmed = LedgerEvent::Confirmed { user: "0xuser2".to_string(), nonce: 1, tx_digest: "again".to_string() };
+        ledger.append(confirmed, 1744038900200);
+        assert!(replay(&ledger.export(), &[kp.public()], SUI_TESTNET).is_err());
+
+        // A signed liquidity request is applied once per (signer, nonce)
+        let mut ledger = signed_ledger(&kp);
+        let added = LedgerEvent::LiquidityAdded { pool_id: 2, provider: "0xuser1".to_string(), amount: 1_000, outcomes: 2, nonce: 1 };
+        ledger.append(added.clone(), 1744038900200);
+        replay(&ledger.export(), &[kp.public()], SUI_TESTNET).unwrap();
+        ledger.append(added, 1744038900300);
+        assert!(replay(&ledger.export(), &[kp.public()], SUI_TESTNET).is_err());
+
+        // Signatures from another key are rejected
+        let other = EnclaveKeyPair::generate(SignatureScheme::Ed25519);
+        assert!(replay(&signed_ledger(&kp).export(), &[other.public()], SUI_TESTNET).is_err());
+
+        // And so are signatures for another chain
+        assert!(replay(&signed_ledger(&kp).export(), &[kp.public()], SUI_MAINNET).is_err());
+    }
+
+    #[test]
+    fn test_replay_follows_rotations_and_restarts() {
+        let keys = EphemeralKeys::new(EnclaveKeyPair::generate(SignatureScheme::Ed25519));
+        let first = keys.public();
+        let mut ledger = signed_ledger(&keys);
+        let handoff = keys.rotate(1744038900200, SUI_TESTNET);
+        ledger.append(LedgerEvent::KeyRotated { handoff }, 1744038900200);
+        ledger.add_checkpoint(to_signed_domain_response(&keys, IntentScope::LedgerHead.header(SUI_TESTNET), ledger.head(), 1744038900200));
+        // The rotated key is followed from the first one
+        let report = replay(&ledger.export(), std::slice::from_ref(&first), SUI_TESTNET).unwrap();
+        assert_eq!(report.checkpoint_count, 2);
+
+        // A handoff from a key that is not current is refused
+        let stranger = EphemeralKeys::new(EnclaveKeyPair::generate(SignatureScheme::Ed25519));
+        let mut forged = Ledger::from_export(ledger.export()).unwrap();
+        forged.append(LedgerEvent::KeyRotated { handoff: stranger.rotate(1744038900250, SUI_TESTNET) }, 1744038900250);
+        assert!(replay(&forged.export(), std::slice::from_ref(&first), SUI_TESTNET).is_err());
+
+        // A restarted enclave's key has no handoff, so it must be trusted itself
+        let restarted = EnclaveKeyPair::generate(SignatureScheme::Secp256r1);
+        let started = LedgerEvent::EnclaveStarted {
+            public_key: restarted.public_key_bytes(),
+            scheme: restarted.scheme(),
+        };
+        ledger.append(started, 1744038900300);
+        ledger.add_checkpoint(to_signed_domain_response(&restarted, IntentScope::LedgerHead.header(SUI_TESTNET), ledger.head(), 1744038900300));
+        assert!(replay(&ledger.export(), std::slice::from_ref(&first), SUI_TESTNET).is_err());
+        let report = replay(&ledger.export(), &[first.clone(), restarted.public()], SUI_TESTNET).unwrap();
+        assert_eq!(report.checkpoint_count, 3);
+
+        // Each checkpoint is checked against the key current at its head
+        let mut export = ledger.export();
+        export.checkpoints.push(to_signed_domain_response(&keys, IntentScope::LedgerHead.header(SUI_TESTNET), ledger.head(), 1744038900400));
+        assert!(replay(&export, &[first, restarted.public()], SUI_TESTNET).is_err());
+    }
+}
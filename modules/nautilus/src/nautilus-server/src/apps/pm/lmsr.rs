@@ -21,6 +21,16 @@ impl LMSR {
         self.b * sum_exp.ln()
     }
 
+    /// The market whose maker can lose at most `max_loss` over `outcomes`
+    /// outcomes, i.e. b = max_loss / ln(n)
+    pub fn for_max_loss(max_loss: f64, outcomes: usize) -> Self {
+        Self::new(max_loss / (outcomes.max(2) as f64).ln())
+    }
+
+    pub fn b(&self) -> f64 {
+        self.b
+    }
+
     /// Calculate prices (probabilities) for each outcome
     /// p_i = e^(q_i/b) / sum(e^(q_j/b))
     pub fn prices(&self, quantities: &[f64]) -> Vec<f64> {
@@ -0,0 +1,567 @@
+//! Per-pool liquidity accounting
+//!
+//! Liquidity providers escrow a subsidy from their vault balances before a pool
+//! takes bets. Stakes are held in the pool as collateral, and every signed bet
+//! adds to what the pool owes if its outcome wins. The pool's worst-case loss is
+//! the most owed on any outcome minus the collateral collected; a bet that would
+//! push it past the subsidy is refused.
+//!
+//! The subsidy sets the pool's LMSR liquidity: `b = subsidy / ln(n)`, so the
+//! `b * ln(n)` loss bound is exactly what the providers escrowed. A maker can opt
+//! into a volume-adaptive schedule, `b = max(subsidy / ln(n), alpha * volume)`,
+//! so thin markets move easily and busy ones get deeper. Past the floor the
+//! subsidy no longer bounds the loss by itself; the worst-case check below still
+//! refuses any bet it cannot cover. Pool prices are kept as probabilities and
+//! quantities are derived from them with the current `b`, so a change of `b`
+//! (liquidity added or removed, volume traded) never moves prices.
+//!
+//! The first provider fixes the pool's outcome count; trading starts from
+//! uniform prices over that many outcomes.
+//!
+//! Providers hold LP shares, minted at the pool's value per share (see
+//! `MakerAccount::lp_value`), so a new provider buys into the fees and collateral
+//! already in the pool rather than taking part of them for free. The LPs'
+//! share of each fee accrues in the pool. On resolution winners are paid first and
+//! the rest (subsidy + collateral + fees - payouts) is split pro-rata over the LP
+//! shares in the same signed payout set. The first provider is the pool's maker
+//! and sets its fees.
+//!
+//! Amounts are scaled by 10^6 like the rest of the app.
+
+use super::fees::{FeeConfig, BPS};
+use super::lmsr::LMSR;
+use super::pending::PendingBet;
+use super::{PlaceBetRequest, PlaceBetResponse};
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Payout of one share (shares are scaled by 1000)
+pub const PAYOUT_PER_SHARE: u64 = 1000;
+
+/// How a pool's `b` follows its traded volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct LiquiditySchedule {
+    pub alpha_bps: u64,            // b per unit of volume, in bps (0 keeps b fixed by the subsidy)
+}
+
+impl LiquiditySchedule {
+    pub fn validate(&self) -> Result<(), EnclaveError> {
+        if self.alpha_bps > BPS {
+            return Err(EnclaveError::GenericError(format!(
+                "Liquidity schedule of {} bps exceeds the traded volume",
+                self.alpha_bps
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Escrow, exposure and liquidity providers of a pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MakerAccount {
+    pub maker: String,             // First provider; sets the pool's fees
+    pub outcomes: u8,              // Fixed by the first provider
+    pub subsidy: u64,              // Escrowed by providers to cover losses
+    pub collateral: u64,           // Stakes collected from bettors
+    pub fees_earned: u64,          // LP share of fees, paid out at resolution
+    pub liabilities: Vec<u64>,     // Owed to winners if each outcome wins
+    pub fees: FeeConfig,
+    pub providers: BTreeMap<String, u64>, // LP shares of each provider
+    pub lp_shares: u64,
+    pub volume: u64,               // Stakes traded (after fees) by live bets
+    pub schedule: LiquiditySchedule,
+}
+
+impl MakerAccount {
+    fn new(maker: &str, outcomes: u8) -> Self {
+        Self {
+            maker: maker.to_string(),
+            outcomes,
+            subsidy: 0,
+            collateral: 0,
+            fees_earned: 0,
+            liabilities: vec![0; outcomes as usize],
+            fees: FeeConfig::default(),
+            providers: BTreeMap::new(),
+            lp_shares: 0,
+            volume: 0,
+            schedule: LiquiditySchedule::default(),
+        }
+    }
+
+    /// What the pool loses if the outcome owing the most wins
+    pub fn worst_case_loss(&self) -> u64 {
+        self.liabilities
+            .iter()
+            .max()
+            .copied()
+            .unwrap_or(0)
+            .saturating_sub(self.collateral)
+    }
+
+    /// What the LP shares are worth if the outcome owing the least wins: the
+    /// subsidy and fees plus the collateral that outcome leaves over. Neither
+    /// withdrawing (subsidy and fees) nor any resolution pays the LPs more, so
+    /// shares minted at this value never pay a new provider more than it added.
+    pub fn lp_value(&self) -> u64 {
+        let least_owed = self.liabilities.iter().min().copied().unwrap_or(0);
+        self.subsidy + self.fees_earned + self.collateral.saturating_sub(least_owed)
+    }
+
+    /// Market maker of a pool with `outcomes` outcomes at the current subsidy
+    /// and volume
+    pub fn lmsr(&self, outcomes: usize) -> LMSR {
+        let floor = LMSR::for_max_loss(self.subsidy as f64 / 1_000_000.0, outcomes);
+        let adaptive = (self.volume as u128 * self.schedule.alpha_bps as u128 / BPS as u128) as f64 / 1_000_000.0;
+        if adaptive > floor.b() {
+            LMSR::new(adaptive)
+        } else {
+            floor
+        }
+    }
+
+    /// What each provider gets once `winners_payout` was paid to winners, in
+    /// address order. Rounding dust goes to the first of them.
+    pub fn distribution(&self, winners_payout: u64) -> Vec<(String, u64)> {
+        if self.lp_shares == 0 {
+            return Vec::new();
+        }
+        let pot = (self.subsidy + self.collateral + self.fees_earned).saturating_sub(winners_payout);
+        let mut shares: Vec<(String, u64)> = self
+            .providers
+            .iter()
+            .map(|(provider, shares)| {
+                (provider.clone(), (pot as u128 * *shares as u128 / self.lp_shares as u128) as u64)
+            })
+            .collect();
+        let paid: u64 = shares.iter().map(|(_, amount)| amount).sum();
+        if let Some((_, amount)) = shares.first_mut() {
+            *amount += pot - paid;
+        }
+        shares
+    }
+}
+
+/// The effect of one bet on its pool
+struct Exposure {
+    outcome: usize,
+    owed: u64,
+    collateral: u64,
+    fee: u64,
+}
+
+impl Exposure {
+    fn of_response(response: &PlaceBetResponse) -> Self {
+        Self {
+            outcome: response.outcome as usize,
+            owed: response.shares * PAYOUT_PER_SHARE,
+            collateral: response.debit_amount.saturating_sub(response.fee_amount),
+            fee: response.credit_amount,
+        }
+    }
+
+    fn of_pending(bet: &PendingBet) -> Self {
+        Self {
+            outcome: bet.outcome as usize,
+            owed: bet.shares * PAYOUT_PER_SHARE,
+            collateral: bet.cost.saturating_sub(bet.credit + bet.protocol_fee + bet.referrer_fee),
+            fee: bet.credit,
+        }
+    }
+}
+
+/// Operator-visible settings of a pool, as bound into attestations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub pool_id: u64,
+    pub maker: String,
+    pub outcomes: u8,
+    pub fees: FeeConfig,
+    pub schedule: LiquiditySchedule,
+}
+
+/// Liquidity account of every pool
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MakerBook {
+    pools: HashMap<u64, MakerAccount>,
+}
+
+impl MakerBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn account(&self, pool_id: u64) -> Option<&MakerAccount> {
+        self.pools.get(&pool_id)
+    }
+
+    /// Settings of every open pool, by pool id
+    pub fn pool_configs(&self) -> Vec<PoolConfig> {
+        let mut configs: Vec<PoolConfig> = self
+            .pools
+            .iter()
+            .map(|(&pool_id, account)| PoolConfig {
+                pool_id,
+                maker: account.maker.clone(),
+                outcomes: account.outcomes,
+                fees: account.fees.clone(),
+                schedule: account.schedule,
+            })
+            .collect();
+        configs.sort_by_key(|config| config.pool_id);
+        configs
+    }
+
+    /// Add `amount` to a pool's subsidy and mint LP shares for it at the pool's
+    /// `lp_value`. The first provider becomes the pool's maker and fixes its
+    /// `outcomes`, which later providers must repeat. Returns the shares minted.
+    pub fn fund(&mut self, pool_id: u64, provider: &str, amount: u64, outcomes: u8) -> Result<u64, EnclaveError> {
+        if outcomes < 2 {
+            return Err(EnclaveError::GenericError(format!(
+                "Pool {pool_id} needs at least 2 outcomes, not {outcomes}"
+            )));
+        }
+        if let Some(account) = self.pools.get(&pool_id).filter(|account| account.outcomes != outcomes) {
+            return Err(EnclaveError::GenericError(format!(
+                "Pool {pool_id} has {} outcomes, not {outcomes}",
+                account.outcomes
+            )));
+        }
+        let account = self
+            .pools
+            .entry(pool_id)
+            .or_insert_with(|| MakerAccount::new(provider, outcomes));
+        let minted = match account.lp_shares {
+            0 => amount,
+            lp_shares => (amount as u128 * lp_shares as u128 / account.lp_value().max(1) as u128) as u64,
+        };
+        if minted == 0 {
+            return Err(EnclaveError::GenericError(format!(
+                "Liquidity of {amount} is too small for pool {pool_id}"
+            )));
+        }
+        account.subsidy += amount;
+        account.lp_shares += minted;
+        *account.providers.entry(provider.to_string()).or_insert(0) += minted;
+        Ok(minted)
+    }
+
+    /// Burn `shares` of a provider's LP shares for their part of the subsidy and
+    /// of the fees earned so far. Refused if the smaller subsidy would no longer
+    /// cover the pool's worst-case loss, or if it would leave the collateral of a
+    /// pool that took bets without providers. Returns the amount released.
+    pub fn withdraw(&mut self, pool_id: u64, provider: &str, shares: u64) -> Result<u64, EnclaveError> {
+        let account = self
+            .pools
+            .get_mut(&pool_id)
+            .ok_or_else(|| EnclaveError::GenericError(format!("Pool {pool_id} has no liquidity")))?;
+        let held = account.providers.get(provider).copied().unwrap_or(0);
+        if shares == 0 || shares > held {
+            return Err(EnclaveError::GenericError(format!(
+                "{provider} holds {held} LP shares of pool {pool_id}, cannot burn {shares}"
+            )));
+        }
+        let subsidy = (account.subsidy as u128 * shares as u128 / account.lp_shares as u128) as u64;
+        let fees = (account.fees_earned as u128 * shares as u128 / account.lp_shares as u128) as u64;
+        if shares == account.lp_shares && account.collateral > 0 {
+            return Err(EnclaveError::GenericError(format!(
+                "The last provider cannot leave pool {pool_id} before it resolves"
+            )));
+        }
+        if account.worst_case_loss() > account.subsidy - subsidy {
+            return Err(EnclaveError::GenericError(format!(
+                "Withdrawing would leave the worst-case loss of pool {pool_id} uncovered"
+            )));
+        }
+        account.subsidy -= subsidy;
+        account.fees_earned -= fees;
+        account.lp_shares -= shares;
+        if held == shares {
+            account.providers.remove(provider);
+        } else {
+            account.providers.insert(provider.to_string(), held - shares);
+        }
+        Ok(subsidy + fees)
+    }
+
+    /// Change how a pool's `b` follows its volume. Only its maker can.
+    pub fn set_schedule(
+        &mut self,
+        pool_id: u64,
+        maker: &str,
+        schedule: LiquiditySchedule,
+    ) -> Result<&MakerAccount, EnclaveError> {
+        schedule.validate()?;
+        let account = self
+            .pools
+            .get_mut(&pool_id)
+            .filter(|account| account.maker == maker)
+            .ok_or_else(|| EnclaveError::GenericError(format!(
+                "Pool {pool_id} is not made by {maker}"
+            )))?;
+        account.schedule = schedule;
+        Ok(account)
+    }
+
+    /// Change a pool's fee settings. Only its maker can.
+    pub fn set_fees(&mut self, pool_id: u64, maker: &str, fees: FeeConfig) -> Result<&MakerAccount, EnclaveError> {
+        fees.validate()?;
+        let account = self
+            .pools
+            .get_mut(&pool_id)
+            .filter(|account| account.maker == maker)
+            .ok_or_else(|| EnclaveError::GenericError(format!(
+                "Pool {pool_id} is not made by {maker}"
+            )))?;
+        account.fees = fees;
+        Ok(account)
+    }
+
+    /// The account a bet is priced against: the pool must be made by `req.maker`
+    /// and have liquidity, and the bet must price every one of its outcomes and
+    /// buy one of them
+    pub fn pool_for_bet(&self, req: &PlaceBetRequest) -> Result<&MakerAccount, EnclaveError> {
+        let account = self
+            .pools
+            .get(&req.pool_id)
+            .filter(|account| account.maker == req.maker && account.subsidy > 0)
+            .ok_or_else(|| EnclaveError::GenericError(format!(
+                "Pool {} has no liquidity from maker {}",
+                req.pool_id, req.maker
+            )))?;
+        if req.current_probs.len() != account.outcomes as usize || req.outcome >= account.outcomes {
+            return Err(EnclaveError::GenericError(format!(
+                "Pool {} has {} outcomes: cannot buy outcome {} priced over {}",
+                req.pool_id,
+                account.outcomes,
+                req.outcome,
+                req.current_probs.len()
+            )));
+        }
+        Ok(account)
+    }
+
+    /// Refuse a bet that would raise the pool's worst-case loss beyond its subsidy
+    pub fn check_bet(&self, req: &PlaceBetRequest, response: &PlaceBetResponse) -> Result<(), EnclaveError> {
+        let mut after = self.pool_for_bet(req)?.clone();
+        apply(&mut after, &Exposure::of_response(response));
+        if after.worst_case_loss() > after.subsidy {
+            return Err(EnclaveError::GenericError(format!(
+                "Bet would raise the worst-case loss of pool {} to {}, beyond its {} subsidy",
+                req.pool_id,
+                after.worst_case_loss(),
+                after.subsidy
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record a signed bet (after `check_bet`)
+    pub fn add_bet(&mut self, req: &PlaceBetRequest, response: &PlaceBetResponse) {
+        if let Some(account) = self.pools.get_mut(&req.pool_id) {
+            apply(account, &Exposure::of_response(response));
+        }
+    }
+
+    /// Undo bets that will never execute on chain
+    pub fn refund(&mut self, bets: &[PendingBet]) {
+        for bet in bets {
+            if let Some(account) = self.pools.get_mut(&bet.pool_id) {
+                let exposure = Exposure::of_pending(bet);
+                if let Some(owed) = account.liabilities.get_mut(exposure.outcome) {
+                    *owed = owed.saturating_sub(exposure.owed);
+                }
+                account.collateral = account.collateral.saturating_sub(exposure.collateral);
+                account.volume = account.volume.saturating_sub(exposure.collateral);
+                account.fees_earned = account.fees_earned.saturating_sub(exposure.fee);
+            }
+        }
+    }
+
+    /// Remove a pool's account once it is resolved
+    pub fn close(&mut self, pool_id: u64) -> Option<MakerAccount> {
+        self.pools.remove(&pool_id)
+    }
+}
+
+/// Add a bet's exposure; its outcome was checked by `pool_for_bet`
+fn apply(account: &mut MakerAccount, exposure: &Exposure) {
+    if let Some(owed) = account.liabilities.get_mut(exposure.outcome) {
+        *owed += exposure.owed;
+    }
+    account.collateral += exposure.collateral;
+    account.volume += exposure.collateral;
+    account.fees_earned += exposure.fee;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bet(outcome: u8, amount: u64, shares: u64) -> (PlaceBetRequest, PlaceBetResponse) {
+        let req = PlaceBetRequest {
+            user: "0xuser1".to_string(),
+            pool_id: 1,
+            outcome,
+            amount,
+            maker: "0xmaker".to_string(),
+            current_probs: vec![5000, 5000],
+            nonce: 1,
+            referrer: None,
+        };
+        let response = PlaceBetResponse {
+            shares,
+            new_probs: vec![5000, 5000],
+            pool_id: 1,
+            outcome,
+            debit_amount: amount,
+            credit_amount: 0,
+            user: req.user.clone(),
+            nonce: 1,
+            seq: 1,
+            prev_state_hash: Vec::new(),
+            user_balance: 0,
+            maker_balance: 0,
+            fee_amount: 0,
+            protocol: String::new(),
+            protocol_fee: 0,
+            protocol_balance: 0,
+            referrer: String::new(),
+            referrer_fee: 0,
+            referrer_balance: 0,
+            b: 0,
+        };
+        (req, response)
+    }
+
+    #[test]
+    fn test_bets_are_limited_by_subsidy() {
+        let mut book = MakerBook::new();
+        let (req, response) = bet(0, 1_000_000, 2000);
+        // No subsidy, no bets
+        assert!(book.check_bet(&req, &response).is_err());
+
+        // Nor bets on an outcome the pool does not have, or priced over another count
+        assert!(book.fund(1, "0xmaker", 1_000_000, 1).is_err());
+        book.fund(1, "0xmaker", 500_000, 2).unwrap();
+        assert!(book.fund(1, "0xlp", 500_000, 3).is_err());
+        let (outside, _) = bet(2, 1_000_000, 2000);
+        assert!(book.pool_for_bet(&outside).is_err());
+        let three_way = PlaceBetRequest { current_probs: vec![3333; 3], ..req.clone() };
+        assert!(book.pool_for_bet(&three_way).is_err());
+        book.close(1);
+
+        // Owes 2 if outcome 0 wins against 1 of collateral
+        book.fund(1, "0xmaker", 1_000_000, 2).unwrap();
+        book.check_bet(&req, &response).unwrap();
+        book.add_bet(&req, &response);
+        assert_eq!(book.account(1).unwrap().worst_case_loss(), 1_000_000);
+        assert!(book.check_bet(&req, &response).is_err());
+
+        // A bet on the other outcome adds collateral and hedges the pool
+        let (hedge_req, hedge) = bet(1, 1_000_000, 1000);
+        book.check_bet(&hedge_req, &hedge).unwrap();
+        book.add_bet(&hedge_req, &hedge);
+        assert_eq!(book.account(1).unwrap().worst_case_loss(), 0);
+    }
+
+    #[test]
+    fn test_liquidity_providers_share_pro_rata() {
+        let mut book = MakerBook::new();
+        assert_eq!(book.fund(1, "0xmaker", 3_000_000, 2).unwrap(), 3_000_000);
+        assert_eq!(book.fund(1, "0xlp", 1_000_000, 2).unwrap(), 1_000_000);
+        let b = book.account(1).unwrap().lmsr(2).b();
+        assert!((b - 4.0 / 2f64.ln()).abs() < 1e-9);
+
+        // Owes 6 on outcome 0 against 2 of collateral: 4 must stay escrowed
+        let (req, response) = bet(0, 2_000_000, 6000);
+        book.add_bet(&req, &response);
+        assert!(book.withdraw(1, "0xlp", 1_000_000).is_err());
+        assert!(book.withdraw(1, "0xmaker", 4_000_000).is_err());
+
+        // Outcome 1 wins: nothing to winners, the 6 in the pool are split 3:1
+        let account = book.close(1).unwrap();
+        assert_eq!(
+            account.distribution(0),
+            vec![("0xlp".to_string(), 1_500_000), ("0xmaker".to_string(), 4_500_000)]
+        );
+        assert!(book.account(1).is_none());
+    }
+
+    #[test]
+    fn test_adaptive_b_grows_with_volume() {
+        let mut book = MakerBook::new();
+        book.fund(1, "0xmaker", 1_000_000, 2).unwrap();
+        let floor = 1.0 / 2f64.ln();
+        assert!(book.set_schedule(1, "0xlp", LiquiditySchedule { alpha_bps: 1_000 }).is_err());
+        assert!(book.set_schedule(1, "0xmaker", LiquiditySchedule { alpha_bps: BPS + 1 }).is_err());
+        book.set_schedule(1, "0xmaker", LiquiditySchedule { alpha_bps: 1_000 }).unwrap();
+
+        // 10% of 5 traded is below the floor, 10% of 25 is above it
+        let (req, response) = bet(0, 5_000_000, 5000);
+        book.add_bet(&req, &response);
+        assert!((book.account(1).unwrap().lmsr(2).b() - floor).abs() < 1e-9);
+        let (req, response) = bet(1, 20_000_000, 20000);
+        book.add_bet(&req, &response);
+        assert!((book.account(1).unwrap().lmsr(2).b() - 2.5).abs() < 1e-9);
+
+        // Refunded bets never traded
+        let refunded = PendingBet {
+            user: req.user.clone(),
+            nonce: 1,
+            pool_id: 1,
+            outcome: 1,
+            shares: 20000,
+            cost: 20_000_000,
+            maker: req.maker.clone(),
+            credit: 0,
+            protocol: String::new(),
+            protocol_fee: 0,
+            referrer: String::new(),
+            referrer_fee: 0,
+            seq: 2,
+            placed_at_ms: 0,
+        };
+        book.refund(&[refunded]);
+        assert_eq!(book.account(1).unwrap().volume, 5_000_000);
+    }
+
+    #[test]
+    fn test_late_providers_buy_in_at_pool_value() {
+        let mut book = MakerBook::new();
+        book.fund(1, "0xmaker", 1_000_000, 2).unwrap();
+        // 0.1 of fees and 0.9 of collateral against 1.5 owed on outcome 0
+        let (req, mut response) = bet(0, 1_000_000, 1500);
+        response.fee_amount = 100_000;
+        response.credit_amount = 100_000;
+        book.add_bet(&req, &response);
+        assert_eq!(book.account(1).unwrap().lp_value(), 2_000_000);
+
+        // Joining late buys half as many shares per unit as the maker got
+        assert_eq!(book.fund(1, "0xlp", 1_000_000, 2).unwrap(), 500_000);
+        let account = book.account(1).unwrap().clone();
+
+        // Leaving at once returns no more than was added
+        let mut left = book.clone();
+        assert!(left.withdraw(1, "0xlp", 500_000).unwrap() <= 1_000_000);
+
+        // Nor does staying to resolution, whichever outcome wins
+        for winners_payout in [0, 1_500_000] {
+            let paid = account.distribution(winners_payout);
+            assert!(paid.iter().find(|(provider, _)| provider == "0xlp").unwrap().1 <= 1_000_000);
+        }
+    }
+
+    #[test]
+    fn test_withdraw_releases_subsidy() {
+        let mut book = MakerBook::new();
+        book.fund(1, "0xmaker", 2_000_000, 2).unwrap();
+        book.fund(1, "0xlp", 2_000_000, 2).unwrap();
+        assert_eq!(book.withdraw(1, "0xlp", 1_000_000).unwrap(), 1_000_000);
+        let account = book.account(1).unwrap();
+        assert_eq!((account.subsidy, account.lp_shares), (3_000_000, 3_000_000));
+        assert_eq!(account.providers["0xlp"], 1_000_000);
+        assert!(book.withdraw(1, "0xlp", 2_000_000).is_err());
+    }
+}
@@ -1,31 +1,191 @@
 //! PM (Prediction Market) Nautilus App
 //! 
 //! TEE calculates LMSR pricing and signs responses for on-chain verification.
+//! Bets are checked against the operator's risk limits first (see `risk`).
+//! Bets and liquidity requests must carry the wallet signature of the user,
+//! provider or maker they name, and resolutions that of PM_RESOLVER_ADDRESS
+//! (see `wallet`).
 //! Endpoints:
-//! - POST /process_data - Place a bet (returns signed response)
+//! - POST /process_data - Place a bet (returns signed response, position stays pending)
+//! - POST /process_data/batched - Place a bet signed as part of a Merkle-root batch
+//! - POST /confirm - Confirm a pending bet once its transaction executed on chain (relayer-signed)
+//! - GET /bets/pending - A wallet's bets awaiting confirmation
+//! - POST /pools/liquidity/add - Add liquidity to a pool from a provider's vault balance
+//! - POST /pools/liquidity/remove - Burn LP shares for their part of a pool's subsidy and fees
+//! - POST /pools/fees - Set a pool's taker fee and its protocol and referrer shares
+//! - POST /pools/liquidity/schedule - Let a pool's LMSR `b` grow with its traded volume
+//! - GET /pools/liquidity - A pool's subsidy, collateral, fees, worst-case loss, `b` and LP shares
+//! - GET /pools/head - Sequence number, state hash and probabilities the next bet of a pool builds on
+//! - POST /pools/sync - Adopt the on-chain sequence state of a pool this enclave has no history for (relayer-signed)
+//! - POST /vault/event - Apply a relayer-signed vault deposit or withdrawal
+//! - GET /balance - A wallet's enclave-side vault balance
 //! - POST /resolve - Resolve market and calculate payouts
+//! - GET /resolve/batch - Re-fetch one signed payout batch of a resolved pool
+//! - GET /ledger - Export the hash-chained trade ledger
+//! - GET /ledger/head - Signed head of the trade ledger
+//! - GET /positions/proof - Signed Merkle root of a pool's positions plus a user's inclusion proofs
+//! - GET /portfolio - A wallet's positions with cost basis and mark-to-market PnL
+//! - POST /verify - Check a signed response against this enclave's key or a given one
+//! - GET /get_attestation - Attestation binding a caller nonce and the hash of the PM configuration
 
+pub mod balances;
+pub mod fees;
+pub mod ledger;
 pub mod lmsr;
+pub mod maker;
+pub mod pending;
+pub mod portfolio;
+pub mod risk;
+pub mod sequence;
+pub mod snapshot;
 pub mod state;
 
-use crate::common::{to_signed_response, IntentMessage, ProcessDataRequest, ProcessedDataResponse};
+use crate::common::{
+    attest, AttestationUserData, GetAttestationParams, GetAttestationResponse, IntentSigner, ProcessDataRequest,
+    SignatureScheme, ProcessedDataResponse, VerifyResponse,
+};
+use crate::intent::{
+    chain_id_from_env, to_signed_domain_response, ChainId, DomainIntentMessage, DomainVerifyRequest, IntentHeader,
+    APP_PM, SUI_TESTNET,
+};
+use crate::batch::{BatchRoot, BatchedResponse, SignatureBatcher};
+use crate::rotation::KeyRotation;
+use crate::wallet::verify_request;
+use crate::merkle::{MerkleProof, MerkleTree};
 use crate::{AppState, EnclaveError};
 use axum::{extract::{State, Query}, Json};
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use lmsr::LMSR;
-use state::PositionStore;
+use balances::{verify_relayer_signature, verify_vault_event, Balances, SignedVaultEvent};
+use fastcrypto::ed25519::Ed25519PublicKey;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use fastcrypto::traits::ToFromBytes;
+use fees::FeeConfig;
+use ledger::{Ledger, LedgerEvent, LedgerExport, LedgerHead};
+use maker::{LiquiditySchedule, MakerAccount, MakerBook, PAYOUT_PER_SHARE};
+use pending::{expire_and_roll_back, PendingBet, PendingBets};
+use risk::{CircuitBreaker, RiskLimits};
+use sequence::{PoolHead, PoolSequencer};
+use snapshot::SnapshotHead;
+use state::PositionRepository;
 
-// Global position store (persists across requests in TEE)
-static POSITION_STORE: Lazy<RwLock<PositionStore>> = Lazy::new(|| {
-    RwLock::new(PositionStore::new())
-});
+// Default window over which batched bets are collected before their root is signed
+const DEFAULT_BATCH_WINDOW_MS: u64 = 250;
 
-// LMSR liquidity parameter
-const LMSR_B: f64 = 100.0;
+// Default number of winners per signed payout batch
+const DEFAULT_PAYOUT_BATCH_SIZE: u64 = 100;
+
+/// Operator settings of the PM app, fixed at startup
+#[derive(Debug, Clone)]
+pub struct PmSettings {
+    pub vault_relayer: Option<Ed25519PublicKey>, // Signs relayed vault events (VAULT_RELAYER_PK)
+    pub resolver: Option<String>,      // Wallet whose signature resolves markets (PM_RESOLVER_ADDRESS)
+    pub risk_limits: RiskLimits,       // PM_MAX_* / PM_BREAKER_*
+    pub protocol: Option<String>,      // Recipient of the protocol share of fees (PM_PROTOCOL_ADDRESS)
+    pub batch_window: Duration,        // Window of batch-mode bets (PM_BATCH_WINDOW_MS)
+    pub chain_id: ChainId,             // Chain every message is signed for (SUI_CHAIN_ID)
+}
+
+impl Default for PmSettings {
+    fn default() -> Self {
+        Self {
+            vault_relayer: None,
+            resolver: None,
+            risk_limits: RiskLimits::default(),
+            protocol: None,
+            batch_window: Duration::from_millis(DEFAULT_BATCH_WINDOW_MS),
+            chain_id: SUI_TESTNET,
+        }
+    }
+}
+
+impl PmSettings {
+    /// Settings from the environment; `SUI_CHAIN_ID` is required since the chain
+    /// is part of every signed message
+    pub fn from_env() -> Result<Self, EnclaveError> {
+        let chain_id = chain_id_from_env()?
+            .ok_or_else(|| EnclaveError::GenericError("SUI_CHAIN_ID must be set for the PM app".into()))?;
+        let batch_window_ms = std::env::var("PM_BATCH_WINDOW_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(DEFAULT_BATCH_WINDOW_MS);
+        Ok(Self {
+            vault_relayer: balances::relayer_pk_from_env(),
+            resolver: std::env::var("PM_RESOLVER_ADDRESS").ok().filter(|address| !address.is_empty()),
+            risk_limits: RiskLimits::from_env(),
+            protocol: fees::protocol_address_from_env(),
+            batch_window: Duration::from_millis(batch_window_ms),
+            chain_id,
+        })
+    }
+}
+
+/// Market state of the PM app, held in `AppState` next to the position store.
+/// Handlers lock the position store first, so it serializes every state change.
+pub struct PmState {
+    pub settings: PmSettings,
+    // Payout sets of resolved pools, kept so batches can be re-fetched after positions are cleared
+    resolved_pools: RwLock<HashMap<u64, ResolvedPool>>,
+    // Append-only, hash-chained log of every trade and resolution
+    ledger: RwLock<Ledger>,
+    // Latest signed Merkle root over each pool's positions
+    position_roots: RwLock<HashMap<u64, ProcessedDataResponse<DomainIntentMessage<PositionsRoot>>>>,
+    // Latest signed probabilities of each open pool (scaled by 10000), used for mark-to-market
+    pool_probs: RwLock<HashMap<u64, Vec<u64>>>,
+    // Bets already processed, keyed by (user, nonce), so retries get the original signed response
+    processed_bets: RwLock<HashMap<(String, u64), ProcessedBet>>,
+    // Liquidity, fee and schedule requests already applied, keyed by (signer, nonce)
+    request_nonces: RwLock<HashSet<(String, u64)>>,
+    // Signed bets whose on-chain execution has not been confirmed yet
+    pending_bets: RwLock<PendingBets>,
+    // Sequence number and state hash of every pool's confirmed and signed bets
+    sequencer: RwLock<PoolSequencer>,
+    // Vault balance of every user, kept in step with on-chain deposits and withdrawals
+    balances: RwLock<Balances>,
+    // Subsidy, collateral, exposure and liquidity providers of each pool
+    makers: RwLock<MakerBook>,
+    // Recent price moves and halts of each pool
+    breaker: RwLock<CircuitBreaker>,
+    // Signs bets placed in batch mode under one Merkle root per window
+    batcher: Arc<SignatureBatcher>,
+    // Head of the last snapshot saved or restored; a restore must extend it
+    last_snapshot: RwLock<SnapshotHead>,
+    // Head of the snapshot the enclave booted from (genesis if it started empty)
+    resumed_from: RwLock<SnapshotHead>,
+}
+
+impl PmState {
+    pub fn new(settings: PmSettings) -> Self {
+        Self {
+            batcher: Arc::new(SignatureBatcher::new(settings.batch_window, IntentScope::BatchRoot.header(settings.chain_id))),
+            settings,
+            resolved_pools: RwLock::new(HashMap::new()),
+            ledger: RwLock::new(Ledger::new()),
+            position_roots: RwLock::new(HashMap::new()),
+            pool_probs: RwLock::new(HashMap::new()),
+            processed_bets: RwLock::new(HashMap::new()),
+            request_nonces: RwLock::new(HashSet::new()),
+            pending_bets: RwLock::new(PendingBets::new()),
+            sequencer: RwLock::new(PoolSequencer::new()),
+            balances: RwLock::new(Balances::new()),
+            makers: RwLock::new(MakerBook::new()),
+            breaker: RwLock::new(CircuitBreaker::new()),
+            last_snapshot: RwLock::new(SnapshotHead::default()),
+            resumed_from: RwLock::new(SnapshotHead::default()),
+        }
+    }
+}
+
+impl Default for PmState {
+    fn default() -> Self {
+        Self::new(PmSettings::default())
+    }
+}
 
 // Intent scopes - must match Move contract
 #[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy)]
@@ -33,6 +193,43 @@ const LMSR_B: f64 = 100.0;
 pub enum IntentScope {
     PlaceBet = 0,
     Resolve = 1,
+    PayoutBatch = 2,
+    LedgerHead = 3,
+    PositionsRoot = 4,
+    BatchRoot = 5,
+}
+
+impl TryFrom<u8> for IntentScope {
+    type Error = EnclaveError;
+
+    fn try_from(intent: u8) -> Result<Self, Self::Error> {
+        Ok(match intent {
+            0 => IntentScope::PlaceBet,
+            1 => IntentScope::Resolve,
+            2 => IntentScope::PayoutBatch,
+            3 => IntentScope::LedgerHead,
+            4 => IntentScope::PositionsRoot,
+            5 => IntentScope::BatchRoot,
+            _ => return Err(EnclaveError::GenericError(format!("Unknown intent scope {intent}"))),
+        })
+    }
+}
+
+impl IntentScope {
+    /// Header of PM messages of this scope on `chain_id`
+    pub fn header(self, chain_id: ChainId) -> IntentHeader {
+        IntentHeader::new(APP_PM, self as u8, chain_id).expect("PM scopes are registered")
+    }
+}
+
+/// Sign `payload` under the PM header of `scope` for the configured chain
+fn sign_pm<T: Serialize + Clone>(
+    state: &AppState,
+    scope: IntentScope,
+    payload: T,
+    timestamp_ms: u64,
+) -> ProcessedDataResponse<DomainIntentMessage<T>> {
+    to_signed_domain_response(&state.eph_kp, scope.header(state.pm.settings.chain_id), payload, timestamp_ms)
 }
 
 // ============================================================
@@ -40,14 +237,17 @@ pub enum IntentScope {
 // ============================================================
 
 /// Request to place a bet
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PlaceBetRequest {
     pub user: String,              // Bettor's wallet address
     pub pool_id: u64,              // Which pool to bet on
     pub outcome: u8,               // Which outcome (0-7) to bet on
     pub amount: u64,               // Amount in smallest units (scaled by 10^6)
-    pub maker: String,             // Pool creator's wallet (receives funds)
+    pub maker: String,             // Pool's maker, its first liquidity provider
     pub current_probs: Vec<u64>,   // Current probabilities from World (scaled by 10000)
+    pub nonce: u64,                // Client idempotency key, unique per user
+    #[serde(default)]
+    pub referrer: Option<String>,  // Wallet that referred the trade, paid a share of the fee
 }
 
 /// Response after placing a bet - MUST match Move PlaceBetResponse exactly
@@ -58,14 +258,53 @@ pub struct PlaceBetResponse {
     pub pool_id: u64,
     pub outcome: u8,
     pub debit_amount: u64,
-    pub credit_amount: u64,
+    pub credit_amount: u64,        // LPs' share of the fee, accrued in the pool until resolution
+    pub user: String,
+    pub nonce: u64,                // Echoed from the request so the chain can reject replays
+    pub seq: u64,                  // Per-pool sequence number of this bet
+    pub prev_state_hash: Vec<u8>,  // Hash of the pool state the bet was priced from
+    pub user_balance: u64,         // User's vault balance after the debit
+    pub maker_balance: u64,        // Maker's vault balance (LP earnings are paid at resolution)
+    pub fee_amount: u64,           // Total fee taken from the debit
+    pub protocol: String,          // Protocol fee recipient (empty if none)
+    pub protocol_fee: u64,
+    pub protocol_balance: u64,     // Protocol's vault balance after its fee
+    pub referrer: String,          // Referrer (empty if none)
+    pub referrer_fee: u64,
+    pub referrer_balance: u64,     // Referrer's vault balance after its fee
+    pub b: u64,                    // LMSR liquidity the bet was priced at (scaled by 10^6)
+}
+
+/// A processed bet: the request it answered and the response that was returned
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProcessedBet {
+    pub request: PlaceBetRequest,
+    pub placed: PlacedBet,
+}
+
+/// A priced bet, signed on its own or left for the batch signer
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum PlacedBet {
+    Signed(ProcessedDataResponse<DomainIntentMessage<PlaceBetResponse>>),
+    Batched(DomainIntentMessage<PlaceBetResponse>),
+}
+
+impl PlacedBet {
+    pub fn message(&self) -> &DomainIntentMessage<PlaceBetResponse> {
+        match self {
+            PlacedBet::Signed(signed) => &signed.response,
+            PlacedBet::Batched(message) => message,
+        }
+    }
 }
 
 /// Request to resolve a market
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolveRequest {
     pub pool_id: u64,
     pub winning_outcome: u8,
+    #[serde(default)]
+    pub batch_size: Option<u64>,   // Winners per payout batch (defaults to DEFAULT_PAYOUT_BATCH_SIZE)
 }
 
 /// Payout info for a winner
@@ -85,141 +324,2012 @@ pub struct ResolveResponse {
     pub total_payout: u64,
 }
 
+/// One chunk of a pool's payouts - MUST match Move PayoutBatch exactly
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PayoutBatch {
+    pub pool_id: u64,
+    pub winning_outcome: u8,
+    pub batch_index: u64,
+    pub total_batches: u64,
+    pub payouts: Vec<Payout>,
+    pub batch_payout: u64,
+}
+
+/// Signed resolution plus every signed payout batch
+#[derive(Serialize, Deserialize)]
+pub struct ResolveResult {
+    #[serde(flatten)]
+    pub resolution: ProcessedDataResponse<DomainIntentMessage<ResolveResponse>>,
+    pub batches: Vec<ProcessedDataResponse<DomainIntentMessage<PayoutBatch>>>,
+}
+
+/// Payouts of a resolved pool, split into batches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedPool {
+    pub winning_outcome: u8,
+    pub timestamp_ms: u64,
+    pub batches: Vec<Vec<Payout>>,
+}
+
+impl ResolvedPool {
+    /// Build the batch message at `batch_index`, if it exists
+    pub fn batch(&self, pool_id: u64, batch_index: u64) -> Option<PayoutBatch> {
+        let payouts = self.batches.get(batch_index as usize)?.clone();
+        Some(PayoutBatch {
+            pool_id,
+            winning_outcome: self.winning_outcome,
+            batch_index,
+            total_batches: self.batches.len() as u64,
+            batch_payout: payouts.iter().map(|p| p.amount).sum(),
+            payouts,
+        })
+    }
+}
+
+/// Split payouts into chunks of `batch_size`. An empty payout set still yields one empty batch.
+pub fn split_payouts(payouts: &[Payout], batch_size: u64) -> Vec<Vec<Payout>> {
+    if payouts.is_empty() {
+        return vec![Vec::new()];
+    }
+    payouts
+        .chunks(batch_size.max(1) as usize)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
 // ============================================================
 // MAIN ENDPOINT: process_data (Place Bet)
 // ============================================================
 pub async fn process_data(
     State(state): State<Arc<AppState>>,
     Json(request): Json<ProcessDataRequest<PlaceBetRequest>>,
-) -> Result<Json<ProcessedDataResponse<IntentMessage<PlaceBetResponse>>>, EnclaveError> {
+) -> Result<Json<ProcessedDataResponse<DomainIntentMessage<PlaceBetResponse>>>, EnclaveError> {
     let req = &request.payload;
+    verify_request(req, request.signature.as_deref(), &req.user)?;
+    match place_bet(&state, req, false)?.placed {
+        PlacedBet::Signed(signed) => Ok(Json(signed)),
+        PlacedBet::Batched(_) => Err(EnclaveError::GenericError(format!(
+            "Bet {} of {} was placed in batch mode",
+            req.nonce, req.user
+        ))),
+    }
+}
+
+/// Price, hold and record a bet, or return the bet already placed for its
+/// (user, nonce). A bet placed with `batched` is left unsigned for the batcher;
+/// any other is signed on its own.
+fn place_bet(state: &AppState, req: &PlaceBetRequest, batched: bool) -> Result<ProcessedBet, EnclaveError> {
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
     
-    // Convert current probs to f64 for LMSR
-    let current_quantities: Vec<f64> = req.current_probs
-        .iter()
-        .map(|&p| {
-            let prob = (p as f64) / 10000.0;
-            if prob > 0.0 {
-                LMSR_B * prob.ln()
-            } else {
-                0.0
-            }
-        })
-        .collect();
-    
-    // Calculate shares using LMSR
-    let lmsr = LMSR::new(LMSR_B);
-    let outcomes = vec![req.outcome as usize];
-    let amount_f64 = (req.amount as f64) / 1_000_000.0;
-    
-    let (shares_f64, new_quantities) = lmsr.shares_for_amount(&current_quantities, &outcomes, amount_f64);
-    let new_prices = lmsr.prices(&new_quantities);
+    // Hold the bet as pending and record the trade. The positions lock is held from
+    // the nonce check onwards so concurrent retries cannot both be applied.
+    let store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut processed = state.pm.processed_bets.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let key = (req.user.clone(), req.nonce);
+    if let Some(previous) = processed.get(&key) {
+        if previous.request != *req {
+            return Err(EnclaveError::GenericError(format!(
+                "Nonce {} already used by {} for a different bet",
+                req.nonce, req.user
+            )));
+        }
+        return Ok(previous.clone());
+    }
     
-    // Store position
-    {
-        let mut store = POSITION_STORE.write()
-            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
-        store.add_position(
-            req.user.clone(),
-            req.pool_id,
-            req.outcome,
-            (shares_f64 * 1000.0) as u64,
-        );
+    // Price from the tip of the pool's sequence only, so bets are signed in order
+    let mut pending = state.pm.pending_bets.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut sequencer = state.pm.sequencer.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    sweep_expired(state, &mut pending, &mut sequencer, timestamp_ms)?;
+    if req.referrer.as_ref().is_some_and(|referrer| *referrer == req.user || *referrer == req.maker) {
+        return Err(EnclaveError::GenericError("A trade cannot be referred by its user or maker".into()));
     }
+    let mut breaker = state.pm.breaker.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    breaker.check(req.pool_id, timestamp_ms)?;
     
-    // Build response
-    let response = PlaceBetResponse {
-        shares: (shares_f64 * 1000.0) as u64,
-        new_probs: new_prices.iter().map(|&p| (p * 10000.0) as u64).collect(),
+    // Refuse bets beyond the risk limits or that the pool's liquidity or the
+    // user's balance cannot cover; nothing is signed if any check fails
+    let mut makers = state.pm.makers.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let pool = makers.pool_for_bet(req)?;
+    let tip = sequencer.check_fresh(req.pool_id, &req.current_probs, pool.outcomes as usize)?;
+    state.pm.balances.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .check_funds(&req.user, req.amount)?;
+    let mut response = quote_bet(req, &tip, pool);
+    check_risk(&state.pm.settings.risk_limits, store.as_ref(), &pending, pool, req, &response)?;
+    makers.check_bet(req, &response)?;
+    state.pm.balances.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .settle_bet(req, &mut response)?;
+    makers.add_bet(req, &response);
+    let placed = if batched {
+        PlacedBet::Batched(DomainIntentMessage {
+            header: IntentScope::PlaceBet.header(state.pm.settings.chain_id),
+            timestamp_ms,
+            data: response,
+        })
+    } else {
+        PlacedBet::Signed(sign_pm(state, IntentScope::PlaceBet, response, timestamp_ms))
+    };
+    let response = &placed.message().data;
+    let head = sequencer.advance(req.pool_id, response.new_probs.clone());
+    breaker.record(&state.pm.settings.risk_limits, req.pool_id, timestamp_ms, &req.current_probs, &head.probs);
+    pending.insert(PendingBet {
+        user: req.user.clone(),
+        nonce: req.nonce,
         pool_id: req.pool_id,
         outcome: req.outcome,
-        debit_amount: req.amount,
-        credit_amount: req.amount,
+        shares: response.shares,
+        cost: response.debit_amount,
+        maker: req.maker.clone(),
+        credit: response.credit_amount,
+        protocol: response.protocol.clone(),
+        protocol_fee: response.protocol_fee,
+        referrer: response.referrer.clone(),
+        referrer_fee: response.referrer_fee,
+        seq: head.seq,
+        placed_at_ms: timestamp_ms,
+    });
+    state.pm.pool_probs.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .insert(req.pool_id, head.probs);
+    record_event(
+        state,
+        LedgerEvent::BetPlaced {
+            request: req.clone(),
+            response: Box::new(response.clone()),
+            signature: match &placed {
+                PlacedBet::Signed(signed) => Some(signed.signature.clone()),
+                PlacedBet::Batched(_) => None,
+            },
+        },
+        timestamp_ms,
+    )?;
+    let bet = ProcessedBet { request: req.clone(), placed };
+    processed.insert(key, bet.clone());
+    Ok(bet)
+}
+
+/// Check a priced bet against the risk limits. The wallet's position counts its
+/// confirmed and pending shares in the pool.
+fn check_risk(
+    limits: &RiskLimits,
+    store: &dyn PositionRepository,
+    pending: &PendingBets,
+    pool: &MakerAccount,
+    req: &PlaceBetRequest,
+    response: &PlaceBetResponse,
+) -> Result<(), EnclaveError> {
+    let confirmed: u64 = store
+        .get_user_positions(&req.user, req.pool_id)?
+        .iter()
+        .map(|p| p.shares)
+        .sum();
+    let pending: u64 = pending
+        .by_wallet(&req.user)
+        .iter()
+        .filter(|bet| bet.pool_id == req.pool_id)
+        .map(|bet| bet.shares)
+        .sum();
+    let outcome = req.outcome as usize;
+    limits.check_bet(
+        response.debit_amount,
+        confirmed.saturating_add(pending).saturating_add(response.shares),
+        pool.collateral.saturating_add(response.debit_amount.saturating_sub(response.fee_amount)),
+        req.current_probs.get(outcome).copied().unwrap_or(0),
+        response.new_probs.get(outcome).copied().unwrap_or(0),
+    )?;
+    Ok(())
+}
+
+/// Place a bet in batch mode. The bet is priced and recorded as by /process_data
+/// but not signed on its own: its message joins the current signing batch and is
+/// only covered by the batch root. Answers once the root is signed, at most
+/// PM_BATCH_WINDOW_MS later; the contract verifies the root once per batch. A
+/// retry puts the same message in a new batch.
+pub async fn process_data_batched(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<PlaceBetRequest>>,
+) -> Result<Json<BatchedResponse<PlaceBetResponse>>, EnclaveError> {
+    let req = &request.payload;
+    verify_request(req, request.signature.as_deref(), &req.user)?;
+    let message = match place_bet(&state, req, true)?.placed {
+        PlacedBet::Batched(message) => message,
+        PlacedBet::Signed(_) => {
+            return Err(EnclaveError::GenericError(format!(
+                "Bet {} of {} was not placed in batch mode",
+                req.nonce, req.user
+            )))
+        }
     };
-    
-    // Get timestamp
+    let batcher = state.pm.batcher.clone();
+    Ok(Json(batcher.enqueue(state, message)?.wait().await?))
+}
+
+// ============================================================
+// CONFIRM ENDPOINT
+// ============================================================
+
+/// `BetSubmitted` event of an executed `submit_bet` transaction - Match Move
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfirmBetRequest {
+    pub user: String,
+    pub nonce: u64,
+    pub pool_id: u64,
+    pub outcome: u8,
+    pub shares: u64,
+    pub tx_digest: String,         // Digest of the transaction that emitted the event
+}
+
+/// A bet confirmation with the vault relayer's hex signature over its BCS encoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedConfirmation {
+    pub confirmation: ConfirmBetRequest,
+    pub signature: String,
+}
+
+/// Turn a pending bet into a position. Fails unless the vault relayer signed the
+/// confirmation and its event matches the bet, or if the bet is unknown, already
+/// confirmed or past its confirmation grace (see `pending`).
+pub async fn confirm_bet(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<SignedConfirmation>>,
+) -> Result<Json<PendingBet>, EnclaveError> {
+    let req = &request.payload.confirmation;
+    let relayer = state.pm.settings.vault_relayer
+        .as_ref()
+        .ok_or_else(|| EnclaveError::GenericError("VAULT_RELAYER_PK is not configured".into()))?;
+    verify_relayer_signature(relayer, req, &request.payload.signature)?;
     let timestamp_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
         .as_millis() as u64;
-    
-    // Return signed response
-    Ok(Json(to_signed_response(
-        &state.eph_kp,
-        response,
+
+    let mut store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let bet = {
+        let mut pending = state.pm.pending_bets.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        let mut sequencer = state.pm.sequencer.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        sweep_expired(&state, &mut pending, &mut sequencer, timestamp_ms)?;
+        if let Some(bet) = pending.get(&req.user, req.nonce) {
+            if (bet.pool_id, bet.outcome, bet.shares) != (req.pool_id, req.outcome, req.shares) {
+                return Err(EnclaveError::GenericError(format!(
+                    "BetSubmitted event does not match bet {} of {}",
+                    req.nonce, req.user
+                )));
+            }
+        }
+        let bet = pending.take(&req.user, req.nonce, timestamp_ms)?;
+        if let Err(e) = sequencer.confirm(bet.pool_id, bet.seq) {
+            pending.insert(bet);
+            return Err(e);
+        }
+        bet
+    };
+    store.add_position(bet.user.clone(), bet.pool_id, bet.outcome, bet.shares, bet.cost)?;
+    commit_pool(&state, store.as_ref(), bet.pool_id, timestamp_ms)?;
+    record_event(
+        &state,
+        LedgerEvent::Confirmed {
+            user: req.user.clone(),
+            nonce: req.nonce,
+            tx_digest: req.tx_digest.clone(),
+        },
+        timestamp_ms,
+    )?;
+    Ok(Json(bet))
+}
+
+#[derive(Deserialize)]
+pub struct GetPendingBetsParams {
+    pub wallet: String,
+}
+
+/// Bets of a wallet that are still awaiting confirmation
+pub async fn get_pending_bets(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPendingBetsParams>,
+) -> Result<Json<Vec<PendingBet>>, EnclaveError> {
+    let pending = state.pm.pending_bets.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    Ok(Json(pending.by_wallet(&params.wallet)))
+}
+
+/// Drop bets past their confirmation grace, refund them and roll their pools back; the latest signed
+/// probabilities of a rolled back pool become those of its new tip
+fn sweep_expired(
+    state: &AppState,
+    pending: &mut PendingBets,
+    sequencer: &mut PoolSequencer,
+    now_ms: u64,
+) -> Result<(), EnclaveError> {
+    let dropped = expire_and_roll_back(pending, sequencer, now_ms);
+    if dropped.is_empty() {
+        return Ok(());
+    }
+    refund_bets(state, &dropped)?;
+    let rolled_back: BTreeSet<u64> = dropped.iter().map(|bet| bet.pool_id).collect();
+    let mut pool_probs = state.pm.pool_probs.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    for pool_id in rolled_back {
+        let tip = sequencer.tip(pool_id);
+        if tip.probs.is_empty() {
+            pool_probs.remove(&pool_id);
+        } else {
+            pool_probs.insert(pool_id, tip.probs);
+        }
+    }
+    Ok(())
+}
+
+/// Return the stakes of bets that will never execute and release their maker exposure
+fn refund_bets(state: &AppState, bets: &[PendingBet]) -> Result<(), EnclaveError> {
+    state.pm.balances.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .refund(bets);
+    state.pm.makers.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .refund(bets);
+    Ok(())
+}
+
+// ============================================================
+// POOL SEQUENCE ENDPOINTS
+// ============================================================
+#[derive(Deserialize)]
+pub struct GetPoolHeadParams {
+    pub pool_id: u64,
+}
+
+/// State the next bet of a pool must be priced from
+pub async fn get_pool_head(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPoolHeadParams>,
+) -> Result<Json<PoolHead>, EnclaveError> {
+    let sequencer = state.pm.sequencer.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    Ok(Json(sequencer.tip(params.pool_id)))
+}
+
+/// On-chain sequence state of a pool, as read by the host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPoolRequest {
+    pub pool_id: u64,
+    pub seq: u64,
+    pub probs: Vec<u64>,
+}
+
+/// A pool state with the vault relayer's hex signature over its BCS encoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPoolSync {
+    pub sync: SyncPoolRequest,
+    pub signature: String,
+}
+
+/// Adopt a pool's on-chain state, e.g. after this enclave replaced another one.
+/// Only the vault relayer may report it, and only for a pool this enclave has no
+/// sequence head for.
+pub async fn sync_pool(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<SignedPoolSync>>,
+) -> Result<Json<PoolHead>, EnclaveError> {
+    let req = &request.payload.sync;
+    let relayer = state.pm.settings.vault_relayer
+        .as_ref()
+        .ok_or_else(|| EnclaveError::GenericError("VAULT_RELAYER_PK is not configured".into()))?;
+    verify_relayer_signature(relayer, req, &request.payload.signature)?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+
+    let _store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut pending = state.pm.pending_bets.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut sequencer = state.pm.sequencer.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    sweep_expired(&state, &mut pending, &mut sequencer, timestamp_ms)?;
+    let head = sequencer.sync(req.pool_id, req.seq, req.probs.clone())?;
+    {
+        let mut pool_probs = state.pm.pool_probs.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        if head.probs.is_empty() {
+            pool_probs.remove(&req.pool_id);
+        } else {
+            pool_probs.insert(req.pool_id, head.probs.clone());
+        }
+    }
+    record_event(
+        &state,
+        LedgerEvent::Synced {
+            pool_id: req.pool_id,
+            seq: req.seq,
+            probs: req.probs.clone(),
+        },
         timestamp_ms,
-        IntentScope::PlaceBet as u8,
-    )))
+    )?;
+    Ok(Json(head))
 }
 
 // ============================================================
-// RESOLVE ENDPOINT
+// LIQUIDITY ENDPOINTS
 // ============================================================
-pub async fn resolve(
+
+/// Request to add liquidity to a pool from the provider's vault balance
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddLiquidityRequest {
+    pub pool_id: u64,
+    pub provider: String,
+    pub amount: u64,
+    pub outcomes: u8,              // Set by the pool's first provider; later ones must repeat it
+    pub nonce: u64,                // Unique per provider, so the signed request applies once
+}
+
+/// Request to burn LP shares for their part of the subsidy and fees
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoveLiquidityRequest {
+    pub pool_id: u64,
+    pub provider: String,
+    pub shares: u64,
+    pub nonce: u64,                // Unique per provider, so the signed request applies once
+}
+
+/// LP shares held by one provider
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProviderShare {
+    pub provider: String,
+    pub shares: u64,
+}
+
+/// A pool's liquidity account with its current exposure
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LiquiditySummary {
+    pub pool_id: u64,
+    pub maker: String,
+    pub subsidy: u64,
+    pub collateral: u64,
+    pub fees_earned: u64,
+    pub worst_case_loss: u64,
+    pub volume: u64,
+    pub outcomes: u8,
+    pub b: u64,                    // LMSR liquidity (scaled by 10^6)
+    pub schedule: LiquiditySchedule,
+    pub fees: FeeConfig,
+    pub lp_shares: u64,
+    pub providers: Vec<ProviderShare>,
+}
+
+impl LiquiditySummary {
+    fn new(pool_id: u64, account: &MakerAccount) -> Self {
+        Self {
+            pool_id,
+            maker: account.maker.clone(),
+            subsidy: account.subsidy,
+            collateral: account.collateral,
+            fees_earned: account.fees_earned,
+            worst_case_loss: account.worst_case_loss(),
+            volume: account.volume,
+            outcomes: account.outcomes,
+            b: scale_b(account.lmsr(account.outcomes as usize).b()),
+            schedule: account.schedule,
+            fees: account.fees.clone(),
+            lp_shares: account.lp_shares,
+            providers: account
+                .providers
+                .iter()
+                .map(|(provider, shares)| ProviderShare { provider: provider.clone(), shares: *shares })
+                .collect(),
+        }
+    }
+}
+
+/// Refuse a liquidity, fee or schedule request whose (signer, nonce) was already
+/// applied: its signature authorizes the payload, not one use of it
+fn check_request_nonce(nonces: &HashSet<(String, u64)>, signer: &str, nonce: u64) -> Result<(), EnclaveError> {
+    if nonces.contains(&(signer.to_string(), nonce)) {
+        return Err(EnclaveError::GenericError(format!("Nonce {nonce} of {signer} was already used")));
+    }
+    Ok(())
+}
+
+/// Move `amount` from the provider's vault balance into the pool's subsidy,
+/// raising its `b` at unchanged prices
+pub async fn add_liquidity(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<ProcessDataRequest<ResolveRequest>>,
-) -> Result<Json<ProcessedDataResponse<IntentMessage<ResolveResponse>>>, EnclaveError> {
+    Json(request): Json<ProcessDataRequest<AddLiquidityRequest>>,
+) -> Result<Json<LiquiditySummary>, EnclaveError> {
     let req = &request.payload;
-    
-    let payouts: Vec<Payout>;
-    let total_payout: u64;
-    
+    verify_request(req, request.signature.as_deref(), &req.provider)?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+
+    let _store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut nonces = state.pm.request_nonces.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    check_request_nonce(&nonces, &req.provider, req.nonce)?;
+    if state.pm.resolved_pools.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .contains_key(&req.pool_id)
     {
-        let mut store = POSITION_STORE.write()
-            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
-        
-        // Get winning positions
-        let winners = store.get_winning_positions(req.pool_id, req.winning_outcome);
-        
-        // Calculate payouts (shares / 1000 = payout in USDC units)
-        payouts = winners
-            .iter()
-            .map(|p| Payout {
-                user: p.wallet.clone(),
-                amount: p.shares * 1000,
-            })
-            .collect();
-        
-        total_payout = payouts.iter().map(|p| p.amount).sum();
-        
-        // Clear positions for this pool
-        store.clear_pool(req.pool_id);
+        return Err(EnclaveError::GenericError(format!("Pool {} is resolved", req.pool_id)));
     }
-    
-    let response = ResolveResponse {
-        success: true,
-        pool_id: req.pool_id,
-        winning_outcome: req.winning_outcome,
-        payouts,
-        total_payout,
+    let summary = {
+        let mut balances = state.pm.balances.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        let mut makers = state.pm.makers.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        if balances.balance(&req.provider) < req.amount {
+            return Err(EnclaveError::GenericError(format!(
+                "Insufficient balance for {} to add {}",
+                req.provider, req.amount
+            )));
+        }
+        makers.fund(req.pool_id, &req.provider, req.amount, req.outcomes)?;
+        balances.debit(&req.provider, req.amount)?;
+        LiquiditySummary::new(req.pool_id, makers.account(req.pool_id).expect("just funded"))
     };
-    
+    nonces.insert((req.provider.clone(), req.nonce));
+    record_event(
+        &state,
+        LedgerEvent::LiquidityAdded {
+            pool_id: req.pool_id,
+            provider: req.provider.clone(),
+            amount: req.amount,
+            outcomes: req.outcomes,
+            nonce: req.nonce,
+        },
+        timestamp_ms,
+    )?;
+    Ok(Json(summary))
+}
+
+/// Burn LP shares and credit their part of the subsidy and fees back to the
+/// provider, lowering the pool's `b` at unchanged prices
+pub async fn remove_liquidity(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<RemoveLiquidityRequest>>,
+) -> Result<Json<LiquiditySummary>, EnclaveError> {
+    let req = &request.payload;
+    verify_request(req, request.signature.as_deref(), &req.provider)?;
     let timestamp_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
         .as_millis() as u64;
-    
-    Ok(Json(to_signed_response(
-        &state.eph_kp,
-        response,
+
+    let _store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut nonces = state.pm.request_nonces.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    check_request_nonce(&nonces, &req.provider, req.nonce)?;
+    let summary = {
+        let mut balances = state.pm.balances.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        let mut makers = state.pm.makers.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        let released = makers.withdraw(req.pool_id, &req.provider, req.shares)?;
+        balances.credit(&req.provider, released);
+        LiquiditySummary::new(req.pool_id, makers.account(req.pool_id).expect("just withdrawn"))
+    };
+    nonces.insert((req.provider.clone(), req.nonce));
+    record_event(
+        &state,
+        LedgerEvent::LiquidityRemoved {
+            pool_id: req.pool_id,
+            provider: req.provider.clone(),
+            shares: req.shares,
+            nonce: req.nonce,
+        },
         timestamp_ms,
-        IntentScope::Resolve as u8,
-    )))
+    )?;
+    Ok(Json(summary))
 }
 
-// ============================================================
-// LIST POSITIONS ENDPOINT (Debug)
-// ============================================================
-#[derive(Deserialize)]
-pub struct GetPositionsParams {
+/// Request to change a pool's fee settings
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetPoolFeesRequest {
     pub pool_id: u64,
+    pub maker: String,
+    pub taker_bps: u64,
+    #[serde(default)]
+    pub protocol_share_bps: u64,
+    #[serde(default)]
+    pub referrer_share_bps: u64,
+    pub nonce: u64,                // Unique per maker, so old settings cannot be resent
 }
 
-pub async fn get_positions_handler(
-    State(_state): State<Arc<AppState>>,
-    Query(params): Query<GetPositionsParams>,
-) -> Result<Json<Vec<state::Position>>, EnclaveError> {
-    let store = POSITION_STORE.read()
+/// Set the fees of a pool for bets signed from now on. The protocol share goes to
+/// `PM_PROTOCOL_ADDRESS`, so it must be configured to charge one.
+pub async fn set_pool_fees(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<SetPoolFeesRequest>>,
+) -> Result<Json<LiquiditySummary>, EnclaveError> {
+    let req = &request.payload;
+    verify_request(req, request.signature.as_deref(), &req.maker)?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    let fees = FeeConfig {
+        taker_bps: req.taker_bps,
+        protocol_share_bps: req.protocol_share_bps,
+        referrer_share_bps: req.referrer_share_bps,
+        protocol: match req.protocol_share_bps {
+            0 => String::new(),
+            _ => state.pm.settings.protocol.clone().unwrap_or_default(),
+        },
+    };
+
+    let _store = state.positions.write()
         .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
-    Ok(Json(store.get_positions_by_pool(params.pool_id)))
+    let mut nonces = state.pm.request_nonces.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    check_request_nonce(&nonces, &req.maker, req.nonce)?;
+    let summary = {
+        let mut makers = state.pm.makers.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        LiquiditySummary::new(req.pool_id, makers.set_fees(req.pool_id, &req.maker, fees.clone())?)
+    };
+    nonces.insert((req.maker.clone(), req.nonce));
+    record_event(
+        &state,
+        LedgerEvent::FeesSet {
+            pool_id: req.pool_id,
+            maker: req.maker.clone(),
+            fees,
+            nonce: req.nonce,
+        },
+        timestamp_ms,
+    )?;
+    Ok(Json(summary))
+}
+
+/// Request to change how a pool's `b` follows its traded volume
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetLiquidityScheduleRequest {
+    pub pool_id: u64,
+    pub maker: String,
+    pub alpha_bps: u64,            // b per unit of volume, in bps (0 keeps b fixed by the subsidy)
+    pub nonce: u64,                // Unique per maker, so old settings cannot be resent
+}
+
+/// Set the liquidity schedule of a pool for bets signed from now on. Prices stay
+/// where they are; only the depth behind them changes.
+pub async fn set_liquidity_schedule(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<SetLiquidityScheduleRequest>>,
+) -> Result<Json<LiquiditySummary>, EnclaveError> {
+    let req = &request.payload;
+    verify_request(req, request.signature.as_deref(), &req.maker)?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    let schedule = LiquiditySchedule { alpha_bps: req.alpha_bps };
+
+    let _store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut nonces = state.pm.request_nonces.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    check_request_nonce(&nonces, &req.maker, req.nonce)?;
+    let summary = {
+        let mut makers = state.pm.makers.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        LiquiditySummary::new(req.pool_id, makers.set_schedule(req.pool_id, &req.maker, schedule)?)
+    };
+    nonces.insert((req.maker.clone(), req.nonce));
+    record_event(
+        &state,
+        LedgerEvent::ScheduleSet {
+            pool_id: req.pool_id,
+            maker: req.maker.clone(),
+            schedule,
+            nonce: req.nonce,
+        },
+        timestamp_ms,
+    )?;
+    Ok(Json(summary))
+}
+
+#[derive(Deserialize)]
+pub struct GetLiquidityParams {
+    pub pool_id: u64,
+}
+
+pub async fn get_liquidity(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetLiquidityParams>,
+) -> Result<Json<LiquiditySummary>, EnclaveError> {
+    let makers = state.pm.makers.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let account = makers
+        .account(params.pool_id)
+        .ok_or_else(|| EnclaveError::GenericError(format!("Pool {} has no liquidity", params.pool_id)))?;
+    Ok(Json(LiquiditySummary::new(params.pool_id, account)))
+}
+
+// ============================================================
+// VAULT BALANCE ENDPOINTS
+// ============================================================
+
+/// Apply a relayed vault deposit or withdrawal and return the user's new balance
+pub async fn apply_vault_event(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<SignedVaultEvent>>,
+) -> Result<Json<u64>, EnclaveError> {
+    let signed = &request.payload;
+    let relayer = state.pm.settings.vault_relayer
+        .as_ref()
+        .ok_or_else(|| EnclaveError::GenericError("VAULT_RELAYER_PK is not configured".into()))?;
+    verify_vault_event(relayer, signed)?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+
+    let _store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let balance = state.pm.balances.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .apply_event(&signed.event)?;
+    record_event(&state, LedgerEvent::Vault { event: signed.event.clone() }, timestamp_ms)?;
+    Ok(Json(balance))
+}
+
+#[derive(Deserialize)]
+pub struct GetBalanceParams {
+    pub wallet: String,
+}
+
+pub async fn get_balance(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetBalanceParams>,
+) -> Result<Json<u64>, EnclaveError> {
+    let balances = state.pm.balances.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    Ok(Json(balances.balance(&params.wallet)))
+}
+
+/// Convert probabilities (scaled by 10000) to LMSR quantities at liquidity `b`
+pub fn probs_to_quantities(probs: &[u64], b: f64) -> Vec<f64> {
+    probs
+        .iter()
+        .map(|&p| {
+            let prob = (p as f64) / 10000.0;
+            if prob > 0.0 {
+                b * prob.ln()
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Price a bet with LMSR on top of pool state `tip`, after taking the pool's fee.
+/// The pool's liquidity account sets `b` and the fees. Pure, so the ledger replay
+/// can recompute it. Post-trade balances are left at zero for `Balances::settle_bet`.
+pub fn quote_bet(req: &PlaceBetRequest, tip: &PoolHead, pool: &MakerAccount) -> PlaceBetResponse {
+    // Convert current probs to f64 for LMSR
+    let lmsr = pool.lmsr(pool.outcomes as usize);
+    let current_quantities = probs_to_quantities(&req.current_probs, lmsr.b());
+    let fees = &pool.fees;
+    let fee = fees.split(req.amount, req.referrer.is_some());
+    
+    // Calculate shares using LMSR; only the amount left after the fee buys shares
+    let outcomes = vec![req.outcome as usize];
+    let amount_f64 = ((req.amount - fee.total) as f64) / 1_000_000.0;
+    
+    let (shares_f64, new_quantities) = lmsr.shares_for_amount(&current_quantities, &outcomes, amount_f64);
+    let new_prices = lmsr.prices(&new_quantities);
+    
+    PlaceBetResponse {
+        shares: (shares_f64 * 1000.0) as u64,
+        new_probs: new_prices.iter().map(|&p| (p * 10000.0) as u64).collect(),
+        pool_id: req.pool_id,
+        outcome: req.outcome,
+        debit_amount: req.amount,
+        credit_amount: fee.maker,
+        user: req.user.clone(),
+        nonce: req.nonce,
+        seq: tip.seq + 1,
+        prev_state_hash: tip.state_hash.clone(),
+        user_balance: 0,
+        maker_balance: 0,
+        fee_amount: fee.total,
+        protocol: if fee.protocol > 0 { fees.protocol.clone() } else { String::new() },
+        protocol_fee: fee.protocol,
+        protocol_balance: 0,
+        referrer: if fee.referrer > 0 { req.referrer.clone().unwrap_or_default() } else { String::new() },
+        referrer_fee: fee.referrer,
+        referrer_balance: 0,
+        b: scale_b(lmsr.b()),
+    }
+}
+
+/// LMSR `b` in amount units (scaled by 10^6)
+fn scale_b(b: f64) -> u64 {
+    (b * 1_000_000.0).round() as u64
+}
+
+// ============================================================
+// RESOLVE ENDPOINT
+// ============================================================
+pub async fn resolve(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ProcessDataRequest<ResolveRequest>>,
+) -> Result<Json<ResolveResult>, EnclaveError> {
+    let req = &request.payload;
+    let resolver = state.pm.settings.resolver
+        .as_deref()
+        .ok_or_else(|| EnclaveError::GenericError("PM_RESOLVER_ADDRESS is not configured".into()))?;
+    verify_request(req, request.signature.as_deref(), resolver)?;
+    
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    
+    let (resolution, resolved) = {
+        let mut store = state.positions.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        
+        // A pool resolves once: its signed payout batches are final
+        if state.pm.resolved_pools.read()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .contains_key(&req.pool_id)
+        {
+            return Err(EnclaveError::Rejected {
+                message: format!("Pool {} is already resolved", req.pool_id),
+                reason: serde_json::json!({ "code": "already_resolved", "pool_id": req.pool_id }),
+            });
+        }
+        
+        // Bets still pending now can never be paid, so they are refunded before the
+        // liquidity providers' share of the pool is worked out
+        let dropped = state.pm.pending_bets.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .clear_pool(req.pool_id);
+        refund_bets(&state, &dropped)?;
+        let pool = state.pm.makers.read()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .account(req.pool_id)
+            .cloned();
+        
+        let response = compute_resolution(store.as_ref(), req.pool_id, req.winning_outcome, pool.as_ref())?;
+        let resolution = sign_pm(&state, IntentScope::Resolve, response, timestamp_ms);
+        
+        // Clear positions for this pool and pay winners and providers
+        store.clear_pool(req.pool_id)?;
+        state.pm.balances.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .pay_out(&resolution.response.data);
+        state.pm.makers.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .close(req.pool_id);
+        state.pm.breaker.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .clear_pool(req.pool_id);
+        state.pm.sequencer.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .clear_pending(req.pool_id);
+        commit_pool(&state, store.as_ref(), req.pool_id, timestamp_ms)?;
+        state.pm.pool_probs.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .remove(&req.pool_id);
+        record_event(
+            &state,
+            LedgerEvent::Resolved {
+                pool_id: req.pool_id,
+                winning_outcome: req.winning_outcome,
+                signature: resolution.signature.clone(),
+            },
+            timestamp_ms,
+        )?;
+        
+        // Split winners into batches and keep them for /resolve/batch
+        let resolved = ResolvedPool {
+            winning_outcome: req.winning_outcome,
+            timestamp_ms,
+            batches: split_payouts(
+                &resolution.response.data.payouts,
+                req.batch_size.unwrap_or(DEFAULT_PAYOUT_BATCH_SIZE),
+            ),
+        };
+        state.pm.resolved_pools.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .insert(req.pool_id, resolved.clone());
+        (resolution, resolved)
+    };
+    
+    let batches = (0..resolved.batches.len() as u64)
+        .filter_map(|i| resolved.batch(req.pool_id, i))
+        .map(|batch| sign_pm(&state, IntentScope::PayoutBatch, batch, timestamp_ms))
+        .collect();
+    
+    Ok(Json(ResolveResult {
+        resolution,
+        batches,
+    }))
+}
+
+/// Build the resolution of a pool from the current (confirmed) positions and
+/// its liquidity account, whose providers share what is left after winners are
+/// paid. Pure, so the ledger replay can recompute it.
+pub fn compute_resolution(
+    store: &dyn PositionRepository,
+    pool_id: u64,
+    winning_outcome: u8,
+    pool: Option<&MakerAccount>,
+) -> Result<ResolveResponse, EnclaveError> {
+    // Get winning positions
+    let winners = store.get_winning_positions(pool_id, winning_outcome)?;
+    
+    // Calculate payouts (shares / 1000 = payout in USDC units), one per wallet
+    let mut amounts: BTreeMap<String, u64> = BTreeMap::new();
+    for p in &winners {
+        *amounts.entry(p.wallet.clone()).or_insert(0) += p.shares * PAYOUT_PER_SHARE;
+    }
+    let winners_payout: u64 = amounts.values().sum();
+    for (provider, amount) in pool.map(|pool| pool.distribution(winners_payout)).unwrap_or_default() {
+        *amounts.entry(provider).or_insert(0) += amount;
+    }
+    
+    // Stable order (by wallet) so batch contents are deterministic
+    let payouts: Vec<Payout> = amounts
+        .into_iter()
+        .filter(|(_, amount)| *amount > 0)
+        .map(|(user, amount)| Payout { user, amount })
+        .collect();
+    
+    let total_payout = payouts.iter().map(|p| p.amount).sum();
+    
+    Ok(ResolveResponse {
+        success: true,
+        pool_id,
+        winning_outcome,
+        payouts,
+        total_payout,
+    })
+}
+
+// ============================================================
+// PAYOUT BATCH ENDPOINT
+// ============================================================
+#[derive(Deserialize)]
+pub struct GetPayoutBatchParams {
+    pub pool_id: u64,
+    pub batch_index: u64,
+}
+
+/// Re-fetch a signed payout batch. Batches are re-signed with the original
+/// resolution timestamp, so the bytes are identical to those returned by /resolve.
+pub async fn get_payout_batch(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPayoutBatchParams>,
+) -> Result<Json<ProcessedDataResponse<DomainIntentMessage<PayoutBatch>>>, EnclaveError> {
+    let resolved_pools = state.pm.resolved_pools.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let resolved = resolved_pools
+        .get(&params.pool_id)
+        .ok_or_else(|| EnclaveError::GenericError(format!("Pool {} is not resolved", params.pool_id)))?;
+    let batch = resolved
+        .batch(params.pool_id, params.batch_index)
+        .ok_or_else(|| EnclaveError::GenericError(format!(
+            "Batch {} out of range ({} batches)",
+            params.batch_index,
+            resolved.batches.len()
+        )))?;
+    
+    Ok(Json(sign_pm(&state, IntentScope::PayoutBatch, batch, resolved.timestamp_ms)))
+}
+
+// ============================================================
+// LEDGER
+// ============================================================
+
+/// Append an event to the ledger, signing the head every LEDGER_CHECKPOINT_INTERVAL entries
+fn record_event(state: &AppState, event: LedgerEvent, timestamp_ms: u64) -> Result<(), EnclaveError> {
+    let mut ledger = state.pm.ledger.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    ledger.append(event, timestamp_ms);
+    if ledger.len() % ledger::LEDGER_CHECKPOINT_INTERVAL == 0 {
+        let head = sign_pm(state, IntentScope::LedgerHead, ledger.head(), timestamp_ms);
+        ledger.add_checkpoint(head);
+    }
+    Ok(())
+}
+
+/// Export every ledger entry and signed checkpoint (input for the replay tool)
+pub async fn get_ledger(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<LedgerExport>, EnclaveError> {
+    let ledger = state.pm.ledger.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    Ok(Json(ledger.export()))
+}
+
+/// Sign the current ledger head
+pub async fn get_ledger_head(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ProcessedDataResponse<DomainIntentMessage<LedgerHead>>>, EnclaveError> {
+    let ledger = state.pm.ledger.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    Ok(Json(sign_pm(&state, IntentScope::LedgerHead, ledger.head(), timestamp_ms)))
+}
+
+// ============================================================
+// POSITION COMMITMENTS
+// ============================================================
+
+/// Signed Merkle root over all positions of a pool
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PositionsRoot {
+    pub pool_id: u64,
+    pub position_count: u64,
+    pub root: Vec<u8>,
+}
+
+/// A position together with its inclusion proof
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PositionProof {
+    pub position: state::Position,
+    pub proof: MerkleProof,
+}
+
+/// Response of /positions/proof
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PositionProofResponse {
+    pub signed_root: ProcessedDataResponse<DomainIntentMessage<PositionsRoot>>,
+    pub proofs: Vec<PositionProof>,
+}
+
+/// A pool's positions in canonical (wallet, outcome) order and the Merkle tree
+/// over their BCS encodings
+pub fn pool_tree(
+    store: &dyn PositionRepository,
+    pool_id: u64,
+) -> Result<(Vec<state::Position>, MerkleTree), EnclaveError> {
+    let mut positions = store.get_positions_by_pool(pool_id)?;
+    positions.sort_by(|a, b| (&a.wallet, a.outcome).cmp(&(&b.wallet, b.outcome)));
+    let leaves: Vec<Vec<u8>> = positions
+        .iter()
+        .map(|p| bcs::to_bytes(p).expect("should not fail"))
+        .collect();
+    let tree = MerkleTree::new(&leaves);
+    Ok((positions, tree))
+}
+
+/// Recompute and sign a pool's positions root after it changed
+fn commit_pool(
+    state: &AppState,
+    store: &dyn PositionRepository,
+    pool_id: u64,
+    timestamp_ms: u64,
+) -> Result<ProcessedDataResponse<DomainIntentMessage<PositionsRoot>>, EnclaveError> {
+    let (_, tree) = pool_tree(store, pool_id)?;
+    let signed_root = sign_pm(
+        state,
+        IntentScope::PositionsRoot,
+        PositionsRoot {
+            pool_id,
+            position_count: tree.len() as u64,
+            root: tree.root().to_vec(),
+        },
+        timestamp_ms,
+    );
+    let mut roots = state.pm.position_roots.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    roots.insert(pool_id, signed_root.clone());
+    Ok(signed_root)
+}
+
+#[derive(Deserialize)]
+pub struct GetPositionProofParams {
+    pub pool_id: u64,
+    pub wallet: String,
+}
+
+/// Latest signed positions root of a pool and inclusion proofs for every
+/// position of `wallet` in it
+pub async fn get_position_proof(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPositionProofParams>,
+) -> Result<Json<PositionProofResponse>, EnclaveError> {
+    let store = state.positions.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let (positions, tree) = pool_tree(store.as_ref(), params.pool_id)?;
+
+    // Roots are not persisted in snapshots; sign afresh if this pool has none yet
+    let cached = state.pm.position_roots.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .get(&params.pool_id)
+        .filter(|signed| signed.response.data.root == tree.root())
+        .cloned();
+    let signed_root = match cached {
+        Some(signed_root) => signed_root,
+        None => {
+            let timestamp_ms = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+                .as_millis() as u64;
+            commit_pool(&state, store.as_ref(), params.pool_id, timestamp_ms)?
+        }
+    };
+
+    let proofs = positions
+        .into_iter()
+        .enumerate()
+        .filter(|(_, p)| p.wallet == params.wallet)
+        .filter_map(|(i, position)| tree.proof(i).map(|proof| PositionProof { position, proof }))
+        .collect();
+
+    Ok(Json(PositionProofResponse {
+        signed_root,
+        proofs,
+    }))
+}
+
+// ============================================================
+// PORTFOLIO ENDPOINT
+// ============================================================
+#[derive(Deserialize)]
+pub struct GetPortfolioParams {
+    pub wallet: String,
+}
+
+pub async fn get_portfolio(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPortfolioParams>,
+) -> Result<Json<portfolio::Portfolio>, EnclaveError> {
+    let positions = state.positions.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .get_wallet_positions(&params.wallet)?;
+    let pool_probs = state.pm.pool_probs.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    Ok(Json(portfolio::build_portfolio(&params.wallet, positions, &pool_probs)))
+}
+
+// ============================================================
+// ATTESTATION ENDPOINT
+// ============================================================
+
+/// Configuration the enclave runs with - its BCS hash is bound into attestations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PmConfig {
+    pub risk_limits: RiskLimits,
+    pub protocol: String,          // PM_PROTOCOL_ADDRESS ('' if unset)
+    pub resolver: String,          // PM_RESOLVER_ADDRESS ('' if unset)
+    pub vault_relayer: String,     // Hex VAULT_RELAYER_PK ('' if unset)
+    pub chain_id: Vec<u8>,         // SUI_CHAIN_ID every message is signed for
+    pub pools: Vec<maker::PoolConfig>, // Pool registry, by pool id
+    pub resumed_from: SnapshotHead, // Snapshot the state was restored from at boot
+}
+
+impl PmConfig {
+    pub fn hash(&self) -> Vec<u8> {
+        Sha256::digest(bcs::to_bytes(self).expect("should not fail")).digest.to_vec()
+    }
+}
+
+/// Attestation plus the configuration whose hash it binds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PmAttestationResponse {
+    #[serde(flatten)]
+    pub attestation: GetAttestationResponse,
+    pub config: PmConfig,
+}
+
+fn current_config(state: &AppState) -> Result<PmConfig, EnclaveError> {
+    let pools = state.pm.makers.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .pool_configs();
+    let settings = &state.pm.settings;
+    Ok(PmConfig {
+        risk_limits: settings.risk_limits.clone(),
+        protocol: settings.protocol.clone().unwrap_or_default(),
+        resolver: settings.resolver.clone().unwrap_or_default(),
+        vault_relayer: settings.vault_relayer.as_ref().map(|pk| Hex::encode(pk.as_bytes())).unwrap_or_default(),
+        chain_id: settings.chain_id.to_vec(),
+        pools,
+        resumed_from: state.pm.resumed_from.read()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+            .clone(),
+    })
+}
+
+/// Refuse an enclave key the PM contract cannot check: Move
+/// `enclave::verify_domain_signature` only verifies Ed25519 signatures
+pub fn check_signature_scheme(scheme: SignatureScheme) -> Result<(), EnclaveError> {
+    match scheme {
+        SignatureScheme::Ed25519 => Ok(()),
+        other => Err(EnclaveError::GenericError(format!(
+            "PM signatures are verified on chain as Ed25519, ENCLAVE_SIGNATURE_SCHEME {other:?} is not supported"
+        ))),
+    }
+}
+
+/// User data of PM attestations: the app id and the current configuration hash
+fn attestation_user_data(config: &PmConfig) -> AttestationUserData {
+    AttestationUserData::new("pm", config.hash())
+}
+
+/// Attestation binding the caller's nonce and the hash of the current PM
+/// configuration. The configuration is returned alongside so a verifier can
+/// recompute the hash.
+pub async fn get_attestation(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetAttestationParams>,
+) -> Result<Json<PmAttestationResponse>, EnclaveError> {
+    let config = current_config(&state)?;
+    let attestation = attest(&state, params.nonce_bytes()?, attestation_user_data(&config))?;
+    Ok(Json(PmAttestationResponse { attestation, config }))
+}
+
+/// Rotate the enclave key and record the handoff in the ledger, attesting the new
+/// key with the same user data as /get_attestation. The positions lock orders the
+/// rotation against every signature recorded in the ledger.
+pub fn rotate_key(state: &AppState) -> Result<KeyRotation, EnclaveError> {
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    let config = current_config(state)?;
+    let handoff = {
+        let _store = state.positions.write()
+            .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+        let handoff = state.eph_kp.rotate(timestamp_ms, state.pm.settings.chain_id);
+        record_event(state, LedgerEvent::KeyRotated { handoff: handoff.clone() }, timestamp_ms)?;
+        handoff
+    };
+    let attestation = attest(state, None, attestation_user_data(&config))?;
+    Ok(KeyRotation { handoff, attestation })
+}
+
+/// Record the key this enclave booted with, after any snapshot was restored, so
+/// a replay can tell which key signed the entries that follow
+pub fn record_enclave_start(state: &AppState) -> Result<(), EnclaveError> {
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    let _store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let started = LedgerEvent::EnclaveStarted {
+        public_key: state.eph_kp.public_key_bytes(),
+        scheme: state.eph_kp.scheme(),
+    };
+    record_event(state, started, timestamp_ms)
+}
+
+// ============================================================
+// VERIFY ENDPOINT
+// ============================================================
+
+/// Check a signed response of any PM intent scope against this enclave's key, or
+/// the public key in the request, for the PM app on the configured chain. An
+/// invalid signature is reported, not an error.
+pub async fn verify(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<DomainVerifyRequest>,
+) -> Result<Json<VerifyResponse>, EnclaveError> {
+    let timestamp_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    let enclave_keys = state.eph_kp.verifying_keys(timestamp_ms);
+    let scope = IntentScope::try_from(request.signed.response.header.scope)?;
+    let chain_id = state.pm.settings.chain_id;
+
+    // The first key the signature verifies with, or the result for the first key
+    let mut first = None;
+    for pk in request.public_keys(&enclave_keys)? {
+        let result = match scope {
+            IntentScope::PlaceBet => request.verify_as::<PlaceBetResponse>(&pk, &enclave_keys, APP_PM, chain_id),
+            IntentScope::Resolve => request.verify_as::<ResolveResponse>(&pk, &enclave_keys, APP_PM, chain_id),
+            IntentScope::PayoutBatch => request.verify_as::<PayoutBatch>(&pk, &enclave_keys, APP_PM, chain_id),
+            IntentScope::LedgerHead => request.verify_as::<LedgerHead>(&pk, &enclave_keys, APP_PM, chain_id),
+            IntentScope::PositionsRoot => request.verify_as::<PositionsRoot>(&pk, &enclave_keys, APP_PM, chain_id),
+            IntentScope::BatchRoot => request.verify_as::<BatchRoot>(&pk, &enclave_keys, APP_PM, chain_id),
+        }?;
+        if result.valid {
+            return Ok(Json(result));
+        }
+        first.get_or_insert(result);
+    }
+    first
+        .map(Json)
+        .ok_or_else(|| EnclaveError::GenericError("No key to verify with".into()))
+}
+
+// ============================================================
+// LIST POSITIONS ENDPOINT (Debug)
+// ============================================================
+#[derive(Deserialize)]
+pub struct GetPositionsParams {
+    pub pool_id: u64,
+}
+
+pub async fn get_positions_handler(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetPositionsParams>,
+) -> Result<Json<Vec<state::Position>>, EnclaveError> {
+    let store = state.positions.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    Ok(Json(store.get_positions_by_pool(params.pool_id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::EnclaveKeyPair;
+    use crate::intent::SUI_MAINNET;
+    use crate::nsm::MockNsm;
+    use crate::rotation::EphemeralKeys;
+    use crate::wallet::{sign_request, sui_address};
+    use fastcrypto::ed25519::Ed25519KeyPair;
+    use fastcrypto::encoding::{Encoding, Hex};
+    use fastcrypto::traits::{KeyPair, Signer};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn test_state() -> Arc<AppState> {
+        test_state_with(settings())
+    }
+
+    /// Vault relayer key of the test settings
+    fn relayer() -> Ed25519KeyPair {
+        Ed25519KeyPair::generate(&mut StdRng::from_seed([7; 32]))
+    }
+
+    fn settings() -> PmSettings {
+        PmSettings { vault_relayer: Some(relayer().public().clone()), ..Default::default() }
+    }
+
+    fn relayer_signed(kp: &Ed25519KeyPair, confirmation: ConfirmBetRequest) -> SignedConfirmation {
+        let signature = Hex::encode(kp.sign(&bcs::to_bytes(&confirmation).unwrap()).as_ref());
+        SignedConfirmation { confirmation, signature }
+    }
+
+    /// Fresh state, so tests share nothing
+    fn test_state_with(settings: PmSettings) -> Arc<AppState> {
+        Arc::new(AppState {
+            eph_kp: EphemeralKeys::new(EnclaveKeyPair::generate(SignatureScheme::Ed25519)),
+            api_key: String::new(),
+            positions: RwLock::new(Box::new(state::PositionStore::new())),
+            nsm: Box::new(MockNsm::new(Default::default(), &mut rand::thread_rng())),
+            pm: PmState::new(settings),
+        })
+    }
+
+    /// A wallet key and its address
+    fn wallet() -> (EnclaveKeyPair, String) {
+        let kp = EnclaveKeyPair::generate(SignatureScheme::Ed25519);
+        let address = sui_address(&kp.public());
+        (kp, address)
+    }
+
+    fn signed<T: Serialize>(kp: &EnclaveKeyPair, payload: T) -> Json<ProcessDataRequest<T>> {
+        let signature = Some(sign_request(kp, &payload));
+        Json(ProcessDataRequest { payload, signature })
+    }
+
+    /// Credit a wallet through a relayed vault deposit, so it is in the ledger
+    async fn deposit(state: &Arc<AppState>, user: &str, amount: u64) {
+        let event = balances::VaultEvent {
+            event_id: format!("0x{:x}", rand::random::<u64>()),
+            kind: balances::VaultEventKind::Deposit,
+            user: user.to_string(),
+            amount,
+        };
+        let signature = Hex::encode(relayer().sign(&bcs::to_bytes(&event).unwrap()).as_ref());
+        let payload = SignedVaultEvent { event, signature };
+        let _ = apply_vault_event(State(state.clone()), Json(ProcessDataRequest { payload, signature: None }))
+            .await
+            .unwrap();
+    }
+
+    async fn fund(state: &Arc<AppState>, pool_id: u64, provider: &EnclaveKeyPair, amount: u64) -> LiquiditySummary {
+        let address = sui_address(&provider.public());
+        deposit(state, &address, amount).await;
+        add_liquidity(
+            State(state.clone()),
+            signed(provider, AddLiquidityRequest { pool_id, provider: address, amount, outcomes: 8, nonce: rand::random() }),
+        )
+        .await
+        .map(|Json(summary)| summary)
+        .unwrap()
+    }
+
+    /// The BetSubmitted event of a pending bet (or of an unknown one)
+    fn submitted(state: &Arc<AppState>, user: &str, nonce: u64) -> ConfirmBetRequest {
+        let pending = state.pm.pending_bets.read().unwrap();
+        let bet = pending.get(user, nonce);
+        ConfirmBetRequest {
+            user: user.to_string(),
+            nonce,
+            pool_id: bet.map_or(0, |bet| bet.pool_id),
+            outcome: bet.map_or(0, |bet| bet.outcome),
+            shares: bet.map_or(0, |bet| bet.shares),
+            tx_digest: "digest".to_string(),
+        }
+    }
+
+    async fn confirm_signed(state: &Arc<AppState>, payload: SignedConfirmation) -> Result<PendingBet, EnclaveError> {
+        confirm_bet(State(state.clone()), Json(ProcessDataRequest { payload, signature: None }))
+            .await
+            .map(|Json(bet)| bet)
+    }
+
+    async fn confirm(state: &Arc<AppState>, user: &str, nonce: u64) -> Result<PendingBet, EnclaveError> {
+        confirm_signed(state, relayer_signed(&relayer(), submitted(state, user, nonce))).await
+    }
+
+    #[tokio::test]
+    async fn test_bet_then_resolve_with_isolated_store() {
+        let (resolver_kp, resolver) = wallet();
+        let state = test_state_with(PmSettings { resolver: Some(resolver), ..settings() });
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        state.pm.balances.write().unwrap().credit(&user, 1_000_000);
+        fund(&state, 42, &maker_kp, 10_000_000).await;
+        let bet = process_data(
+            State(state.clone()),
+            signed(
+                &user_kp,
+                PlaceBetRequest {
+                    user: user.clone(),
+                    pool_id: 42,
+                    outcome: 3,
+                    amount: 1_000_000,
+                    maker: maker.clone(),
+                    current_probs: vec![1250; 8],
+                    nonce: 1,
+                    referrer: None,
+                },
+            ),
+        )
+        .await
+        .unwrap();
+        // Nothing is paid until the bet is confirmed on chain
+        assert!(state.positions.read().unwrap().get_positions_by_pool(42).unwrap().is_empty());
+        confirm(&state, &user, 1).await.unwrap();
+        assert!(confirm(&state, &user, 1).await.is_err());
+        assert_eq!(state.positions.read().unwrap().get_positions_by_pool(42).unwrap().len(), 1);
+
+        // Only the configured resolver may resolve
+        let request = ResolveRequest { pool_id: 42, winning_outcome: 3, batch_size: None };
+        assert!(resolve(State(state.clone()), signed(&maker_kp, request.clone())).await.is_err());
+        let result = resolve(State(state.clone()), signed(&resolver_kp, request.clone())).await.unwrap();
+        let resolution = &result.resolution.response.data;
+        // The winner and the pool's only provider, who gets back the subsidy and
+        // the stake less the winner's payout
+        assert_eq!(resolution.payouts.len(), 2);
+        assert_eq!(resolution.total_payout, 11_000_000);
+        assert!(state.positions.read().unwrap().get_positions_by_pool(42).unwrap().is_empty());
+        assert_eq!(
+            state.pm.balances.read().unwrap().balance(&maker),
+            11_000_000 - bet.response.data.shares * 1000
+        );
+        assert!(state.pm.makers.read().unwrap().account(42).is_none());
+
+        // Resolving again would re-sign batch 0 with other payouts
+        let again = resolve(State(state.clone()), signed(&resolver_kp, ResolveRequest { winning_outcome: 1, ..request }));
+        assert!(matches!(again.await, Err(EnclaveError::Rejected { .. })));
+        let Json(batch) = get_payout_batch(
+            State(state.clone()),
+            Query(GetPayoutBatchParams { pool_id: 42, batch_index: 0 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(batch.signature, result.batches[0].signature);
+    }
+
+    #[tokio::test]
+    async fn test_live_ledger_replays() {
+        let (resolver_kp, resolver) = wallet();
+        let state = test_state_with(PmSettings { resolver: Some(resolver), ..settings() });
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        deposit(&state, &user, 3_000_000).await;
+        deposit(&state, &maker, 20_000_000).await;
+        for pool_id in [47, 48] {
+            let request = AddLiquidityRequest { pool_id, provider: maker.clone(), amount: 10_000_000, outcomes: 8, nonce: pool_id };
+            let _ = add_liquidity(State(state.clone()), signed(&maker_kp, request)).await.unwrap();
+        }
+        let request = PlaceBetRequest {
+            user: user.clone(),
+            pool_id: 47,
+            outcome: 2,
+            amount: 1_000_000,
+            maker: maker.clone(),
+            current_probs: vec![1250; 8],
+            nonce: 1,
+            referrer: None,
+        };
+        let place = |payload: PlaceBetRequest| process_data(State(state.clone()), signed(&user_kp, payload));
+        let _ = place(request.clone()).await.unwrap();
+        let Json(second) = place(PlaceBetRequest { pool_id: 48, nonce: 2, ..request.clone() }).await.unwrap();
+        // Left pending when the ledger is exported
+        let _ = place(PlaceBetRequest {
+            pool_id: 48,
+            nonce: 3,
+            current_probs: second.response.data.new_probs.clone(),
+            ..request
+        })
+        .await
+        .unwrap();
+        confirm(&state, &user, 1).await.unwrap();
+        confirm(&state, &user, 2).await.unwrap();
+        let pool = SyncPoolRequest { pool_id: 49, seq: 4, probs: vec![1250; 8] };
+        let signature = Hex::encode(relayer().sign(&bcs::to_bytes(&pool).unwrap()).as_ref());
+        let payload = SignedPoolSync { sync: pool, signature };
+        let _ = sync_pool(State(state.clone()), Json(ProcessDataRequest { payload, signature: None })).await.unwrap();
+        let request = ResolveRequest { pool_id: 47, winning_outcome: 2, batch_size: None };
+        let _ = resolve(State(state.clone()), signed(&resolver_kp, request)).await.unwrap();
+
+        // The handlers' own ledger reproduces every signature and the positions
+        let export = state.pm.ledger.read().unwrap().export();
+        let report = ledger::replay(&export, &[state.eph_kp.public()], SUI_TESTNET).unwrap();
+        assert_eq!(report.entry_count, export.entries.len() as u64);
+        let live = state.positions.read().unwrap();
+        for pool_id in [47, 48] {
+            assert_eq!(
+                report.store.get_positions_by_pool(pool_id).unwrap(),
+                live.get_positions_by_pool(pool_id).unwrap()
+            );
+        }
+        assert_eq!(live.get_positions_by_pool(48).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batched_bet_is_only_signed_by_its_root() {
+        let state = test_state_with(PmSettings { batch_window: Duration::from_millis(20), ..settings() });
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        fund(&state, 50, &maker_kp, 10_000_000).await;
+        deposit(&state, &user, 1_000_000).await;
+        let request = PlaceBetRequest {
+            user: user.clone(),
+            pool_id: 50,
+            outcome: 4,
+            amount: 1_000_000,
+            maker,
+            current_probs: vec![1250; 8],
+            nonce: 1,
+            referrer: None,
+        };
+        let Json(batched) = process_data_batched(State(state.clone()), signed(&user_kp, request.clone()))
+            .await
+            .unwrap();
+        crate::batch::verify_batched_response(&state.eph_kp.public(), &batched, APP_PM, SUI_TESTNET).unwrap();
+        let export = state.pm.ledger.read().unwrap().export();
+        let placed = export.entries.iter().find_map(|entry| match &entry.event {
+            LedgerEvent::BetPlaced { signature, .. } => Some(signature.clone()),
+            _ => None,
+        });
+        assert_eq!(placed, Some(None));
+        ledger::replay(&export, &[state.eph_kp.public()], SUI_TESTNET).unwrap();
+
+        // A retry cannot obtain a signature of its own, only the same message again
+        assert!(process_data(State(state.clone()), signed(&user_kp, request.clone())).await.is_err());
+        let Json(retry) = process_data_batched(State(state.clone()), signed(&user_kp, request)).await.unwrap();
+        assert_eq!(bcs::to_bytes(&retry.response).unwrap(), bcs::to_bytes(&batched.response).unwrap());
+        assert_eq!(state.pm.pending_bets.read().unwrap().by_wallet(&user).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retried_bet_is_idempotent() {
+        let state = test_state();
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        let request = PlaceBetRequest {
+            user: user.clone(),
+            pool_id: 43,
+            outcome: 1,
+            amount: 2_000_000,
+            maker,
+            current_probs: vec![1250; 8],
+            nonce: 7,
+            referrer: None,
+        };
+        let place = |payload: PlaceBetRequest| process_data(State(state.clone()), signed(&user_kp, payload));
+
+        // Bets on a pool without liquidity or beyond the user's balance are refused
+        state.pm.balances.write().unwrap().credit(&user, 5_000_000);
+        assert!(place(request.clone()).await.is_err());
+        let summary = fund(&state, 43, &maker_kp, 30_000_000).await;
+        assert_eq!(summary.worst_case_loss, 0);
+        assert!(place(PlaceBetRequest { amount: 6_000_000, ..request.clone() }).await.is_err());
+        // So are bets in the user's name signed by another wallet, or unsigned
+        let forged = process_data(State(state.clone()), signed(&maker_kp, request.clone()));
+        assert!(forged.await.is_err());
+        let unsigned = Json(ProcessDataRequest { payload: request.clone(), signature: None });
+        assert!(process_data(State(state.clone()), unsigned).await.is_err());
+
+        let first = place(request.clone()).await.unwrap();
+        let retry = place(request.clone()).await.unwrap();
+        assert_eq!(first.signature, retry.signature);
+        assert_eq!(first.response.data.nonce, 7);
+        assert_eq!(first.response.data.user_balance, 3_000_000);
+        assert_eq!(state.pm.pending_bets.read().unwrap().by_wallet(&user).len(), 1);
+
+        // Reusing the nonce for a different bet is rejected
+        assert!(place(PlaceBetRequest { amount: 1, ..request.clone() }).await.is_err());
+        // A fresh nonce is a new bet, priced from the state the first one left
+        assert!(place(PlaceBetRequest { nonce: 8, ..request.clone() }).await.is_err());
+        let second = place(PlaceBetRequest {
+            nonce: 8,
+            current_probs: first.response.data.new_probs.clone(),
+            ..request
+        })
+        .await
+        .unwrap();
+        assert_eq!(second.response.data.seq, first.response.data.seq + 1);
+        assert_eq!(
+            second.response.data.prev_state_hash,
+            sequence::state_hash(43, first.response.data.seq, &first.response.data.new_probs)
+        );
+        confirm(&state, &user, 7).await.unwrap();
+        confirm(&state, &user, 8).await.unwrap();
+        let positions = state.positions.read().unwrap().get_user_positions(&user, 43).unwrap();
+        assert_eq!(positions[0].shares, first.response.data.shares + second.response.data.shares);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_requires_relayer_signature() {
+        let state = test_state();
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        fund(&state, 45, &maker_kp, 10_000_000).await;
+        state.pm.balances.write().unwrap().credit(&user, 1_000_000);
+        let request = PlaceBetRequest {
+            user: user.clone(),
+            pool_id: 45,
+            outcome: 2,
+            amount: 1_000_000,
+            maker,
+            current_probs: vec![1250; 8],
+            nonce: 1,
+            referrer: None,
+        };
+        let _ = process_data(State(state.clone()), signed(&user_kp, request)).await.unwrap();
+        let event = submitted(&state, &user, 1);
+
+        // Unsigned, signed by another key, or with a digest the relayer did not sign
+        let unsigned = SignedConfirmation { confirmation: event.clone(), signature: String::new() };
+        assert!(confirm_signed(&state, unsigned).await.is_err());
+        let other = Ed25519KeyPair::generate(&mut rand::thread_rng());
+        assert!(confirm_signed(&state, relayer_signed(&other, event.clone())).await.is_err());
+        let mut forged = relayer_signed(&relayer(), event.clone());
+        forged.confirmation.tx_digest = "forged".to_string();
+        assert!(confirm_signed(&state, forged).await.is_err());
+        // A relayed event that does not match the bet
+        let mismatched = ConfirmBetRequest { shares: event.shares + 1, ..event.clone() };
+        assert!(confirm_signed(&state, relayer_signed(&relayer(), mismatched)).await.is_err());
+        assert!(state.positions.read().unwrap().get_positions_by_pool(45).unwrap().is_empty());
+
+        // Without a configured relayer nothing can be confirmed
+        let unconfigured = test_state_with(PmSettings::default());
+        assert!(confirm_signed(&unconfigured, relayer_signed(&relayer(), event.clone())).await.is_err());
+
+        confirm_signed(&state, relayer_signed(&relayer(), event)).await.unwrap();
+        assert_eq!(state.positions.read().unwrap().get_positions_by_pool(45).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_requires_relayer_signature() {
+        let state = test_state();
+        let sync = |payload: SignedPoolSync| {
+            sync_pool(State(state.clone()), Json(ProcessDataRequest { payload, signature: None }))
+        };
+        let pool = SyncPoolRequest { pool_id: 46, seq: 3, probs: vec![1250; 8] };
+        let signature = Hex::encode(relayer().sign(&bcs::to_bytes(&pool).unwrap()).as_ref());
+
+        assert!(sync(SignedPoolSync { sync: pool.clone(), signature: String::new() }).await.is_err());
+        let other = Ed25519KeyPair::generate(&mut rand::thread_rng());
+        let forged = Hex::encode(other.sign(&bcs::to_bytes(&pool).unwrap()).as_ref());
+        assert!(sync(SignedPoolSync { sync: pool.clone(), signature: forged }).await.is_err());
+
+        let Json(head) = sync(SignedPoolSync { sync: pool.clone(), signature: signature.clone() }).await.unwrap();
+        assert_eq!(head.seq, 3);
+        // Replaying the same signed state cannot reset the pool's history
+        assert!(sync(SignedPoolSync { sync: pool, signature }).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_attests_pm_config() {
+        let state = test_state();
+        let Json(attested) = get_attestation(State(state.clone()), Query(Default::default())).await.unwrap();
+        let before = state.eph_kp.public();
+        let rotation = rotate_key(&state).unwrap();
+        assert_ne!(state.eph_kp.public(), before);
+        assert_eq!(rotation.attestation.user_data, attested.attestation.user_data);
+        assert_eq!(rotation.attestation.user_data, AttestationUserData::new("pm", attested.config.hash()));
+    }
+
+    #[test]
+    fn test_only_ed25519_enclave_keys() {
+        assert!(check_signature_scheme(SignatureScheme::Ed25519).is_ok());
+        assert!(check_signature_scheme(SignatureScheme::Secp256k1).is_err());
+        assert!(check_signature_scheme(SignatureScheme::Secp256r1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ledger_replays_across_rotation_and_restart() {
+        let state = test_state();
+        record_enclave_start(&state).unwrap();
+        let first = state.eph_kp.public();
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        fund(&state, 51, &maker_kp, 10_000_000).await;
+        deposit(&state, &user, 2_000_000).await;
+        let request = PlaceBetRequest {
+            user: user.clone(),
+            pool_id: 51,
+            outcome: 0,
+            amount: 1_000_000,
+            maker,
+            current_probs: vec![1250; 8],
+            nonce: 1,
+            referrer: None,
+        };
+        let Json(bet) = process_data(State(state.clone()), signed(&user_kp, request.clone())).await.unwrap();
+        rotate_key(&state).unwrap();
+        confirm(&state, &user, 1).await.unwrap();
+        ledger::replay(&state.pm.ledger.read().unwrap().export(), std::slice::from_ref(&first), SUI_TESTNET).unwrap();
+
+        // A restarted enclave carries the ledger on under a key of its own
+        let restarted = test_state();
+        snapshot::apply_snapshot(&restarted, snapshot::capture_snapshot(&state).unwrap()).unwrap();
+        record_enclave_start(&restarted).unwrap();
+        let next = PlaceBetRequest { nonce: 2, current_probs: bet.response.data.new_probs.clone(), ..request };
+        let _ = process_data(State(restarted.clone()), signed(&user_kp, next)).await.unwrap();
+        let head = restarted.pm.ledger.read().unwrap().head();
+        let checkpoint = to_signed_domain_response(&restarted.eph_kp, IntentScope::LedgerHead.header(SUI_TESTNET), head, 1744038900000);
+        restarted.pm.ledger.write().unwrap().add_checkpoint(checkpoint);
+
+        let export = restarted.pm.ledger.read().unwrap().export();
+        assert!(ledger::replay(&export, std::slice::from_ref(&first), SUI_TESTNET).is_err());
+        let report = ledger::replay(&export, &[first, restarted.eph_kp.public()], SUI_TESTNET).unwrap();
+        assert_eq!(report.checkpoint_count, 1);
+        assert_eq!(report.store.get_positions_by_pool(51).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_only_move_forward() {
+        let state = test_state();
+        let (_, user) = wallet();
+        let config = snapshot::SnapshotConfig {
+            key: [7u8; 32],
+            path: std::env::temp_dir().join(format!("pm_snapshot_{}.bin", std::process::id())),
+            interval: Duration::from_secs(60),
+            min_head: None,
+        };
+        deposit(&state, &user, 1_000_000).await;
+        let old_head = snapshot::save_snapshot(&state, &config).unwrap();
+        let old = std::fs::read(&config.path).unwrap();
+        deposit(&state, &user, 1_000_000).await;
+        let new_head = snapshot::save_snapshot(&state, &config).unwrap();
+        assert_eq!((old_head.generation, new_head.generation), (1, 2));
+
+        // The host serves the older snapshot: refused once the newer head is known
+        std::fs::write(&config.path, &old).unwrap();
+        let expecting = snapshot::SnapshotConfig { min_head: Some(new_head.clone()), ..config };
+        assert!(snapshot::restore_snapshot(&test_state(), &expecting).is_err());
+
+        // Without it the enclave resumes, but attests the head it resumed from
+        let restarted = test_state();
+        let config = snapshot::SnapshotConfig { min_head: None, ..expecting };
+        assert!(snapshot::restore_snapshot(&restarted, &config).unwrap());
+        assert_eq!(current_config(&restarted).unwrap().resumed_from, old_head);
+        assert_ne!(current_config(&restarted).unwrap().hash(), current_config(&test_state()).unwrap().hash());
+
+        // A running enclave never goes back to an older snapshot
+        snapshot::apply_snapshot(&restarted, snapshot::capture_snapshot(&state).unwrap()).unwrap();
+        std::fs::write(&config.path, &old).unwrap();
+        assert!(snapshot::restore_snapshot(&restarted, &config).is_err());
+        assert_eq!(restarted.pm.balances.read().unwrap().balance(&user), 2_000_000);
+
+        // Nor starts empty when a snapshot is expected
+        std::fs::remove_file(&config.path).unwrap();
+        assert!(!snapshot::restore_snapshot(&test_state(), &config).unwrap());
+        let expecting = snapshot::SnapshotConfig { min_head: Some(old_head), ..config };
+        assert!(snapshot::restore_snapshot(&test_state(), &expecting).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fees_are_itemized() {
+        let state = test_state();
+        let (user_kp, user) = wallet();
+        let (maker_kp, maker) = wallet();
+        fund(&state, 44, &maker_kp, 10_000_000).await;
+        state.pm.balances.write().unwrap().credit(&user, 1_000_000);
+        let set_fees = |protocol_share_bps: u64| {
+            set_pool_fees(
+                State(state.clone()),
+                signed(
+                    &maker_kp,
+                    SetPoolFeesRequest {
+                        pool_id: 44,
+                        maker: maker.clone(),
+                        taker_bps: 200,
+                        protocol_share_bps,
+                        referrer_share_bps: 2_500,
+                        nonce: protocol_share_bps,
+                    },
+                ),
+            )
+        };
+        // No protocol share without a protocol recipient
+        assert!(set_fees(1_000).await.is_err());
+        let summary = set_fees(0).await.unwrap();
+        assert_eq!(summary.fees.taker_bps, 200);
+        // A signed fee change applies once
+        assert!(set_fees(0).await.is_err());
+
+        // A stake beyond the balance is refused before pricing, without poisoning any lock
+        let oversized = PlaceBetRequest {
+            user: user.clone(),
+            pool_id: 44,
+            outcome: 0,
+            amount: u64::MAX,
+            maker: maker.clone(),
+            current_probs: vec![1250; 8],
+            nonce: 99,
+            referrer: None,
+        };
+        assert!(process_data(State(state.clone()), signed(&user_kp, oversized)).await.is_err());
+
+        let bet = process_data(
+            State(state.clone()),
+            signed(
+                &user_kp,
+                PlaceBetRequest {
+                    user: user.clone(),
+                    pool_id: 44,
+                    outcome: 0,
+                    amount: 1_000_000,
+                    maker: maker.clone(),
+                    current_probs: vec![1250; 8],
+                    nonce: 1,
+                    referrer: Some("0xreferrer44".to_string()),
+                },
+            ),
+        )
+        .await
+        .unwrap();
+        let response = &bet.response.data;
+        assert_eq!(response.fee_amount, 20_000);
+        assert_eq!((response.credit_amount, response.referrer_fee, response.protocol_fee), (15_000, 5_000, 0));
+        assert_eq!(response.referrer_balance, 5_000);
+        // The LPs' share stays in the pool until resolution
+        assert_eq!(response.maker_balance, 0);
+        let account = state.pm.makers.read().unwrap().account(44).unwrap().clone();
+        assert_eq!((account.collateral, account.fees_earned), (980_000, 15_000));
+        // Priced at the subsidy's b, 10 / ln(8)
+        assert_eq!(response.b, (10_000_000.0 / 8f64.ln()).round() as u64);
+    }
+
+    #[test]
+    fn test_scopes_registered() {
+        for scope in 0..=IntentScope::BatchRoot as u8 {
+            IntentScope::try_from(scope).unwrap();
+            assert!(crate::intent::lookup(crate::intent::APP_PM, scope).is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_signed_responses() {
+        let state = test_state();
+        let head = sign_pm(&state, IntentScope::LedgerHead, LedgerHead { entry_count: 3, head_hash: vec![7; 32] }, 1);
+        let check = |signed: &ProcessedDataResponse<DomainIntentMessage<LedgerHead>>, public_key: Option<String>| {
+            verify(
+                State(state.clone()),
+                Json(DomainVerifyRequest {
+                    signed: serde_json::from_value(serde_json::to_value(signed).unwrap()).unwrap(),
+                    public_key,
+                }),
+            )
+        };
+
+        let result = check(&head, None).await.unwrap();
+        assert!(result.valid && result.enclave_key);
+
+        let mut tampered = head.clone();
+        tampered.response.data.entry_count = 4;
+        let result = check(&tampered, None).await.unwrap();
+        assert!(!result.valid);
+        assert!(result.error.is_some());
+
+        // Signed for another chain
+        let mainnet = to_signed_domain_response(&state.eph_kp, IntentScope::LedgerHead.header(SUI_MAINNET), head.response.data.clone(), 1);
+        assert!(!check(&mainnet, None).await.unwrap().valid);
+
+        // Another key, given explicitly
+        let other = EnclaveKeyPair::generate(SignatureScheme::Ed25519);
+        let result = check(&head, Some(Hex::encode(other.public_key_bytes()))).await.unwrap();
+        assert!(!result.valid && !result.enclave_key);
+
+        // Signed before a rotation: still the enclave's within the overlap window
+        state.eph_kp.rotate(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64, SUI_TESTNET);
+        let result = check(&head, None).await.unwrap();
+        assert!(result.valid && result.enclave_key);
+        assert_ne!(result.public_key, Hex::encode(state.eph_kp.public_key_bytes()));
+
+        // Data of the wrong shape for its scope, or an unknown scope
+        let mut wrong = head.clone();
+        wrong.response.header.scope = IntentScope::PlaceBet as u8;
+        assert!(check(&wrong, None).await.is_err());
+        wrong.response.header.scope = 9;
+        assert!(check(&wrong, None).await.is_err());
+    }
+
+    fn payouts(n: usize) -> Vec<Payout> {
+        (0..n)
+            .map(|i| Payout { user: format!("0xuser{i}"), amount: 1000 })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_payouts() {
+        let batches = split_payouts(&payouts(250), 100);
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[2].len(), 50);
+
+        // No winners still produces a single (empty) batch to settle
+        assert_eq!(split_payouts(&[], 100).len(), 1);
+    }
+
+    #[test]
+    fn test_position_proofs_verify_against_root() {
+        let mut store = state::PositionStore::new();
+        store.add_position("0xuser1".to_string(), 1, 3, 1000, 100000).unwrap();
+        store.add_position("0xuser2".to_string(), 1, 5, 500, 50000).unwrap();
+        store.add_position("0xuser1".to_string(), 1, 5, 250, 25000).unwrap();
+        store.add_position("0xuser3".to_string(), 2, 0, 750, 75000).unwrap();
+
+        let (positions, tree) = pool_tree(&store, 1).unwrap();
+        assert_eq!(positions.len(), 3);
+        for (i, position) in positions.iter().enumerate() {
+            let leaf = bcs::to_bytes(position).unwrap();
+            assert!(crate::merkle::verify_proof(&tree.root(), &leaf, &tree.proof(i).unwrap()));
+        }
+
+        // Root is independent of insertion order
+        let mut reordered = state::PositionStore::new();
+        reordered.add_position("0xuser1".to_string(), 1, 5, 250, 25000).unwrap();
+        reordered.add_position("0xuser2".to_string(), 1, 5, 500, 50000).unwrap();
+        reordered.add_position("0xuser1".to_string(), 1, 3, 1000, 100000).unwrap();
+        assert_eq!(pool_tree(&reordered, 1).unwrap().1.root(), tree.root());
+    }
+
+    #[test]
+    fn test_resolved_pool_batch() {
+        let resolved = ResolvedPool {
+            winning_outcome: 3,
+            timestamp_ms: 1744038900000,
+            batches: split_payouts(&payouts(5), 2),
+        };
+        let batch = resolved.batch(7, 2).unwrap();
+        assert_eq!(batch.pool_id, 7);
+        assert_eq!(batch.total_batches, 3);
+        assert_eq!(batch.batch_payout, 1000);
+        assert!(resolved.batch(7, 3).is_none());
+    }
 }
@@ -0,0 +1,276 @@
+//! Pending bets awaiting on-chain confirmation
+//!
+//! A signed bet only becomes a position once the host reports that its
+//! `submit_bet` transaction executed on Sui (POST /confirm with the digest and the
+//! `BetSubmitted` event, signed by the vault relayer).
+//! Until then it is held here, keyed by (user, nonce). The chain rejects a signed
+//! bet older than `BET_TTL_MS`, but a bet that executed at the end of that window
+//! can still be reported late, so it stays confirmable for `CONFIRM_GRACE_MS`
+//! more. Only then does the enclave refund it and roll its pool back: refunding a
+//! bet that did execute would pay the user twice and leave the pool's sequence
+//! behind the chain's.
+
+use super::sequence::PoolSequencer;
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How long a signed bet can be submitted on chain, and so how long it is kept
+/// unconfirmed - must match Move BET_TTL_MS
+pub const BET_TTL_MS: u64 = 120_000;
+
+/// How long past `BET_TTL_MS` a bet can still be confirmed before it is refunded;
+/// must exceed the relayer's worst-case reporting delay plus clock skew
+pub const CONFIRM_GRACE_MS: u64 = 600_000;
+
+/// A signed bet that has not been confirmed on chain yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingBet {
+    pub user: String,
+    pub nonce: u64,
+    pub pool_id: u64,
+    pub outcome: u8,
+    pub shares: u64,
+    pub cost: u64,                 // Debited from the user
+    pub maker: String,
+    pub credit: u64,               // LPs' share of the fee, accrued in the pool
+    pub protocol: String,
+    pub protocol_fee: u64,         // Credited to the protocol
+    pub referrer: String,
+    pub referrer_fee: u64,         // Credited to the referrer
+    pub seq: u64,                  // Position of the bet in its pool's sequence
+    pub placed_at_ms: u64,
+}
+
+impl PendingBet {
+    /// Last moment the chain accepts the bet
+    pub fn expires_at_ms(&self) -> u64 {
+        self.placed_at_ms + BET_TTL_MS
+    }
+
+    /// Last moment a confirmation of the bet is accepted
+    pub fn confirmable_until_ms(&self) -> u64 {
+        self.expires_at_ms() + CONFIRM_GRACE_MS
+    }
+}
+
+/// Pending bets keyed by (user, nonce)
+#[derive(Debug, Default)]
+pub struct PendingBets {
+    bets: HashMap<(String, u64), PendingBet>,
+}
+
+impl PendingBets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bets.is_empty()
+    }
+
+    pub fn insert(&mut self, bet: PendingBet) {
+        self.bets.insert((bet.user.clone(), bet.nonce), bet);
+    }
+
+    pub fn get(&self, user: &str, nonce: u64) -> Option<&PendingBet> {
+        self.bets.get(&(user.to_string(), nonce))
+    }
+
+    /// Remove and return the bet to confirm, unless it is unknown or no longer
+    /// confirmable at `now_ms`
+    pub fn take(&mut self, user: &str, nonce: u64, now_ms: u64) -> Result<PendingBet, EnclaveError> {
+        let key = (user.to_string(), nonce);
+        match self.bets.get(&key) {
+            None => Err(EnclaveError::GenericError(format!(
+                "No pending bet {nonce} for {user} (unknown or already confirmed)"
+            ))),
+            Some(bet) if bet.confirmable_until_ms() < now_ms => {
+                self.bets.remove(&key);
+                Err(EnclaveError::GenericError(format!(
+                    "Pending bet {nonce} for {user} expired"
+                )))
+            }
+            Some(_) => Ok(self.bets.remove(&key).expect("checked above")),
+        }
+    }
+
+    /// Drop every bet no longer confirmable at `now_ms`, returning them
+    pub fn expire(&mut self, now_ms: u64) -> Vec<PendingBet> {
+        let expired: Vec<(String, u64)> = self
+            .bets
+            .iter()
+            .filter(|(_, bet)| bet.confirmable_until_ms() < now_ms)
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|key| self.bets.remove(&key))
+            .collect()
+    }
+
+    /// Drop every bet of a pool from sequence number `seq` on, returning them
+    pub fn drop_from(&mut self, pool_id: u64, seq: u64) -> Vec<PendingBet> {
+        let dropped: Vec<(String, u64)> = self
+            .bets
+            .iter()
+            .filter(|(_, bet)| bet.pool_id == pool_id && bet.seq >= seq)
+            .map(|(key, _)| key.clone())
+            .collect();
+        dropped
+            .into_iter()
+            .filter_map(|key| self.bets.remove(&key))
+            .collect()
+    }
+
+    /// Drop every pending bet of a pool (after resolution), returning them
+    pub fn clear_pool(&mut self, pool_id: u64) -> Vec<PendingBet> {
+        let dropped: Vec<(String, u64)> = self
+            .bets
+            .iter()
+            .filter(|(_, bet)| bet.pool_id == pool_id)
+            .map(|(key, _)| key.clone())
+            .collect();
+        dropped
+            .into_iter()
+            .filter_map(|key| self.bets.remove(&key))
+            .collect()
+    }
+
+    /// Pending bets of one wallet
+    pub fn by_wallet(&self, wallet: &str) -> Vec<PendingBet> {
+        let mut bets: Vec<PendingBet> = self
+            .bets
+            .values()
+            .filter(|bet| bet.user == wallet)
+            .cloned()
+            .collect();
+        bets.sort_by_key(|bet| bet.nonce);
+        bets
+    }
+
+    pub fn all(&self) -> Vec<PendingBet> {
+        self.bets.values().cloned().collect()
+    }
+
+    /// Replace every pending bet (used when restoring a snapshot)
+    pub fn restore(&mut self, bets: Vec<PendingBet>) {
+        self.bets = bets
+            .into_iter()
+            .map(|bet| ((bet.user.clone(), bet.nonce), bet))
+            .collect();
+    }
+}
+
+/// Drop bets past their confirmation grace. A pool whose bet expired is rolled back to the state before
+/// it, and the bets signed after it are dropped too since they can no longer
+/// execute. Returns every dropped bet.
+pub fn expire_and_roll_back(pending: &mut PendingBets, sequencer: &mut PoolSequencer, now_ms: u64) -> Vec<PendingBet> {
+    let mut dropped = pending.expire(now_ms);
+    let mut first_expired: BTreeMap<u64, u64> = BTreeMap::new();
+    for bet in &dropped {
+        let seq = first_expired.entry(bet.pool_id).or_insert(bet.seq);
+        *seq = (*seq).min(bet.seq);
+    }
+    for (pool_id, seq) in first_expired {
+        sequencer.roll_back(pool_id, seq);
+        dropped.extend(pending.drop_from(pool_id, seq));
+    }
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bet(user: &str, nonce: u64, pool_id: u64, placed_at_ms: u64) -> PendingBet {
+        PendingBet {
+            user: user.to_string(),
+            nonce,
+            pool_id,
+            outcome: 3,
+            shares: 1000,
+            cost: 500_000,
+            maker: "0xmaker".to_string(),
+            credit: 0,
+            protocol: String::new(),
+            protocol_fee: 0,
+            referrer: String::new(),
+            referrer_fee: 0,
+            seq: 1,
+            placed_at_ms,
+        }
+    }
+
+    #[test]
+    fn test_confirm_once_before_expiry() {
+        let mut pending = PendingBets::new();
+        pending.insert(bet("0xuser1", 1, 1, 1_000));
+
+        assert_eq!(pending.take("0xuser1", 1, 2_000).unwrap().shares, 1000);
+        // Already confirmed
+        assert!(pending.take("0xuser1", 1, 2_000).is_err());
+
+        // A bet that executed at the end of its TTL can still be reported late
+        pending.insert(bet("0xuser1", 2, 1, 1_000));
+        assert!(pending.take("0xuser1", 2, 1_000 + BET_TTL_MS + 1).is_ok());
+
+        pending.insert(bet("0xuser1", 3, 1, 1_000));
+        assert!(pending.take("0xuser1", 3, 1_000 + BET_TTL_MS + CONFIRM_GRACE_MS + 1).is_err());
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_expire_and_clear_pool() {
+        let mut pending = PendingBets::new();
+        pending.insert(bet("0xuser1", 1, 1, 1_000));
+        pending.insert(bet("0xuser2", 1, 1, 50_000));
+        pending.insert(bet("0xuser3", 1, 2, 50_000));
+
+        let expired = pending.expire(1_000 + BET_TTL_MS + CONFIRM_GRACE_MS + 1);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].user, "0xuser1");
+
+        assert_eq!(pending.clear_pool(1).len(), 1);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending.by_wallet("0xuser3").len(), 1);
+    }
+
+    #[test]
+    fn test_expiry_rolls_back_later_bets() {
+        let mut sequencer = PoolSequencer::new();
+        let mut pending = PendingBets::new();
+        for (nonce, placed_at_ms) in [(1, 1_000), (2, 50_000)] {
+            let head = sequencer.advance(1, vec![5000 + nonce * 100, 5000 - nonce * 100]);
+            pending.insert(PendingBet { seq: head.seq, ..bet("0xuser1", nonce, 1, placed_at_ms) });
+        }
+
+        // Only the first bet expired, but the second was built on it
+        let dropped = expire_and_roll_back(&mut pending, &mut sequencer, 1_000 + BET_TTL_MS + CONFIRM_GRACE_MS + 1);
+        assert_eq!(dropped.len(), 2);
+        assert!(pending.is_empty());
+        assert_eq!(sequencer.tip(1).seq, 0);
+    }
+
+    #[test]
+    fn test_unsubmitted_bet_releases_pool_after_grace() {
+        let mut sequencer = PoolSequencer::new();
+        let mut pending = PendingBets::new();
+        let head = sequencer.advance(1, vec![6000, 4000]);
+        pending.insert(PendingBet { seq: head.seq, ..bet("0xuser1", 1, 1, 1_000) });
+
+        // While the bet may still have executed, the next one must build on it
+        assert!(expire_and_roll_back(&mut pending, &mut sequencer, 1_000 + BET_TTL_MS + CONFIRM_GRACE_MS).is_empty());
+        assert!(sequencer.check_fresh(1, &[5000, 5000], 2).is_err());
+
+        // Once it could no longer be reported, the pool is priced from genesis again
+        let dropped = expire_and_roll_back(&mut pending, &mut sequencer, 1_000 + BET_TTL_MS + CONFIRM_GRACE_MS + 1);
+        assert_eq!(dropped.len(), 1);
+        assert!(sequencer.check_fresh(1, &[5000, 5000], 2).is_ok());
+        assert_eq!(sequencer.advance(1, vec![5500, 4500]).seq, 1);
+    }
+}
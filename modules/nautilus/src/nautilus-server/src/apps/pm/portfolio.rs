@@ -0,0 +1,177 @@
+//! Per-wallet portfolio for PM
+//!
+//! Values each position at the pool's current LMSR price (taken from the last
+//! signed bet) and reports cost basis, unrealised PnL and the payout the wallet
+//! would receive if each world wins.
+//!
+//! Units follow the rest of the app: shares scaled by 1000, amounts by 10^6,
+//! prices/probabilities by 10000. One share pays 1 USDC if its world wins.
+
+use super::state::Position;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// One position with cost basis and mark-to-market value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioPosition {
+    pub pool_id: u64,
+    pub outcome: u8,
+    pub shares: u64,
+    pub cost: u64,
+    pub avg_entry_price: u64,           // Cost per share (scaled by 10000)
+    pub current_price: Option<u64>,     // None if the pool has no known prices yet
+    pub current_value: Option<u64>,
+    pub unrealised_pnl: Option<i64>,
+}
+
+/// A wallet's positions in one pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolPortfolio {
+    pub pool_id: u64,
+    pub positions: Vec<PortfolioPosition>,
+    pub cost: u64,
+    pub current_value: Option<u64>,
+    pub unrealised_pnl: Option<i64>,
+    pub payout_if_wins: Vec<u64>,       // Index = world
+}
+
+/// A wallet's positions across all pools
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Portfolio {
+    pub wallet: String,
+    pub pools: Vec<PoolPortfolio>,
+    pub total_cost: u64,
+    pub total_value: Option<u64>,
+    pub total_unrealised_pnl: Option<i64>,
+}
+
+/// Current LMSR prices for a pool, scaled by 10000. The tip probabilities are the
+/// prices the last signed bet left, so they are used as they are: an outcome at 0
+/// is worth 0.
+pub fn current_prices(probs: &[u64]) -> Vec<u64> {
+    probs.to_vec()
+}
+
+fn value_position(position: &Position, prices: Option<&Vec<u64>>) -> PortfolioPosition {
+    let avg_entry_price = match position.shares {
+        0 => 0,
+        shares => position.cost * 10 / shares,
+    };
+    let current_price = prices.and_then(|p| p.get(position.outcome as usize)).copied();
+    let current_value = current_price.map(|price| position.shares * price / 10);
+    PortfolioPosition {
+        pool_id: position.pool_id,
+        outcome: position.outcome,
+        shares: position.shares,
+        cost: position.cost,
+        avg_entry_price,
+        current_price,
+        current_value,
+        unrealised_pnl: current_value.map(|v| v as i64 - position.cost as i64),
+    }
+}
+
+/// Sum values, or `None` if any of them is unknown
+fn sum_known<T: std::iter::Sum<T>>(values: impl Iterator<Item = Option<T>>) -> Option<T> {
+    values.collect::<Option<Vec<T>>>().map(|v| v.into_iter().sum())
+}
+
+/// Build the portfolio of `wallet` from its positions and the latest probabilities per pool
+pub fn build_portfolio(wallet: &str, positions: Vec<Position>, pool_probs: &HashMap<u64, Vec<u64>>) -> Portfolio {
+    let mut by_pool: BTreeMap<u64, Vec<Position>> = BTreeMap::new();
+    for position in positions {
+        by_pool.entry(position.pool_id).or_default().push(position);
+    }
+
+    let pools: Vec<PoolPortfolio> = by_pool
+        .into_iter()
+        .map(|(pool_id, mut positions)| {
+            positions.sort_by_key(|p| p.outcome);
+            let prices = pool_probs.get(&pool_id).map(|probs| current_prices(probs));
+            let valued: Vec<PortfolioPosition> = positions
+                .iter()
+                .map(|p| value_position(p, prices.as_ref()))
+                .collect();
+
+            let num_worlds = prices
+                .as_ref()
+                .map(|p| p.len())
+                .unwrap_or(0)
+                .max(positions.iter().map(|p| p.outcome as usize + 1).max().unwrap_or(0));
+            let mut payout_if_wins = vec![0u64; num_worlds];
+            for p in &positions {
+                payout_if_wins[p.outcome as usize] += p.shares * 1000;
+            }
+
+            PoolPortfolio {
+                pool_id,
+                cost: valued.iter().map(|p| p.cost).sum(),
+                current_value: sum_known(valued.iter().map(|p| p.current_value)),
+                unrealised_pnl: sum_known(valued.iter().map(|p| p.unrealised_pnl)),
+                positions: valued,
+                payout_if_wins,
+            }
+        })
+        .collect();
+
+    Portfolio {
+        wallet: wallet.to_string(),
+        total_cost: pools.iter().map(|p| p.cost).sum(),
+        total_value: sum_known(pools.iter().map(|p| p.current_value)),
+        total_unrealised_pnl: sum_known(pools.iter().map(|p| p.unrealised_pnl)),
+        pools,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(pool_id: u64, outcome: u8, shares: u64, cost: u64) -> Position {
+        Position {
+            wallet: "0xuser1".to_string(),
+            pool_id,
+            outcome,
+            shares,
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_portfolio_valuation() {
+        // 2000 shares (2.0) bought for 0.5 USDC at a current price of 0.5
+        let mut pool_probs = HashMap::new();
+        pool_probs.insert(1, vec![5000, 5000]);
+        let portfolio = build_portfolio("0xuser1", vec![position(1, 0, 2000, 500_000)], &pool_probs);
+
+        let pool = &portfolio.pools[0];
+        let p = &pool.positions[0];
+        assert_eq!(p.avg_entry_price, 2500);
+        assert_eq!(p.current_price, Some(5000));
+        assert_eq!(p.current_value, Some(1_000_000));
+        assert_eq!(p.unrealised_pnl, Some(500_000));
+        assert_eq!(pool.payout_if_wins, vec![2_000_000, 0]);
+        assert_eq!(portfolio.total_unrealised_pnl, Some(500_000));
+    }
+
+    #[test]
+    fn test_zero_probability_outcome_is_worth_nothing() {
+        let mut pool_probs = HashMap::new();
+        pool_probs.insert(1, vec![0, 2500, 7500]);
+        let portfolio = build_portfolio("0xuser1", vec![position(1, 0, 1000, 100_000)], &pool_probs);
+
+        let p = &portfolio.pools[0].positions[0];
+        assert_eq!(p.current_price, Some(0));
+        assert_eq!(p.current_value, Some(0));
+        assert_eq!(p.unrealised_pnl, Some(-100_000));
+        assert_eq!(current_prices(&[0, 2500, 7500]), vec![0, 2500, 7500]);
+    }
+
+    #[test]
+    fn test_portfolio_without_prices() {
+        let portfolio = build_portfolio("0xuser1", vec![position(7, 2, 1000, 300_000)], &HashMap::new());
+        assert_eq!(portfolio.total_cost, 300_000);
+        assert_eq!(portfolio.total_value, None);
+        assert_eq!(portfolio.pools[0].payout_if_wins, vec![0, 0, 1_000_000]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Risk limits for PM
+//!
+//! Operator-set limits checked in `process_data` before a bet is signed: stake
+//! per bet, shares one wallet holds in a pool (confirmed and pending), a pool's
+//! open interest (the stakes it holds) and how far one bet may move its outcome's
+//! probability. Each is loaded from the environment and 0 leaves it off.
+//!
+//! A circuit breaker watches each pool's signed probabilities. When any of them
+//! moves more than `breaker_move_bps` within `breaker_window_ms`, the pool is
+//! halted for `breaker_halt_ms` and refuses bets. Halts live in enclave memory
+//! only; a restart lifts them.
+//!
+//! Rejections carry a machine-readable `RiskViolation` as the error's `reason`.
+
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Limits applied to every bet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RiskLimits {
+    pub max_stake: u64,            // Largest single bet (scaled by 10^6)
+    pub max_position: u64,         // Shares one wallet may hold in a pool (scaled by 1000)
+    pub max_open_interest: u64,    // Stakes a pool may hold (scaled by 10^6)
+    pub max_price_impact_bps: u64, // Largest move of the bet outcome's probability (scaled by 10000)
+    pub breaker_move_bps: u64,     // Probability move within the window that halts a pool
+    pub breaker_window_ms: u64,
+    pub breaker_halt_ms: u64,
+}
+
+impl RiskLimits {
+    /// Load the limits from `PM_MAX_STAKE`, `PM_MAX_POSITION`, `PM_MAX_OPEN_INTEREST`,
+    /// `PM_MAX_PRICE_IMPACT_BPS`, `PM_BREAKER_MOVE_BPS`, `PM_BREAKER_WINDOW_MS` and
+    /// `PM_BREAKER_HALT_MS`
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+        Self {
+            max_stake: var("PM_MAX_STAKE"),
+            max_position: var("PM_MAX_POSITION"),
+            max_open_interest: var("PM_MAX_OPEN_INTEREST"),
+            max_price_impact_bps: var("PM_MAX_PRICE_IMPACT_BPS"),
+            breaker_move_bps: var("PM_BREAKER_MOVE_BPS"),
+            breaker_window_ms: var("PM_BREAKER_WINDOW_MS"),
+            breaker_halt_ms: var("PM_BREAKER_HALT_MS"),
+        }
+    }
+
+    /// Check a priced bet: `stake` is its debit, `position` the wallet's shares in
+    /// the pool after it, `open_interest` the pool's stakes after it and `from` /
+    /// `to` its outcome's probability before and after
+    pub fn check_bet(
+        &self,
+        stake: u64,
+        position: u64,
+        open_interest: u64,
+        from: u64,
+        to: u64,
+    ) -> Result<(), RiskViolation> {
+        if self.max_stake > 0 && stake > self.max_stake {
+            return Err(RiskViolation::StakeLimit { stake, max: self.max_stake });
+        }
+        if self.max_position > 0 && position > self.max_position {
+            return Err(RiskViolation::PositionLimit { position, max: self.max_position });
+        }
+        if self.max_open_interest > 0 && open_interest > self.max_open_interest {
+            return Err(RiskViolation::OpenInterestLimit { open_interest, max: self.max_open_interest });
+        }
+        let impact_bps = to.abs_diff(from);
+        if self.max_price_impact_bps > 0 && impact_bps > self.max_price_impact_bps {
+            return Err(RiskViolation::PriceImpactLimit { impact_bps, max: self.max_price_impact_bps });
+        }
+        Ok(())
+    }
+}
+
+/// Why a bet was refused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum RiskViolation {
+    StakeLimit { stake: u64, max: u64 },
+    PositionLimit { position: u64, max: u64 },
+    OpenInterestLimit { open_interest: u64, max: u64 },
+    PriceImpactLimit { impact_bps: u64, max: u64 },
+    PoolHalted { pool_id: u64, until_ms: u64 },
+}
+
+impl std::fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskViolation::StakeLimit { stake, max } => write!(f, "Stake {stake} exceeds the {max} limit"),
+            RiskViolation::PositionLimit { position, max } => {
+                write!(f, "Position of {position} shares exceeds the {max} limit")
+            }
+            RiskViolation::OpenInterestLimit { open_interest, max } => {
+                write!(f, "Pool open interest {open_interest} would exceed the {max} limit")
+            }
+            RiskViolation::PriceImpactLimit { impact_bps, max } => {
+                write!(f, "Price impact of {impact_bps} bps exceeds the {max} bps limit")
+            }
+            RiskViolation::PoolHalted { pool_id, until_ms } => {
+                write!(f, "Pool {pool_id} is halted until {until_ms}")
+            }
+        }
+    }
+}
+
+impl From<RiskViolation> for EnclaveError {
+    fn from(violation: RiskViolation) -> Self {
+        EnclaveError::Rejected {
+            message: violation.to_string(),
+            reason: serde_json::to_value(&violation).expect("should not fail"),
+        }
+    }
+}
+
+/// Recent signed probabilities and halts of every pool
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    history: HashMap<u64, VecDeque<(u64, Vec<u64>)>>,
+    halted_until: HashMap<u64, u64>,
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse bets on a pool that is halted at `now_ms`
+    pub fn check(&self, pool_id: u64, now_ms: u64) -> Result<(), RiskViolation> {
+        match self.halted_until.get(&pool_id) {
+            Some(&until_ms) if now_ms < until_ms => Err(RiskViolation::PoolHalted { pool_id, until_ms }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the probabilities a bet moved a pool to (from `before`). Halts the
+    /// pool if any probability moved more than the limit within the window, and
+    /// returns when the halt ends.
+    pub fn record(
+        &mut self,
+        limits: &RiskLimits,
+        pool_id: u64,
+        now_ms: u64,
+        before: &[u64],
+        after: &[u64],
+    ) -> Option<u64> {
+        if limits.breaker_move_bps == 0 {
+            return None;
+        }
+        let history = self.history.entry(pool_id).or_default();
+        while history
+            .front()
+            .is_some_and(|(at_ms, _)| at_ms + limits.breaker_window_ms < now_ms)
+        {
+            history.pop_front();
+        }
+        if history.is_empty() && !before.is_empty() {
+            history.push_back((now_ms, before.to_vec()));
+        }
+        history.push_back((now_ms, after.to_vec()));
+
+        let moved = |i: usize| {
+            let seen = history.iter().filter_map(|(_, probs)| probs.get(i).copied());
+            seen.clone().max().unwrap_or(0) - seen.min().unwrap_or(0)
+        };
+        if (0..after.len()).any(|i| moved(i) > limits.breaker_move_bps) {
+            let until_ms = now_ms + limits.breaker_halt_ms;
+            self.halted_until.insert(pool_id, until_ms);
+            self.history.remove(&pool_id);
+            return Some(until_ms);
+        }
+        None
+    }
+
+    /// Forget a resolved pool
+    pub fn clear_pool(&mut self, pool_id: u64) {
+        self.history.remove(&pool_id);
+        self.halted_until.remove(&pool_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> RiskLimits {
+        RiskLimits {
+            max_stake: 10_000_000,
+            max_position: 50_000,
+            max_open_interest: 100_000_000,
+            max_price_impact_bps: 500,
+            breaker_move_bps: 1_000,
+            breaker_window_ms: 60_000,
+            breaker_halt_ms: 300_000,
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = limits();
+        assert!(limits.check_bet(1_000_000, 2_000, 1_000_000, 1250, 1400).is_ok());
+        assert_eq!(
+            limits.check_bet(20_000_000, 2_000, 1_000_000, 1250, 1400),
+            Err(RiskViolation::StakeLimit { stake: 20_000_000, max: 10_000_000 })
+        );
+        assert!(matches!(
+            limits.check_bet(1_000_000, 60_000, 1_000_000, 1250, 1400),
+            Err(RiskViolation::PositionLimit { .. })
+        ));
+        assert!(matches!(
+            limits.check_bet(1_000_000, 2_000, 200_000_000, 1250, 1400),
+            Err(RiskViolation::OpenInterestLimit { .. })
+        ));
+        assert_eq!(
+            limits.check_bet(1_000_000, 2_000, 1_000_000, 1250, 1900),
+            Err(RiskViolation::PriceImpactLimit { impact_bps: 650, max: 500 })
+        );
+        assert!(RiskLimits::default().check_bet(u64::MAX, u64::MAX, u64::MAX, 0, 10_000).is_ok());
+
+        let reason = serde_json::to_value(RiskViolation::PoolHalted { pool_id: 1, until_ms: 5 }).unwrap();
+        assert_eq!(reason["code"], "pool_halted");
+    }
+
+    #[test]
+    fn test_breaker_halts_on_fast_moves() {
+        let limits = limits();
+        let mut breaker = CircuitBreaker::new();
+        // Two moves of 600 bps within the window add up past the threshold
+        assert_eq!(breaker.record(&limits, 1, 1_000, &[5000, 5000], &[5600, 4400]), None);
+        assert_eq!(breaker.record(&limits, 1, 2_000, &[5600, 4400], &[6200, 3800]), Some(302_000));
+        assert_eq!(
+            breaker.check(1, 2_000),
+            Err(RiskViolation::PoolHalted { pool_id: 1, until_ms: 302_000 })
+        );
+        assert!(breaker.check(1, 302_000).is_ok());
+        assert!(breaker.check(2, 2_000).is_ok());
+
+        // The same moves spread beyond the window do not
+        assert_eq!(breaker.record(&limits, 2, 1_000, &[5000, 5000], &[5600, 4400]), None);
+        assert_eq!(breaker.record(&limits, 2, 100_000, &[5600, 4400], &[6200, 3800]), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! Per-pool ordering of signed bets
+//!
+//! Every signed `PlaceBetResponse` carries the pool's next sequence number and
+//! the hash of the state it was priced from, so the contract applies bets strictly
+//! in order and rejects any built on a stale state. A pool state hash is
+//! `sha256(bcs(PoolTransition))`; a pool with no trades is at sequence 0 with an
+//! all-zero hash, priced uniformly over the outcomes fixed when it was funded.
+//!
+//! Signed bets of a pool form a chain on top of the last confirmed state. A new
+//! bet must be priced from the probabilities at the tip. If a pending bet expires
+//! unconfirmed, it and everything signed after it can never execute on chain, so
+//! the tip rolls back to the bet before it.
+
+use crate::EnclaveError;
+use fastcrypto::hash::{HashFunction, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// State hash of a pool before its first trade
+pub const GENESIS_STATE_HASH: [u8; 32] = [0u8; 32];
+
+/// Pool state after a transition - MUST match Move PoolTransition exactly
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolTransition {
+    pub pool_id: u64,
+    pub seq: u64,
+    pub probs: Vec<u64>,
+}
+
+/// Hash of the pool state reached by transition `seq`
+pub fn state_hash(pool_id: u64, seq: u64, probs: &[u64]) -> Vec<u8> {
+    let transition = PoolTransition {
+        pool_id,
+        seq,
+        probs: probs.to_vec(),
+    };
+    let bytes = bcs::to_bytes(&transition).expect("should not fail");
+    Sha256::digest(bytes).digest.to_vec()
+}
+
+/// Probabilities (scaled by 10000) a pool of `outcomes` outcomes starts from
+pub fn uniform_probs(outcomes: usize) -> Vec<u64> {
+    vec![10_000 / outcomes.max(1) as u64; outcomes]
+}
+
+/// A pool state: the sequence number that produced it, its hash and probabilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolHead {
+    pub seq: u64,
+    pub state_hash: Vec<u8>,
+    pub probs: Vec<u64>,        // Empty at genesis, where prices are uniform
+}
+
+impl PoolHead {
+    pub fn genesis() -> Self {
+        Self {
+            seq: 0,
+            state_hash: GENESIS_STATE_HASH.to_vec(),
+            probs: Vec::new(),
+        }
+    }
+
+    /// The state reached by applying a bet that moved prices to `probs`
+    pub fn next(&self, pool_id: u64, probs: Vec<u64>) -> Self {
+        let seq = self.seq + 1;
+        Self {
+            seq,
+            state_hash: state_hash(pool_id, seq, &probs),
+            probs,
+        }
+    }
+}
+
+/// Confirmed state of a pool and the states of its signed but unconfirmed bets, in order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolChain {
+    pub confirmed: PoolHead,
+    pub pending: Vec<PoolHead>,
+}
+
+impl PoolChain {
+    fn tip(&self) -> &PoolHead {
+        self.pending.last().unwrap_or(&self.confirmed)
+    }
+}
+
+/// Sequencing state of every pool
+#[derive(Debug, Default)]
+pub struct PoolSequencer {
+    pools: HashMap<u64, PoolChain>,
+}
+
+impl PoolSequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// State the next bet of `pool_id` builds on
+    pub fn tip(&self, pool_id: u64) -> PoolHead {
+        self.pools
+            .get(&pool_id)
+            .map(|chain| chain.tip().clone())
+            .unwrap_or_else(PoolHead::genesis)
+    }
+
+    /// Reject a bet priced from anything but the tip probabilities, which at
+    /// genesis are uniform over the pool's `outcomes`
+    pub fn check_fresh(&self, pool_id: u64, current_probs: &[u64], outcomes: usize) -> Result<PoolHead, EnclaveError> {
+        let tip = self.tip(pool_id);
+        let expected = match tip.probs.is_empty() {
+            true => uniform_probs(outcomes),
+            false => tip.probs.clone(),
+        };
+        if expected != current_probs {
+            return Err(EnclaveError::GenericError(format!(
+                "Stale probabilities for pool {pool_id}: latest state is seq {} with {:?}",
+                tip.seq, expected
+            )));
+        }
+        Ok(tip)
+    }
+
+    /// Append a signed bet that moved the pool to `probs`
+    pub fn advance(&mut self, pool_id: u64, probs: Vec<u64>) -> PoolHead {
+        let chain = self.pools.entry(pool_id).or_insert_with(|| PoolChain {
+            confirmed: PoolHead::genesis(),
+            pending: Vec::new(),
+        });
+        let next = chain.tip().next(pool_id, probs);
+        chain.pending.push(next.clone());
+        next
+    }
+
+    /// Mark bet `seq` as executed. Bets execute on chain strictly in order, so it
+    /// must be the oldest pending one.
+    pub fn confirm(&mut self, pool_id: u64, seq: u64) -> Result<(), EnclaveError> {
+        let chain = self.pools.get_mut(&pool_id);
+        match chain {
+            Some(chain) if chain.pending.first().map(|head| head.seq) == Some(seq) => {
+                chain.confirmed = chain.pending.remove(0);
+                Ok(())
+            }
+            _ => Err(EnclaveError::GenericError(format!(
+                "Bet {seq} of pool {pool_id} is not next in line to confirm"
+            ))),
+        }
+    }
+
+    /// Forget bet `seq` and every bet signed after it. Returns the dropped sequence numbers.
+    pub fn roll_back(&mut self, pool_id: u64, seq: u64) -> Vec<u64> {
+        match self.pools.get_mut(&pool_id) {
+            Some(chain) => {
+                let keep = chain.pending.iter().take_while(|head| head.seq < seq).count();
+                chain.pending.drain(keep..).map(|head| head.seq).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Adopt the on-chain state of a pool this enclave has no history for.
+    /// A pool it already has a head for keeps its own history.
+    pub fn sync(&mut self, pool_id: u64, seq: u64, probs: Vec<u64>) -> Result<PoolHead, EnclaveError> {
+        if self.pools.contains_key(&pool_id) {
+            return Err(EnclaveError::GenericError(format!(
+                "Pool {pool_id} already has a sequence head"
+            )));
+        }
+        let head = match seq {
+            0 => PoolHead::genesis(),
+            _ => PoolHead {
+                seq,
+                state_hash: state_hash(pool_id, seq, &probs),
+                probs,
+            },
+        };
+        self.pools.insert(
+            pool_id,
+            PoolChain {
+                confirmed: head.clone(),
+                pending: Vec::new(),
+            },
+        );
+        Ok(head)
+    }
+
+    /// Drop a pool's unconfirmed bets (after resolution). The confirmed state is
+    /// kept since the contract keeps it too.
+    pub fn clear_pending(&mut self, pool_id: u64) {
+        if let Some(chain) = self.pools.get_mut(&pool_id) {
+            chain.pending.clear();
+        }
+    }
+
+    pub fn export(&self) -> Vec<(u64, PoolChain)> {
+        self.pools
+            .iter()
+            .map(|(pool_id, chain)| (*pool_id, chain.clone()))
+            .collect()
+    }
+
+    /// Replace every pool's state (used when restoring a snapshot)
+    pub fn restore(&mut self, pools: Vec<(u64, PoolChain)>) {
+        self.pools = pools.into_iter().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_advances_and_confirms_in_order() {
+        let mut sequencer = PoolSequencer::new();
+        // A new pool starts at uniform prices, not ones its first bettor picks
+        assert!(sequencer.check_fresh(1, &[1, 9999], 2).is_err());
+        assert!(sequencer.check_fresh(1, &[5000, 5000], 2).is_ok());
+        assert!(sequencer.check_fresh(1, &[3333, 3333, 3333], 3).is_ok());
+
+        let first = sequencer.advance(1, vec![6000, 4000]);
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.state_hash, state_hash(1, 1, &[6000, 4000]));
+
+        // The next bet must be priced from the tip
+        assert!(sequencer.check_fresh(1, &[5000, 5000], 2).is_err());
+        assert_eq!(sequencer.check_fresh(1, &[6000, 4000], 2).unwrap(), first);
+        let second = sequencer.advance(1, vec![7000, 3000]);
+        assert_eq!(second.seq, 2);
+
+        assert!(sequencer.confirm(1, 2).is_err());
+        sequencer.confirm(1, 1).unwrap();
+        sequencer.confirm(1, 2).unwrap();
+        assert_eq!(sequencer.tip(1), second);
+    }
+
+    #[test]
+    fn test_roll_back_drops_later_bets() {
+        let mut sequencer = PoolSequencer::new();
+        let first = sequencer.advance(1, vec![6000, 4000]);
+        sequencer.advance(1, vec![7000, 3000]);
+        sequencer.advance(1, vec![8000, 2000]);
+        sequencer.confirm(1, 1).unwrap();
+
+        assert_eq!(sequencer.roll_back(1, 2), vec![2, 3]);
+        assert_eq!(sequencer.tip(1), first);
+        // The next bet reuses sequence number 2
+        assert_eq!(sequencer.advance(1, vec![5500, 4500]).seq, 2);
+    }
+
+    #[test]
+    fn test_sync_only_adopts_unknown_pools() {
+        let mut sequencer = PoolSequencer::new();
+        let head = sequencer.sync(2, 5, vec![6000, 4000]).unwrap();
+        assert_eq!(head.state_hash, state_hash(2, 5, &[6000, 4000]));
+        assert_eq!(sequencer.advance(2, vec![7000, 3000]).seq, 6);
+
+        // Neither a synced pool nor one with local history can be overwritten
+        assert!(sequencer.sync(2, 1, vec![5000, 5000]).is_err());
+        sequencer.advance(1, vec![6000, 4000]);
+        sequencer.confirm(1, 1).unwrap();
+        assert!(sequencer.sync(1, 0, Vec::new()).is_err());
+        assert_eq!(sequencer.tip(1).seq, 1);
+    }
+}
@@ -0,0 +1,489 @@
+//! Encrypted snapshots of PM market state
+//!
+//! Positions, vault balances, pool liquidity accounts, pending bets, pool sequences,
+//! resolved payout sets, processed bet nonces and the trade ledger live in enclave
+//! memory only. This module periodically (and on shutdown) seals
+//! them with AES-256-GCM and writes the ciphertext to host storage, then restores
+//! them on boot.
+//!
+//! The snapshot key is provisioned into the enclave by the operator as the
+//! `SNAPSHOT_KEY` secret (hex, 32 bytes), the same way `API_KEY` is; it is not
+//! derived from the enclave's measurements. The GCM tag rejects a snapshot that was
+//! tampered with or sealed under another key, but whoever holds the key can read
+//! and seal snapshots, so the key must stay with the operator.
+//!
+//! Host storage can still serve an older, validly sealed snapshot. Each snapshot
+//! therefore carries a `SnapshotHead`: a generation counter incremented by every
+//! snapshot and the ledger head it holds, both bound into the AEAD associated data.
+//! A restore must extend the head of the last snapshot this enclave saved or
+//! restored, and the `SNAPSHOT_MIN_HEAD` the operator last recorded; a missing
+//! snapshot is refused when one is expected. The head an enclave resumed from is
+//! part of its attested configuration, so a rollback past a published signed
+//! ledger checkpoint shows in the attestation.
+
+use super::ledger::{Ledger, LedgerExport, LedgerHead};
+use super::balances::Balances;
+use super::maker::MakerBook;
+use super::pending::PendingBet;
+use super::sequence::PoolChain;
+use super::{ProcessedBet, ResolvedPool};
+use super::state::Position;
+use crate::{AppState, EnclaveError};
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use fastcrypto::encoding::{Encoding, Hex};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+// Snapshot file format version (also bound into the AEAD associated data)
+const SNAPSHOT_VERSION: u8 = 18;
+
+// Associated data prefix, so the key cannot be confused with any other use
+const SNAPSHOT_AAD: &[u8] = b"pm-snapshot";
+
+const DEFAULT_SNAPSHOT_PATH: &str = "pm_snapshot.bin";
+const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 60;
+
+/// Place of a snapshot in the enclave's history: its generation and the ledger
+/// head it holds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHead {
+    pub generation: u64,
+    pub ledger: LedgerHead,
+}
+
+impl Default for SnapshotHead {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            ledger: Ledger::new().head(),
+        }
+    }
+}
+
+impl std::fmt::Display for SnapshotHead {
+    /// The `SNAPSHOT_MIN_HEAD` form: `<generation>:<entry_count>:<head_hash_hex>`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.generation, self.ledger.entry_count, Hex::encode(&self.ledger.head_hash))
+    }
+}
+
+impl std::str::FromStr for SnapshotHead {
+    type Err = EnclaveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EnclaveError::GenericError(format!("Invalid snapshot head {s}"));
+        let mut parts = s.split(':');
+        let (Some(generation), Some(entry_count), Some(head_hash), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            generation: generation.parse().map_err(|_| invalid())?,
+            ledger: LedgerHead {
+                entry_count: entry_count.parse().map_err(|_| invalid())?,
+                head_hash: Hex::decode(head_hash).map_err(|_| invalid())?,
+            },
+        })
+    }
+}
+
+/// Plaintext market state captured in a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub generation: u64,
+    pub taken_at_ms: u64,
+    pub positions: Vec<Position>,
+    pub resolved_pools: Vec<(u64, ResolvedPool)>,
+    pub ledger: LedgerExport,
+    pub pool_probs: Vec<(u64, Vec<u64>)>,
+    pub processed_bets: Vec<ProcessedBet>,
+    pub request_nonces: Vec<(String, u64)>,
+    pub pending_bets: Vec<PendingBet>,
+    pub pool_chains: Vec<(u64, PoolChain)>,
+    pub balances: Balances,
+    pub makers: MakerBook,
+}
+
+impl MarketSnapshot {
+    pub fn head(&self) -> SnapshotHead {
+        SnapshotHead {
+            generation: self.generation,
+            ledger: self.ledger.head(),
+        }
+    }
+
+    /// Refuse a snapshot that does not extend `head`: an earlier generation, or a
+    /// ledger that is shorter or forked
+    pub fn check_extends(&self, head: &SnapshotHead) -> Result<(), EnclaveError> {
+        if self.generation < head.generation || !self.ledger.extends(&head.ledger) {
+            return Err(EnclaveError::GenericError(format!(
+                "Stale snapshot {} does not extend {head}",
+                self.head()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// On-disk envelope: version, head, GCM nonce and ciphertext (BCS encoded)
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotEnvelope {
+    version: u8,
+    head: SnapshotHead,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// Where and how often to write snapshots, the key to seal them with and the
+/// oldest snapshot to accept
+pub struct SnapshotConfig {
+    pub key: [u8; 32],
+    pub path: PathBuf,
+    pub interval: Duration,
+    pub min_head: Option<SnapshotHead>,
+}
+
+impl SnapshotConfig {
+    /// Load from `SNAPSHOT_KEY`, `SNAPSHOT_PATH`, `SNAPSHOT_INTERVAL_SECS` and
+    /// `SNAPSHOT_MIN_HEAD` (the head logged by the last shutdown snapshot).
+    /// Returns `None` (snapshots disabled) when no key is provisioned.
+    pub fn from_env() -> Result<Option<Self>, EnclaveError> {
+        let key_hex = match std::env::var("SNAPSHOT_KEY") {
+            Ok(key_hex) => key_hex,
+            Err(_) => return Ok(None),
+        };
+        let key: [u8; 32] = Hex::decode(&key_hex)
+            .map_err(|e| EnclaveError::GenericError(format!("Invalid SNAPSHOT_KEY: {e}")))?
+            .try_into()
+            .map_err(|_| EnclaveError::GenericError("SNAPSHOT_KEY must be 32 bytes".into()))?;
+        let path = std::env::var("SNAPSHOT_PATH")
+            .unwrap_or_else(|_| DEFAULT_SNAPSHOT_PATH.to_string())
+            .into();
+        let interval_secs = std::env::var("SNAPSHOT_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS);
+        let min_head = std::env::var("SNAPSHOT_MIN_HEAD")
+            .ok()
+            .filter(|head| !head.is_empty())
+            .map(|head| head.parse())
+            .transpose()?;
+
+        Ok(Some(Self {
+            key,
+            path,
+            interval: Duration::from_secs(interval_secs),
+            min_head,
+        }))
+    }
+}
+
+fn aad(head: &SnapshotHead) -> Vec<u8> {
+    let mut aad = SNAPSHOT_AAD.to_vec();
+    aad.push(SNAPSHOT_VERSION);
+    aad.extend(bcs::to_bytes(head).expect("should not fail"));
+    aad
+}
+
+/// Seal a snapshot into the on-disk envelope bytes
+pub fn encrypt_snapshot(key: &[u8; 32], snapshot: &MarketSnapshot) -> Result<Vec<u8>, EnclaveError> {
+    let plaintext = bcs::to_bytes(snapshot)
+        .map_err(|e| EnclaveError::GenericError(format!("Snapshot encode error: {e}")))?;
+
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let head = snapshot.head();
+    let cipher = Aes256Gcm::new(key.into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &aad(&head) })
+        .map_err(|_| EnclaveError::GenericError("Snapshot encryption failed".into()))?;
+
+    bcs::to_bytes(&SnapshotEnvelope {
+        version: SNAPSHOT_VERSION,
+        head,
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+    .map_err(|e| EnclaveError::GenericError(format!("Snapshot encode error: {e}")))
+}
+
+/// Open an envelope, checking version, nonce length, the GCM tag and that the
+/// state matches the head it was sealed under
+pub fn decrypt_snapshot(key: &[u8; 32], bytes: &[u8]) -> Result<MarketSnapshot, EnclaveError> {
+    let envelope: SnapshotEnvelope = bcs::from_bytes(bytes)
+        .map_err(|e| EnclaveError::GenericError(format!("Corrupt snapshot: {e}")))?;
+    if envelope.version != SNAPSHOT_VERSION {
+        return Err(EnclaveError::GenericError(format!(
+            "Unsupported snapshot version {}",
+            envelope.version
+        )));
+    }
+    if envelope.nonce.len() != 12 {
+        return Err(EnclaveError::GenericError("Corrupt snapshot: bad nonce".into()));
+    }
+
+    let cipher = Aes256Gcm::new(key.into());
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&envelope.nonce),
+            Payload { msg: &envelope.ciphertext, aad: &aad(&envelope.head) },
+        )
+        .map_err(|_| EnclaveError::GenericError("Snapshot integrity check failed".into()))?;
+
+    let snapshot: MarketSnapshot = bcs::from_bytes(&plaintext)
+        .map_err(|e| EnclaveError::GenericError(format!("Corrupt snapshot: {e}")))?;
+    if snapshot.head() != envelope.head {
+        return Err(EnclaveError::GenericError("Snapshot does not match its head".into()));
+    }
+    Ok(snapshot)
+}
+
+/// Capture the current in-memory market state as the next snapshot generation.
+/// All locks are held together so positions and ledger are mutually consistent.
+pub fn capture_snapshot(state: &AppState) -> Result<MarketSnapshot, EnclaveError> {
+    let store = state.positions.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let resolved_pools = state.pm.resolved_pools.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let ledger = state.pm.ledger.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let pool_probs = state.pm.pool_probs.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let processed_bets = state.pm.processed_bets.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let request_nonces = state.pm.request_nonces.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let pending_bets = state.pm.pending_bets.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let sequencer = state.pm.sequencer.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let balances = state.pm.balances.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let makers = state.pm.makers.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let generation = state.pm.last_snapshot.read()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?
+        .generation + 1;
+    let taken_at_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+
+    Ok(MarketSnapshot {
+        generation,
+        taken_at_ms,
+        positions: store.get_all_positions()?,
+        resolved_pools: resolved_pools
+            .iter()
+            .map(|(pool_id, resolved)| (*pool_id, resolved.clone()))
+            .collect(),
+        ledger: ledger.export(),
+        pool_probs: pool_probs
+            .iter()
+            .map(|(pool_id, probs)| (*pool_id, probs.clone()))
+            .collect(),
+        processed_bets: processed_bets.values().cloned().collect(),
+        request_nonces: request_nonces.iter().cloned().collect(),
+        pending_bets: pending_bets.all(),
+        pool_chains: sequencer.export(),
+        balances: balances.clone(),
+        makers: makers.clone(),
+    })
+}
+
+/// Replace the in-memory market state with a snapshot, which must extend the last
+/// snapshot this enclave saved or restored
+pub fn apply_snapshot(state: &AppState, snapshot: MarketSnapshot) -> Result<(), EnclaveError> {
+    let mut last_snapshot = state.pm.last_snapshot.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    snapshot.check_extends(&last_snapshot)?;
+    let head = snapshot.head();
+    let ledger = Ledger::from_export(snapshot.ledger)?;
+    let mut store = state.positions.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut resolved_pools = state.pm.resolved_pools.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut current_ledger = state.pm.ledger.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut pool_probs = state.pm.pool_probs.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut processed_bets = state.pm.processed_bets.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut request_nonces = state.pm.request_nonces.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut pending_bets = state.pm.pending_bets.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut sequencer = state.pm.sequencer.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut balances = state.pm.balances.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+    let mut makers = state.pm.makers.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+
+    store.restore(snapshot.positions)?;
+    *resolved_pools = snapshot.resolved_pools.into_iter().collect();
+    *current_ledger = ledger;
+    *pool_probs = snapshot.pool_probs.into_iter().collect();
+    *processed_bets = snapshot
+        .processed_bets
+        .into_iter()
+        .map(|bet| ((bet.request.user.clone(), bet.request.nonce), bet))
+        .collect();
+    *request_nonces = snapshot.request_nonces.into_iter().collect();
+    pending_bets.restore(snapshot.pending_bets);
+    sequencer.restore(snapshot.pool_chains);
+    *balances = snapshot.balances;
+    *makers = snapshot.makers;
+    *last_snapshot = head;
+    Ok(())
+}
+
+/// Seal the current state and write it to `config.path`, returning its head.
+/// Written to a temp file first so a crash mid-write never leaves a torn snapshot.
+pub fn save_snapshot(state: &AppState, config: &SnapshotConfig) -> Result<SnapshotHead, EnclaveError> {
+    let snapshot = capture_snapshot(state)?;
+    let bytes = encrypt_snapshot(&config.key, &snapshot)?;
+    let tmp_path = config.path.with_extension("tmp");
+    std::fs::write(&tmp_path, bytes)
+        .and_then(|_| std::fs::rename(&tmp_path, &config.path))
+        .map_err(|e| EnclaveError::GenericError(format!("Failed to write snapshot: {e}")))?;
+    let head = snapshot.head();
+    *state.pm.last_snapshot.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))? = head.clone();
+    Ok(head)
+}
+
+/// Restore state from `config.path`. Returns `false` if there is no snapshot yet,
+/// and fails if there is none but `config.min_head` expects one.
+pub fn restore_snapshot(state: &AppState, config: &SnapshotConfig) -> Result<bool, EnclaveError> {
+    let bytes = match std::fs::read(&config.path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return match &config.min_head {
+                Some(min_head) => Err(EnclaveError::GenericError(format!(
+                    "No snapshot at {}, expected one extending {min_head}",
+                    config.path.display()
+                ))),
+                None => Ok(false),
+            }
+        }
+        Err(e) => {
+            return Err(EnclaveError::GenericError(format!("Failed to read snapshot: {e}")))
+        }
+    };
+    let snapshot = decrypt_snapshot(&config.key, &bytes)?;
+    if let Some(min_head) = &config.min_head {
+        snapshot.check_extends(min_head)?;
+    }
+    info!(
+        "Restored snapshot {} taken at {} ({} positions, {} resolved pools, {} ledger entries)",
+        snapshot.head(),
+        snapshot.taken_at_ms,
+        snapshot.positions.len(),
+        snapshot.resolved_pools.len(),
+        snapshot.ledger.entries.len()
+    );
+    let head = snapshot.head();
+    apply_snapshot(state, snapshot)?;
+    *state.pm.resumed_from.write()
+        .map_err(|_| EnclaveError::GenericError("Lock error".into()))? = head;
+    Ok(true)
+}
+
+/// Write a snapshot every `config.interval` until the task is dropped
+pub async fn run_snapshot_loop(state: Arc<AppState>, config: Arc<SnapshotConfig>) {
+    let mut ticker = tokio::time::interval(config.interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(e) = save_snapshot(&state, &config) {
+            error!("Periodic snapshot failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::Payout;
+
+    fn sample_snapshot() -> MarketSnapshot {
+        MarketSnapshot {
+            generation: 1,
+            taken_at_ms: 1744038900000,
+            positions: vec![Position {
+                wallet: "0xuser1".to_string(),
+                pool_id: 1,
+                outcome: 3,
+                shares: 1500,
+                cost: 1_000_000,
+            }],
+            resolved_pools: vec![(
+                2,
+                ResolvedPool {
+                    winning_outcome: 5,
+                    timestamp_ms: 1744038900000,
+                    batches: vec![vec![Payout { user: "0xuser2".to_string(), amount: 1000 }]],
+                },
+            )],
+            ledger: LedgerExport::default(),
+            pool_probs: vec![(1, vec![5000, 5000])],
+            processed_bets: Vec::new(),
+            request_nonces: vec![("0xmaker".to_string(), 1)],
+            pending_bets: Vec::new(),
+            pool_chains: Vec::new(),
+            balances: Balances::new(),
+            makers: MakerBook::new(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let key = [7u8; 32];
+        let bytes = encrypt_snapshot(&key, &sample_snapshot()).unwrap();
+        let restored = decrypt_snapshot(&key, &bytes).unwrap();
+        assert_eq!(restored.positions.len(), 1);
+        assert_eq!(restored.positions[0].shares, 1500);
+        assert_eq!(restored.resolved_pools[0].1.batches[0][0].amount, 1000);
+        assert_eq!(restored.request_nonces, vec![("0xmaker".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_tampering_and_wrong_key() {
+        let key = [7u8; 32];
+        let mut bytes = encrypt_snapshot(&key, &sample_snapshot()).unwrap();
+        assert!(decrypt_snapshot(&[8u8; 32], &bytes).is_err());
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        assert!(decrypt_snapshot(&key, &bytes).is_err());
+
+        // The head is authenticated: a snapshot cannot pass for a later one
+        let bytes = encrypt_snapshot(&key, &sample_snapshot()).unwrap();
+        let mut envelope: SnapshotEnvelope = bcs::from_bytes(&bytes).unwrap();
+        envelope.head.generation = 9;
+        assert!(decrypt_snapshot(&key, &bcs::to_bytes(&envelope).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_heads() {
+        let snapshot = sample_snapshot();
+        let head = snapshot.head();
+        assert_eq!(head.to_string().parse::<SnapshotHead>().unwrap(), head);
+        assert!("1:0".parse::<SnapshotHead>().is_err());
+        snapshot.check_extends(&head).unwrap();
+        snapshot.check_extends(&SnapshotHead::default()).unwrap();
+
+        // An earlier generation, or a ledger behind or beside the expected one
+        assert!(snapshot.check_extends(&SnapshotHead { generation: 2, ..head.clone() }).is_err());
+        let ahead = LedgerHead { entry_count: 1, head_hash: vec![1; 32] };
+        assert!(snapshot.check_extends(&SnapshotHead { generation: 1, ledger: ahead }).is_err());
+    }
+}
@@ -11,20 +11,11 @@ pub mod state;
 use crate::common::{to_signed_response, IntentMessage, ProcessDataRequest, ProcessedDataResponse};
 use crate::{AppState, EnclaveError};
 use axum::{extract::State, Json};
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
 use lmsr::LMSR;
-use state::PositionStore;
-
-// ============================================================
-// GLOBAL POSITION STORE (persists across requests in TEE)
-// ============================================================
-static POSITION_STORE: Lazy<RwLock<PositionStore>> = Lazy::new(|| {
-    RwLock::new(PositionStore::new())
-});
 
 // LMSR liquidity parameter
 const LMSR_B: f64 = 100.0;
@@ -129,14 +120,15 @@ pub async fn process_data(
     
     // Store position
     {
-        let mut store = POSITION_STORE.write()
+        let mut store = state.positions.write()
             .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
         store.add_position(
             req.user.clone(),
             req.pool_id,
             req.outcome,
             (shares_f64 * 1000.0) as u64,
-        );
+            req.amount,
+        )?;
     }
     
     // Build response
@@ -179,11 +171,11 @@ pub async fn resolve(
     let total_payout: u64;
     
     {
-        let mut store = POSITION_STORE.write()
+        let mut store = state.positions.write()
             .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
         
         // Get winning positions
-        let winners = store.get_winning_positions(req.pool_id, req.winning_outcome);
+        let winners = store.get_winning_positions(req.pool_id, req.winning_outcome)?;
         
         // Calculate payouts (shares / 1000 = payout in USDC units)
         // In reality, payout = shares * (1 / winning_prob), but simplified here
@@ -198,7 +190,7 @@ pub async fn resolve(
         total_payout = payouts.iter().map(|p| p.amount).sum();
         
         // Clear positions for this pool
-        store.clear_positions_for_pool(req.pool_id);
+        store.clear_pool(req.pool_id)?;
     }
     
     let response = ResolveResponse {
@@ -225,10 +217,10 @@ pub async fn resolve(
 // ============================================================
 // HELPER: Get current state (for debugging)
 // ============================================================
-pub fn get_positions_for_pool(pool_id: u64) -> Result<Vec<state::Position>, EnclaveError> {
-    let store = POSITION_STORE.read()
+pub fn get_positions_for_pool(state: &AppState, pool_id: u64) -> Result<Vec<state::Position>, EnclaveError> {
+    let store = state.positions.read()
         .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
-    Ok(store.get_positions_by_pool(pool_id).into_iter().cloned().collect())
+    store.get_positions_by_pool(pool_id)
 }
 
 #[cfg(test)]
@@ -3,16 +3,11 @@
 //! Stores user positions. Simple and minimal.
 //! Positions: (wallet, pool_id, outcome, shares)
 
+use crate::EnclaveError;
 use std::collections::HashMap;
 
-/// A single user position
-#[derive(Debug, Clone)]
-pub struct Position {
-    pub wallet: String,
-    pub pool_id: u64,
-    pub outcome: u8,    // Which world (0-7)
-    pub shares: u64,    // Scaled by 1000
-}
+pub use crate::repository::{Position, PositionRepository};
+use crate::repository::remaining_cost;
 
 /// Position store - holds all positions in the TEE
 #[derive(Debug, Default)]
@@ -29,13 +24,14 @@ impl PositionStore {
     }
 
     /// Add a position for a user
-    pub fn add_position(&mut self, wallet: String, pool_id: u64, outcome: u8, shares: u64) {
+    pub fn add_position(&mut self, wallet: String, pool_id: u64, outcome: u8, shares: u64, cost: u64) {
         let key = (wallet.clone(), pool_id);
         let position = Position {
             wallet,
             pool_id,
             outcome,
             shares,
+            cost,
         };
         self.positions.entry(key).or_default().push(position);
     }
@@ -80,6 +76,54 @@ impl PositionStore {
     }
 }
 
+/// Repository view of the store. Each bet stays a separate position.
+impl PositionRepository for PositionStore {
+    fn add_position(&mut self, wallet: String, pool_id: u64, outcome: u8, shares: u64, cost: u64) -> Result<(), EnclaveError> {
+        PositionStore::add_position(self, wallet, pool_id, outcome, shares, cost);
+        Ok(())
+    }
+
+    fn remove_shares(&mut self, wallet: &str, pool_id: u64, outcome: u8, shares: u64) -> Result<(), EnclaveError> {
+        // Take shares from the user's positions on this outcome, oldest first
+        let mut remaining = shares;
+        if let Some(positions) = self.positions.get_mut(&(wallet.to_string(), pool_id)) {
+            for position in positions.iter_mut().filter(|p| p.outcome == outcome) {
+                let taken = remaining.min(position.shares);
+                position.cost = remaining_cost(position.cost, position.shares, taken);
+                position.shares -= taken;
+                remaining -= taken;
+            }
+            positions.retain(|p| p.shares > 0);
+        }
+        Ok(())
+    }
+
+    fn get_positions_by_pool(&self, pool_id: u64) -> Result<Vec<Position>, EnclaveError> {
+        Ok(PositionStore::get_positions_by_pool(self, pool_id).into_iter().cloned().collect())
+    }
+
+    fn get_user_positions(&self, wallet: &str, pool_id: u64) -> Result<Vec<Position>, EnclaveError> {
+        Ok(PositionStore::get_user_positions(self, wallet, pool_id).into_iter().cloned().collect())
+    }
+
+    fn clear_pool(&mut self, pool_id: u64) -> Result<(), EnclaveError> {
+        self.clear_positions_for_pool(pool_id);
+        Ok(())
+    }
+
+    fn get_all_positions(&self) -> Result<Vec<Position>, EnclaveError> {
+        Ok(self.positions.values().flatten().cloned().collect())
+    }
+
+    fn restore(&mut self, positions: Vec<Position>) -> Result<(), EnclaveError> {
+        self.positions.clear();
+        for p in positions {
+            PositionStore::add_position(self, p.wallet, p.pool_id, p.outcome, p.shares, p.cost);
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,9 +132,9 @@ mod tests {
     fn test_add_and_get_positions() {
         let mut store = PositionStore::new();
         
-        store.add_position("0xuser1".to_string(), 1, 3, 1000);
-        store.add_position("0xuser1".to_string(), 1, 5, 500);
-        store.add_position("0xuser2".to_string(), 1, 3, 2000);
+        store.add_position("0xuser1".to_string(), 1, 3, 1000, 100000);
+        store.add_position("0xuser1".to_string(), 1, 5, 500, 50000);
+        store.add_position("0xuser2".to_string(), 1, 3, 2000, 200000);
         
         let pool_positions = store.get_positions_by_pool(1);
         assert_eq!(pool_positions.len(), 3);
@@ -103,9 +147,9 @@ mod tests {
     fn test_winning_positions() {
         let mut store = PositionStore::new();
         
-        store.add_position("0xuser1".to_string(), 1, 3, 1000);
-        store.add_position("0xuser2".to_string(), 1, 5, 500);
-        store.add_position("0xuser3".to_string(), 1, 3, 2000);
+        store.add_position("0xuser1".to_string(), 1, 3, 1000, 100000);
+        store.add_position("0xuser2".to_string(), 1, 5, 500, 50000);
+        store.add_position("0xuser3".to_string(), 1, 3, 2000, 200000);
         
         let winners = store.get_winning_positions(1, 3);
         assert_eq!(winners.len(), 2);
@@ -118,8 +162,8 @@ mod tests {
     fn test_clear_positions() {
         let mut store = PositionStore::new();
         
-        store.add_position("0xuser1".to_string(), 1, 3, 1000);
-        store.add_position("0xuser1".to_string(), 2, 5, 500);
+        store.add_position("0xuser1".to_string(), 1, 3, 1000, 100000);
+        store.add_position("0xuser1".to_string(), 2, 5, 500, 50000);
         
         store.clear_positions_for_pool(1);
         
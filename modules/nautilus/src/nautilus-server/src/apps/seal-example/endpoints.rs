@@ -6,7 +6,8 @@ use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::IntentScope;
-use crate::common::IntentMessage;
+use crate::common::{IntentMessage, IntentSigner};
+use crate::rotation::EphemeralKeys;
 use axum::extract::State;
 use axum::Json;
 use fastcrypto::ed25519::Ed25519KeyPair;
@@ -205,12 +206,13 @@ struct WalletPK {
 
 /// Helper function that creates a PTB with a single seal_approve command for the given ID and the
 /// enclave shared object. The signature argument is created using the enclave ephemeral keypair
-/// signing over the intent message of wallet public key.
+/// signing over the intent message of wallet public key. The on-chain check is Ed25519, so the
+/// enclave must boot with the default signature scheme.
 async fn create_ptb(
     package_id: Address,
     enclave_object_id: Address,
     initial_shared_version: u64,
-    enclave_kp: &Ed25519KeyPair,
+    enclave_kp: &EphemeralKeys,
     timestamp: u64,
 ) -> Result<ProgrammableTransaction, Box<dyn std::error::Error>> {
     let mut inputs = vec![];
@@ -228,7 +230,7 @@ async fn create_ptb(
 
     // Sign with enclave ephemeral keypair.
     let signing_bytes = bcs::to_bytes(&intent_msg)?;
-    let signature = enclave_kp.sign(&signing_bytes).as_bytes().to_vec();
+    let signature = enclave_kp.sign_bytes(&signing_bytes);
 
     // Uncomment to run locally and generate test data for consistency test in Move contract.
     // println!(
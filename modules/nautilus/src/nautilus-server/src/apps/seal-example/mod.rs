@@ -9,11 +9,13 @@ pub use types::*;
 
 use crate::app::endpoints::SEAL_API_KEY;
 use crate::common::IntentMessage;
+use crate::common::{attest, AttestationUserData, GetAttestationParams, GetAttestationResponse};
 use crate::common::{to_signed_response, ProcessDataRequest, ProcessedDataResponse};
 use crate::AppState;
 use crate::EnclaveError;
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::Json;
+use fastcrypto::hash::{HashFunction, Sha256};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_repr::{Deserialize_repr, Serialize_repr};
@@ -41,6 +43,17 @@ pub struct WeatherRequest {
     pub location: String,
 }
 
+/// Attestation that also binds the hash of the Seal configuration the enclave
+/// was built with (seal_config.yaml).
+pub async fn get_attestation(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GetAttestationParams>,
+) -> Result<Json<GetAttestationResponse>, EnclaveError> {
+    let config_hash = Sha256::digest(include_str!("seal_config.yaml")).digest.to_vec();
+    let user_data = AttestationUserData::new("seal-example", config_hash);
+    Ok(Json(attest(&state, params.nonce_bytes()?, user_data)?))
+}
+
 pub async fn process_data(
     State(state): State<Arc<AppState>>,
     Json(request): Json<ProcessDataRequest<WeatherRequest>>,
@@ -82,13 +82,21 @@ mod test {
     use super::*;
     use crate::common::IntentMessage;
     use axum::{extract::State, Json};
-    use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
+    use crate::common::{EnclaveKeyPair, SignatureScheme};
+    use crate::rotation::EphemeralKeys;
 
     #[tokio::test]
     async fn test_process_data() {
         let state = Arc::new(AppState {
-            eph_kp: Ed25519KeyPair::generate(&mut rand::thread_rng()),
+            eph_kp: EphemeralKeys::new(EnclaveKeyPair::generate(SignatureScheme::Ed25519)),
             api_key: "045a27812dbe456392913223221306".to_string(),
+            positions: std::sync::RwLock::new(Box::new(
+                crate::repository::InMemoryPositionStore::new(),
+            )),
+            nsm: Box::new(crate::nsm::MockNsm::new(
+                Default::default(),
+                &mut rand::thread_rng(),
+            )),
         });
         let signed_weather_response = process_data(
             State(state),
@@ -96,6 +104,7 @@ mod test {
                 payload: WeatherRequest {
                     location: "San Francisco".to_string(),
                 },
+                signature: None,
             }),
         )
         .await
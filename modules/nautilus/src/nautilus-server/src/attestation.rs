@@ -0,0 +1,303 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Nitro attestation documents.
+//!
+//! The NSM returns its attestation document as a COSE_Sign1 structure: the
+//! payload is the CBOR document, signed with ES384 by a leaf certificate the
+//! document carries, chained through its CA bundle to the AWS Nitro Enclaves
+//! root. `verify` checks the signature, that the chain starts at a pinned root
+//! and that every certificate in it is valid at a given time, then returns the
+//! decoded document.
+//!
+//! The root is pinned by the SHA-256 of its DER encoding; the AWS root is
+//! `AWS_NITRO_ROOT_SHA256`, as published in the Nitro Enclaves documentation.
+
+use crate::common::AttestationUserData;
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use p384::ecdsa::signature::{Signer, Verifier};
+use p384::ecdsa::{Signature, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use x509_cert::der::{Decode, Encode};
+use x509_cert::ext::pkix::BasicConstraints;
+use x509_cert::Certificate;
+
+/// SHA-256 of the DER encoded AWS Nitro Enclaves root certificate
+pub const AWS_NITRO_ROOT_SHA256: [u8; 32] = [
+    0x64, 0x1a, 0x03, 0x21, 0xa3, 0xe2, 0x44, 0xef, 0xe4, 0x56, 0x46, 0x31, 0x95, 0xd6, 0x06, 0x31, 0x7e, 0xd7,
+    0xcd, 0xcc, 0x3c, 0x17, 0x56, 0xe0, 0x98, 0x93, 0xf3, 0xc6, 0x8f, 0x79, 0xbb, 0x5b,
+];
+
+const COSE_SIGN1_TAG: u8 = 0xd2; // CBOR tag 18
+const COSE_ALG: i128 = 1; // Protected header label of the algorithm
+const COSE_ES384: i128 = -35;
+const ECDSA_WITH_SHA384: &str = "1.2.840.10045.4.3.3";
+
+/// Attestation document - Match the NSM's CBOR payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationDocument {
+    pub module_id: String,
+    pub digest: String,
+    pub timestamp: u64, // Unix ms
+    pub pcrs: BTreeMap<u8, ByteBuf>,
+    pub certificate: ByteBuf,    // Leaf certificate, DER
+    pub cabundle: Vec<ByteBuf>,  // Root first, DER
+    pub public_key: Option<ByteBuf>,
+    pub user_data: Option<ByteBuf>,
+    pub nonce: Option<ByteBuf>,
+}
+
+/// COSE_Sign1 parts the signature covers
+struct CoseSign1 {
+    protected: Vec<u8>,
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl CoseSign1 {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let bytes = bytes.strip_prefix(&[COSE_SIGN1_TAG]).unwrap_or(bytes);
+        let (protected, _unprotected, payload, signature): (ByteBuf, serde_cbor::Value, ByteBuf, ByteBuf) =
+            serde_cbor::from_slice(bytes).map_err(|e| invalid(format!("not a COSE_Sign1 structure: {e}")))?;
+        Ok(Self {
+            protected: protected.into_vec(),
+            payload: payload.into_vec(),
+            signature: signature.into_vec(),
+        })
+    }
+
+    fn algorithm(&self) -> Result<i128, EnclaveError> {
+        let header: BTreeMap<serde_cbor::Value, serde_cbor::Value> =
+            serde_cbor::from_slice(&self.protected).map_err(|e| invalid(format!("bad protected header: {e}")))?;
+        match header.get(&serde_cbor::Value::Integer(COSE_ALG)) {
+            Some(serde_cbor::Value::Integer(alg)) => Ok(*alg),
+            _ => Err(invalid("no algorithm in protected header".into())),
+        }
+    }
+
+    /// Sig_structure of RFC 8152, with no external data
+    fn signed_bytes(&self) -> Vec<u8> {
+        serde_cbor::to_vec(&(
+            "Signature1",
+            ByteBuf::from(self.protected.clone()),
+            ByteBuf::new(),
+            ByteBuf::from(self.payload.clone()),
+        ))
+        .expect("should not fail")
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        serde_cbor::to_vec(&(
+            ByteBuf::from(self.protected.clone()),
+            BTreeMap::<u8, u8>::new(),
+            ByteBuf::from(self.payload.clone()),
+            ByteBuf::from(self.signature.clone()),
+        ))
+        .expect("should not fail")
+    }
+}
+
+/// Encode `document` as the NSM does, signed with the key of its leaf certificate
+pub fn sign(document: &AttestationDocument, key: &SigningKey) -> Vec<u8> {
+    let mut header = BTreeMap::new();
+    header.insert(COSE_ALG, COSE_ES384);
+    let mut cose = CoseSign1 {
+        protected: serde_cbor::to_vec(&header).expect("should not fail"),
+        payload: serde_cbor::to_vec(document).expect("should not fail"),
+        signature: Vec::new(),
+    };
+    let signature: Signature = key.sign(&cose.signed_bytes());
+    cose.signature = signature.to_bytes().to_vec();
+    cose.to_bytes()
+}
+
+fn invalid(e: String) -> EnclaveError {
+    EnclaveError::GenericError(format!("Invalid attestation document: {e}"))
+}
+
+/// Decode an attestation document without checking its signature or chain
+pub fn decode(bytes: &[u8]) -> Result<AttestationDocument, EnclaveError> {
+    let cose = CoseSign1::from_bytes(bytes)?;
+    serde_cbor::from_slice(&cose.payload).map_err(|e| invalid(e.to_string()))
+}
+
+/// Decode an attestation document, checking it is signed by a certificate that
+/// chains to the root with SHA-256 `root_sha256` and that the chain is valid at
+/// `at_ms`
+pub fn verify(bytes: &[u8], root_sha256: &[u8; 32], at_ms: u64) -> Result<AttestationDocument, EnclaveError> {
+    let cose = CoseSign1::from_bytes(bytes)?;
+    if cose.algorithm()? != COSE_ES384 {
+        return Err(invalid("not signed with ES384".into()));
+    }
+    let document: AttestationDocument = serde_cbor::from_slice(&cose.payload).map_err(|e| invalid(e.to_string()))?;
+    if document.digest != "SHA384" {
+        return Err(invalid(format!("unexpected digest {}", document.digest)));
+    }
+    if let Some((index, _)) = document.pcrs.iter().find(|(_, pcr)| ![32, 48, 64].contains(&pcr.len())) {
+        return Err(invalid(format!("PCR{index} has an invalid length")));
+    }
+
+    // Root first, leaf last
+    let root = document.cabundle.first().ok_or_else(|| invalid("empty CA bundle".into()))?;
+    if Sha256::digest(root).digest != *root_sha256 {
+        return Err(invalid("CA bundle does not start at the pinned root".into()));
+    }
+    let chain = document
+        .cabundle
+        .iter()
+        .chain(std::iter::once(&document.certificate))
+        .map(|der| Certificate::from_der(der).map_err(|e| invalid(format!("bad certificate: {e}"))))
+        .collect::<Result<Vec<_>, _>>()?;
+    for cert in &chain {
+        check_validity(cert, at_ms)?;
+    }
+    for pair in chain.windows(2) {
+        check_issued_by(&pair[1], &pair[0])?;
+    }
+
+    let leaf = chain.last().expect("chain has the leaf");
+    let signature = Signature::from_slice(&cose.signature).map_err(|e| invalid(format!("bad signature: {e}")))?;
+    public_key(leaf)?
+        .verify(&cose.signed_bytes(), &signature)
+        .map_err(|_| invalid("signature does not match the leaf certificate".into()))?;
+    Ok(document)
+}
+
+fn public_key(cert: &Certificate) -> Result<VerifyingKey, EnclaveError> {
+    let spki = &cert.tbs_certificate.subject_public_key_info;
+    VerifyingKey::from_sec1_bytes(spki.subject_public_key.raw_bytes())
+        .map_err(|_| invalid(format!("{} does not have a P-384 key", cert.tbs_certificate.subject)))
+}
+
+fn check_validity(cert: &Certificate, at_ms: u64) -> Result<(), EnclaveError> {
+    let validity = &cert.tbs_certificate.validity;
+    let not_before = validity.not_before.to_unix_duration().as_millis() as u64;
+    let not_after = validity.not_after.to_unix_duration().as_millis() as u64;
+    if at_ms < not_before || at_ms > not_after {
+        return Err(invalid(format!("{} is not valid at {at_ms}", cert.tbs_certificate.subject)));
+    }
+    Ok(())
+}
+
+fn check_issued_by(cert: &Certificate, issuer: &Certificate) -> Result<(), EnclaveError> {
+    let subject = &cert.tbs_certificate.subject;
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        return Err(invalid(format!("{subject} is not issued by {}", issuer.tbs_certificate.subject)));
+    }
+    let is_ca = issuer
+        .tbs_certificate
+        .get::<BasicConstraints>()
+        .map_err(|e| invalid(format!("bad basic constraints: {e}")))?
+        .is_some_and(|(_, constraints)| constraints.ca);
+    if !is_ca {
+        return Err(invalid(format!("{} is not a CA", issuer.tbs_certificate.subject)));
+    }
+    if cert.signature_algorithm.oid.to_string() != ECDSA_WITH_SHA384 {
+        return Err(invalid(format!("{subject} is not signed with ecdsa-with-SHA384")));
+    }
+    let tbs = cert.tbs_certificate.to_der().map_err(|e| invalid(e.to_string()))?;
+    let signature = cert
+        .signature
+        .as_bytes()
+        .and_then(|der| Signature::from_der(der).ok())
+        .ok_or_else(|| invalid(format!("bad signature on {subject}")))?;
+    public_key(issuer)?
+        .verify(&tbs, &signature)
+        .map_err(|_| invalid(format!("bad signature on {subject}")))
+}
+
+/// Fields of an attestation document for clients, byte strings in Hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecodedAttestation {
+    pub module_id: String,
+    pub timestamp: u64,
+    pub digest: String,
+    pub pcrs: BTreeMap<u8, String>,
+    pub public_key: Option<String>,
+    pub user_data: Option<String>,
+    pub nonce: Option<String>,
+    /// `user_data`, if it is the user data of a Nautilus server
+    pub app: Option<AttestationUserData>,
+}
+
+impl From<&AttestationDocument> for DecodedAttestation {
+    fn from(document: &AttestationDocument) -> Self {
+        let hex = |bytes: &Option<ByteBuf>| bytes.as_ref().map(Hex::encode);
+        Self {
+            module_id: document.module_id.clone(),
+            timestamp: document.timestamp,
+            digest: document.digest.clone(),
+            pcrs: document.pcrs.iter().map(|(index, pcr)| (*index, Hex::encode(pcr))).collect(),
+            public_key: hex(&document.public_key),
+            user_data: hex(&document.user_data),
+            nonce: hex(&document.nonce),
+            app: document.user_data.as_ref().and_then(|bytes| bcs::from_bytes(bytes).ok()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Recorded documents signed by a test chain (root, zonal CA, instance leaf). The
+    // leaf is valid 2026-01-01T00:00Z to 03:00Z and the documents are from 01:00Z.
+    const SAMPLE: &str = include_str!("../testdata/attestation_sample.hex");
+    const SAMPLE_BARE: &str = include_str!("../testdata/attestation_sample_bare.hex");
+    const TEST_ROOT: &str = include_str!("../testdata/nitro_test_root.hex");
+    const AT_MS: u64 = 1_767_229_200_000;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        Hex::decode(hex.trim()).unwrap()
+    }
+
+    fn test_root() -> [u8; 32] {
+        Sha256::digest(bytes(TEST_ROOT)).digest
+    }
+
+    #[test]
+    fn test_verify_attestation() {
+        let document = verify(&bytes(SAMPLE), &test_root(), AT_MS).unwrap();
+        assert_eq!(document.timestamp, AT_MS);
+        assert_eq!(document.pcrs.len(), 16);
+        assert_eq!(document.public_key.as_ref().unwrap().as_slice(), &[0x42; 32]);
+        assert_eq!(document.nonce.as_ref().unwrap().as_slice(), b"nautilus-nonce");
+
+        let decoded = DecodedAttestation::from(&document);
+        assert_eq!(decoded.pcrs[&0], "a0".repeat(48));
+        assert_eq!(decoded.nonce, Some(Hex::encode(b"nautilus-nonce")));
+        let app = decoded.app.unwrap();
+        assert_eq!((app.app_id.as_str(), app.config_hash), ("pm", vec![0x11; 32]));
+
+        let bare = DecodedAttestation::from(&verify(&bytes(SAMPLE_BARE), &test_root(), AT_MS).unwrap());
+        assert_eq!((bare.public_key, bare.user_data, bare.nonce, bare.app), (None, None, None, None));
+    }
+
+    #[test]
+    fn test_reject_attestation() {
+        let sample = bytes(SAMPLE);
+        decode(&sample).unwrap();
+
+        // Another root, or a chain that is not valid yet or has expired
+        assert!(verify(&sample, &AWS_NITRO_ROOT_SHA256, AT_MS).is_err());
+        assert!(verify(&sample, &test_root(), 1_767_225_599_000).is_err());
+        assert!(verify(&sample, &test_root(), 1_767_236_401_000).is_err());
+
+        // A tampered signature or payload
+        let mut tampered = sample.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(verify(&tampered, &test_root(), AT_MS).is_err());
+        let mut tampered = sample.clone();
+        let at = tampered.windows(14).position(|w| w == b"nautilus-nonce").unwrap();
+        tampered[at] = b'N';
+        assert!(decode(&tampered).is_ok());
+        assert!(verify(&tampered, &test_root(), AT_MS).is_err());
+
+        // Not an attestation document
+        assert!(decode(b"not cbor").is_err());
+    }
+}
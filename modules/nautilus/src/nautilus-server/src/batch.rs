@@ -0,0 +1,222 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Batch signing of intent messages under one Merkle root.
+//!
+//! Messages submitted within `window` of the first message of a batch are
+//! collected. When the window closes, the enclave builds a Merkle tree (see
+//! `merkle`) over their BCS bytes, signs `DomainIntentMessage<BatchRoot>` under
+//! the batcher's header once and hands every caller its message, the signed root
+//! and its inclusion proof. A verifier checks the root signature once, then each
+//! message against the root.
+
+use crate::common::{EnclavePublicKey, ProcessedDataResponse};
+use crate::intent::{
+    to_signed_domain_response, verify_signed_domain_response, ChainId, DomainIntentMessage, IntentHeader,
+};
+use crate::merkle::{verify_proof, MerkleProof, MerkleTree};
+use crate::{AppState, EnclaveError};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Root over the BCS bytes of every intent message of a batch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRoot {
+    pub root: Vec<u8>,
+    pub leaf_count: u64,
+}
+
+/// A message signed as part of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchedResponse<T: Serialize> {
+    pub response: DomainIntentMessage<T>,
+    pub batch: ProcessedDataResponse<DomainIntentMessage<BatchRoot>>,
+    pub proof: MerkleProof,
+}
+
+/// Check that `batched.response` is included in a batch whose root `pk` signed,
+/// both for `app_id` on `chain_id`
+pub fn verify_batched_response<T: Serialize>(
+    pk: &EnclavePublicKey,
+    batched: &BatchedResponse<T>,
+    app_id: u16,
+    chain_id: ChainId,
+) -> Result<(), EnclaveError> {
+    verify_signed_domain_response(pk, &batched.batch, app_id, chain_id)?;
+    let header = &batched.response.header;
+    header.validate()?;
+    if header.app_id != app_id || header.chain_id != chain_id {
+        return Err(EnclaveError::GenericError("Message is for another app or chain than its batch".into()));
+    }
+    let leaf = bcs::to_bytes(&batched.response)
+        .map_err(|e| EnclaveError::GenericError(format!("Encode error: {e}")))?;
+    if !verify_proof(&batched.batch.response.data.root, &leaf, &batched.proof) {
+        return Err(EnclaveError::GenericError("Message is not included in the signed batch".into()));
+    }
+    Ok(())
+}
+
+type SignedRoot = Arc<ProcessedDataResponse<DomainIntentMessage<BatchRoot>>>;
+type PendingLeaf = (Vec<u8>, oneshot::Sender<(SignedRoot, MerkleProof)>);
+
+/// A message waiting for its batch to be signed
+pub struct QueuedMessage<T: Serialize> {
+    message: DomainIntentMessage<T>,
+    rx: oneshot::Receiver<(SignedRoot, MerkleProof)>,
+}
+
+impl<T: Serialize> QueuedMessage<T> {
+    /// Wait for the batch root and the message's inclusion proof
+    pub async fn wait(self) -> Result<BatchedResponse<T>, EnclaveError> {
+        let (batch, proof) = self
+            .rx
+            .await
+            .map_err(|_| EnclaveError::GenericError("Batch was dropped before signing".into()))?;
+        Ok(BatchedResponse {
+            response: self.message,
+            batch: (*batch).clone(),
+            proof,
+        })
+    }
+}
+
+/// Collects messages and signs them in batches
+pub struct SignatureBatcher {
+    window: Duration,
+    header: IntentHeader,
+    pending: Mutex<Vec<PendingLeaf>>,
+}
+
+impl SignatureBatcher {
+    /// Batches close `window` after their first message; roots are signed under
+    /// `header`
+    pub fn new(window: Duration, header: IntentHeader) -> Self {
+        Self {
+            window,
+            header,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add `message` to the current batch and wait for its root to be signed
+    pub async fn sign<T: Serialize>(
+        self: &Arc<Self>,
+        state: Arc<AppState>,
+        message: DomainIntentMessage<T>,
+    ) -> Result<BatchedResponse<T>, EnclaveError> {
+        self.enqueue(state, message)?.wait().await
+    }
+
+    /// Add `message` to the current batch without waiting. The message is signed
+    /// with the batch even if the returned handle is dropped.
+    pub fn enqueue<T: Serialize>(
+        self: &Arc<Self>,
+        state: Arc<AppState>,
+        message: DomainIntentMessage<T>,
+    ) -> Result<QueuedMessage<T>, EnclaveError> {
+        let leaf = bcs::to_bytes(&message)
+            .map_err(|e| EnclaveError::GenericError(format!("Encode error: {e}")))?;
+        let (tx, rx) = oneshot::channel();
+        let opens_batch = {
+            let mut pending = self.pending.lock()
+                .map_err(|_| EnclaveError::GenericError("Lock error".into()))?;
+            pending.push((leaf, tx));
+            pending.len() == 1
+        };
+        // The timer runs on its own task so a caller going away cannot strand the batch
+        if opens_batch {
+            let batcher = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(batcher.window).await;
+                batcher.flush(&state);
+            });
+        }
+        Ok(QueuedMessage { message, rx })
+    }
+
+    /// Sign the root over every pending message and answer their callers
+    pub fn flush(&self, state: &AppState) {
+        let pending = match self.pending.lock() {
+            Ok(mut pending) => std::mem::take(&mut *pending),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+        };
+        if pending.is_empty() {
+            return;
+        }
+        let tree = MerkleTree::new(&pending.iter().map(|(leaf, _)| leaf).collect::<Vec<_>>());
+        let timestamp_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let batch = Arc::new(to_signed_domain_response(
+            &state.eph_kp,
+            self.header.clone(),
+            BatchRoot {
+                root: tree.root().to_vec(),
+                leaf_count: tree.len() as u64,
+            },
+            timestamp_ms,
+        ));
+        for (i, (_, tx)) in pending.into_iter().enumerate() {
+            // The caller may have gone away; its message is still in the signed root
+            let _ = tx.send((batch.clone(), tree.proof(i).expect("leaf in range")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{EnclaveKeyPair, SignatureScheme};
+    use crate::intent::{APP_NAUTILUS, APP_PM, SUI_MAINNET, SUI_TESTNET};
+    use crate::nsm::MockNsm;
+    use crate::rotation::EphemeralKeys;
+    use crate::repository::InMemoryPositionStore;
+    use std::sync::RwLock;
+
+    #[tokio::test]
+    async fn test_messages_share_one_root_signature() {
+        let state = Arc::new(AppState {
+            eph_kp: EphemeralKeys::new(EnclaveKeyPair::generate(SignatureScheme::Ed25519)),
+            api_key: String::new(),
+            positions: RwLock::new(Box::new(InMemoryPositionStore::new())),
+            nsm: Box::new(MockNsm::new(Default::default(), &mut rand::thread_rng())),
+            #[cfg(feature = "pm")]
+            pm: Default::default(),
+        });
+        let root_header = IntentHeader::new(APP_PM, 5, SUI_TESTNET).unwrap();
+        let batcher = Arc::new(SignatureBatcher::new(Duration::from_millis(50), root_header.clone()));
+
+        let message = |n: u64| DomainIntentMessage {
+            header: IntentHeader::new(APP_PM, 0, SUI_TESTNET).unwrap(),
+            timestamp_ms: 1744038900000,
+            data: n,
+        };
+        let sign = |n: u64| batcher.sign(state.clone(), message(n));
+        let (a, b, c) = tokio::join!(sign(1), sign(2), sign(3));
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        assert_eq!(a.batch.signature, b.batch.signature);
+        assert_eq!(b.batch.signature, c.batch.signature);
+        assert_eq!(a.batch.response.data.leaf_count, 3);
+        assert_eq!(a.batch.response.header, root_header);
+
+        let pk = state.eph_kp.public();
+        for batched in [&a, &b, &c] {
+            verify_batched_response(&pk, batched, APP_PM, SUI_TESTNET).unwrap();
+        }
+        // A message cannot borrow another's proof
+        let mut forged = a.clone();
+        forged.response.data = 4;
+        assert!(verify_batched_response(&pk, &forged, APP_PM, SUI_TESTNET).is_err());
+        // Nor is a batch valid for another app or chain
+        assert!(verify_batched_response(&pk, &a, APP_NAUTILUS, SUI_TESTNET).is_err());
+        assert!(verify_batched_response(&pk, &a, APP_PM, SUI_MAINNET).is_err());
+
+        // A later message opens a new batch
+        let d = sign(4).await.unwrap();
+        assert_eq!(d.batch.response.data.leaf_count, 1);
+        verify_batched_response(&pk, &d, APP_PM, SUI_TESTNET).unwrap();
+    }
+}
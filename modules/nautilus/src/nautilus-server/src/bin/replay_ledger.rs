@@ -0,0 +1,56 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Replay a PM trade ledger exported from `GET /ledger`.
+//!
+//! Rebuilds the position store from scratch, recomputes every bet and resolution,
+//! and checks each signature and signed checkpoint against the enclave key current
+//! at its entry. Pass the key the ledger started with, then the key of every later
+//! enclave boot, comma-separated; rotated keys are followed through their handoffs.
+//! Signatures are checked for the chain the enclave ran on (its SUI_CHAIN_ID).
+//!
+//! Usage: replay-ledger <ledger.json> <enclave_pk_hex>[,<pk_hex>...] <mainnet|testnet|chain_id_hex> [ed25519|secp256k1|secp256r1]
+
+use anyhow::{anyhow, Result};
+use fastcrypto::encoding::{Encoding, Hex};
+use nautilus_server::app::ledger::{replay, LedgerExport};
+use nautilus_server::common::{EnclavePublicKey, SignatureScheme};
+use nautilus_server::intent::parse_chain_id;
+use nautilus_server::repository::PositionRepository;
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 4 && args.len() != 5 {
+        return Err(anyhow!(
+            "usage: {} <ledger.json> <enclave_pk_hex>[,<pk_hex>...] <mainnet|testnet|chain_id_hex> [ed25519|secp256k1|secp256r1]",
+            args[0]
+        ));
+    }
+
+    let export: LedgerExport = serde_json::from_str(&std::fs::read_to_string(&args[1])?)?;
+    let chain_id = parse_chain_id(&args[3])?;
+    let scheme = match args.get(4) {
+        Some(scheme) => scheme.parse()?,
+        None => SignatureScheme::Ed25519,
+    };
+    let trusted = args[2]
+        .split(',')
+        .map(|pk_hex| {
+            let pk_bytes = Hex::decode(pk_hex).map_err(|e| anyhow!("Invalid public key hex: {e}"))?;
+            Ok(EnclavePublicKey::from_bytes(scheme, &pk_bytes)?)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let report = replay(&export, &trusted, chain_id)?;
+    println!(
+        "OK: {} entries, {} signed checkpoints, head {}",
+        report.entry_count, report.checkpoint_count, report.head_hash
+    );
+    for position in report.store.get_all_positions()? {
+        println!(
+            "{} pool={} outcome={} shares={}",
+            position.wallet, position.pool_id, position.outcome, position.shares
+        );
+    }
+    Ok(())
+}
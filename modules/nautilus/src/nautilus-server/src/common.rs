@@ -1,29 +1,34 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::attestation::{self, DecodedAttestation};
 use crate::AppState;
 use crate::EnclaveError;
-use axum::{extract::State, Json};
-use fastcrypto::traits::Signer;
+use axum::{extract::Query, extract::State, Json};
+use fastcrypto::ed25519::{Ed25519KeyPair, Ed25519PublicKey, Ed25519Signature};
+use fastcrypto::secp256k1::{Secp256k1KeyPair, Secp256k1PublicKey, Secp256k1Signature};
+use fastcrypto::secp256r1::{Secp256r1KeyPair, Secp256r1PublicKey, Secp256r1Signature};
+use fastcrypto::traits::{AllowedRng, Signer, VerifyingKey};
 use fastcrypto::{encoding::Encoding, traits::ToFromBytes};
 use fastcrypto::{encoding::Hex, traits::KeyPair as FcKeyPair};
-use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
-use nsm_api::driver;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_bytes::ByteBuf;
+use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::info;
 
-use fastcrypto::ed25519::Ed25519KeyPair;
 /// ==== COMMON TYPES ====
 /// Intent message wrapper struct containing the intent scope and timestamp.
 /// This standardizes the serialized payload for signing.
 /// Generic over the data type T. Intent scope is stored as u8.
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntentMessage<T: Serialize> {
     pub intent: u8,
     pub timestamp_ms: u64,
@@ -40,22 +45,28 @@ impl<T: Serialize> IntentMessage<T> {
     }
 }
 
-/// Wrapper struct containing the response (the intent message) and signature.
-#[derive(Serialize, Deserialize)]
+/// Wrapper struct containing the response (the intent message), signature and
+/// the scheme that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessedDataResponse<T> {
     pub response: T,
     pub signature: String,
+    #[serde(default)]
+    pub scheme: SignatureScheme,
 }
 
-/// Wrapper struct containing the request payload.
+/// Wrapper struct containing the request payload, and the wallet signature of
+/// apps that authorize requests by their sender (see `wallet`).
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProcessDataRequest<T> {
     pub payload: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 /// Sign the bcs bytes of the the payload with keypair.
-pub fn to_signed_response<T: Serialize + Clone>(
-    kp: &Ed25519KeyPair,
+pub fn to_signed_response<T: Serialize + Clone, S: IntentSigner + ?Sized>(
+    kp: &S,
     payload: T,
     timestamp_ms: u64,
     intent: u8,
@@ -63,53 +74,438 @@ pub fn to_signed_response<T: Serialize + Clone>(
     let intent_msg = IntentMessage::new(payload.clone(), timestamp_ms, intent);
 
     let signing_payload = bcs::to_bytes(&intent_msg).expect("should not fail");
-    let sig = kp.sign(&signing_payload);
+    let sig = kp.sign_bytes(&signing_payload);
     ProcessedDataResponse {
         response: intent_msg,
         signature: Hex::encode(sig),
+        scheme: kp.scheme(),
+    }
+}
+
+/// Check that `signed` was signed by `pk`: rebuild the bcs bytes of the intent
+/// message and verify the signature with the key's scheme.
+pub fn verify_signed_response<T: Serialize>(
+    pk: &EnclavePublicKey,
+    signed: &ProcessedDataResponse<IntentMessage<T>>,
+) -> Result<(), EnclaveError> {
+    if signed.scheme != pk.scheme() {
+        return Err(EnclaveError::GenericError(format!(
+            "Signed with {:?}, key is {:?}",
+            signed.scheme,
+            pk.scheme()
+        )));
+    }
+    let msg = bcs::to_bytes(&signed.response)
+        .map_err(|e| EnclaveError::GenericError(format!("Encode error: {e}")))?;
+    let sig = Hex::decode(&signed.signature)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid signature hex: {e}")))?;
+    pk.verify(&msg, &sig)
+}
+
+/// Request to check a signed response as returned by the enclave (JSON).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyRequest {
+    pub signed: ProcessedDataResponse<IntentMessage<serde_json::Value>>,
+    /// Hex public key to check against, of the response's scheme. Defaults to
+    /// this enclave's keys, current and recently retired.
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+/// Keys a verify request checks against: `public_key` of `scheme` if given, or
+/// `enclave_keys`.
+pub fn keys_to_check(
+    public_key: Option<&str>,
+    scheme: SignatureScheme,
+    enclave_keys: &[EnclavePublicKey],
+) -> Result<Vec<EnclavePublicKey>, EnclaveError> {
+    match public_key {
+        Some(hex) => {
+            let bytes = Hex::decode(hex)
+                .map_err(|e| EnclaveError::GenericError(format!("Invalid public key hex: {e}")))?;
+            Ok(vec![EnclavePublicKey::from_bytes(scheme, &bytes)?])
+        }
+        None => Ok(enclave_keys.to_vec()),
+    }
+}
+
+/// Result of checking a signed response.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+    pub intent: u8,
+    pub scheme: SignatureScheme,
+    /// Hex public key the signature was checked against
+    pub public_key: String,
+    /// Whether that key is this enclave's key, or one it retired within the
+    /// rotation overlap window
+    pub enclave_key: bool,
+    /// Why the signature did not verify
+    pub error: Option<String>,
+}
+
+impl VerifyRequest {
+    /// Keys to check against: the provided one, or `enclave_keys`.
+    pub fn public_keys(&self, enclave_keys: &[EnclavePublicKey]) -> Result<Vec<EnclavePublicKey>, EnclaveError> {
+        keys_to_check(self.public_key.as_deref(), self.signed.scheme, enclave_keys)
+    }
+
+    /// Check the signed response with its data read as `T`, the payload type of
+    /// its intent scope. Fails only if the data is not a `T`.
+    pub fn verify_as<T: Serialize + DeserializeOwned>(
+        &self,
+        pk: &EnclavePublicKey,
+        enclave_keys: &[EnclavePublicKey],
+    ) -> Result<VerifyResponse, EnclaveError> {
+        let message = &self.signed.response;
+        let data: T = serde_json::from_value(message.data.clone())
+            .map_err(|e| EnclaveError::GenericError(format!("Data does not match intent {}: {e}", message.intent)))?;
+        let typed = ProcessedDataResponse {
+            response: IntentMessage::new(data, message.timestamp_ms, message.intent),
+            signature: self.signed.signature.clone(),
+            scheme: self.signed.scheme,
+        };
+        let result = verify_signed_response(pk, &typed);
+        Ok(VerifyResponse {
+            valid: result.is_ok(),
+            intent: message.intent,
+            scheme: self.signed.scheme,
+            public_key: Hex::encode(pk.as_bytes()),
+            enclave_key: enclave_keys.contains(pk),
+            error: result.err().map(|e| e.to_string()),
+        })
+    }
+}
+
+/// ==== SIGNERS ====
+/// Signature scheme of an enclave key. Flags follow Sui's signature scheme flags.
+/// ECDSA schemes sign the SHA-256 digest of the message.
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum SignatureScheme {
+    #[default]
+    Ed25519 = 0,
+    Secp256k1 = 1,
+    Secp256r1 = 2,
+}
+
+impl SignatureScheme {
+    /// Scheme of the enclave key from `ENCLAVE_SIGNATURE_SCHEME` (Ed25519 if unset)
+    pub fn from_env() -> Result<Self, EnclaveError> {
+        match std::env::var("ENCLAVE_SIGNATURE_SCHEME") {
+            Ok(scheme) if !scheme.is_empty() => scheme.parse(),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+impl FromStr for SignatureScheme {
+    type Err = EnclaveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ed25519" => Ok(Self::Ed25519),
+            "secp256k1" => Ok(Self::Secp256k1),
+            "secp256r1" => Ok(Self::Secp256r1),
+            _ => Err(EnclaveError::GenericError(format!("Unknown signature scheme {s}"))),
+        }
+    }
+}
+
+/// The repr values are Sui's signature flags.
+impl TryFrom<u8> for SignatureScheme {
+    type Error = EnclaveError;
+
+    fn try_from(flag: u8) -> Result<Self, Self::Error> {
+        match flag {
+            0 => Ok(Self::Ed25519),
+            1 => Ok(Self::Secp256k1),
+            2 => Ok(Self::Secp256r1),
+            _ => Err(EnclaveError::GenericError(format!("Unknown signature flag {flag}"))),
+        }
+    }
+}
+
+/// A key that signs intent messages.
+pub trait IntentSigner {
+    fn scheme(&self) -> SignatureScheme;
+    fn public_key_bytes(&self) -> Vec<u8>;
+    fn sign_bytes(&self, msg: &[u8]) -> Vec<u8>;
+}
+
+impl IntentSigner for Ed25519KeyPair {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::Ed25519
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        self.public().as_bytes().to_vec()
+    }
+
+    fn sign_bytes(&self, msg: &[u8]) -> Vec<u8> {
+        self.sign(msg).as_bytes().to_vec()
+    }
+}
+
+impl IntentSigner for Secp256k1KeyPair {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::Secp256k1
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        self.public().as_bytes().to_vec()
+    }
+
+    fn sign_bytes(&self, msg: &[u8]) -> Vec<u8> {
+        self.sign(msg).as_bytes().to_vec()
+    }
+}
+
+impl IntentSigner for Secp256r1KeyPair {
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::Secp256r1
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        self.public().as_bytes().to_vec()
+    }
+
+    fn sign_bytes(&self, msg: &[u8]) -> Vec<u8> {
+        self.sign(msg).as_bytes().to_vec()
+    }
+}
+
+/// Ephemeral enclave keypair of the scheme chosen at boot.
+pub enum EnclaveKeyPair {
+    Ed25519(Box<Ed25519KeyPair>),
+    Secp256k1(Secp256k1KeyPair),
+    Secp256r1(Secp256r1KeyPair),
+}
+
+impl EnclaveKeyPair {
+    /// Generate a fresh keypair of `scheme`.
+    pub fn generate(scheme: SignatureScheme) -> Self {
+        Self::generate_with(scheme, &mut rand::thread_rng())
+    }
+
+    /// Derive the keypair of `scheme` from `seed`, for reproducible local runs.
+    pub fn from_seed(scheme: SignatureScheme, seed: [u8; 32]) -> Self {
+        Self::generate_with(scheme, &mut StdRng::from_seed(seed))
+    }
+
+    fn generate_with<R: AllowedRng>(scheme: SignatureScheme, rng: &mut R) -> Self {
+        match scheme {
+            SignatureScheme::Ed25519 => Self::Ed25519(Box::new(Ed25519KeyPair::generate(rng))),
+            SignatureScheme::Secp256k1 => Self::Secp256k1(Secp256k1KeyPair::generate(rng)),
+            SignatureScheme::Secp256r1 => Self::Secp256r1(Secp256r1KeyPair::generate(rng)),
+        }
+    }
+
+    pub fn public(&self) -> EnclavePublicKey {
+        match self {
+            Self::Ed25519(kp) => EnclavePublicKey::Ed25519(kp.public().clone()),
+            Self::Secp256k1(kp) => EnclavePublicKey::Secp256k1(kp.public().clone()),
+            Self::Secp256r1(kp) => EnclavePublicKey::Secp256r1(kp.public().clone()),
+        }
+    }
+
+    fn signer(&self) -> &dyn IntentSigner {
+        match self {
+            Self::Ed25519(kp) => kp.as_ref(),
+            Self::Secp256k1(kp) => kp,
+            Self::Secp256r1(kp) => kp,
+        }
+    }
+}
+
+impl IntentSigner for EnclaveKeyPair {
+    fn scheme(&self) -> SignatureScheme {
+        self.signer().scheme()
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        self.signer().public_key_bytes()
+    }
+
+    fn sign_bytes(&self, msg: &[u8]) -> Vec<u8> {
+        self.signer().sign_bytes(msg)
+    }
+}
+
+/// Public key of an enclave keypair, used to verify its signatures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnclavePublicKey {
+    Ed25519(Ed25519PublicKey),
+    Secp256k1(Secp256k1PublicKey),
+    Secp256r1(Secp256r1PublicKey),
+}
+
+impl EnclavePublicKey {
+    pub fn from_bytes(scheme: SignatureScheme, bytes: &[u8]) -> Result<Self, EnclaveError> {
+        let invalid = |e| EnclaveError::GenericError(format!("Invalid {scheme:?} public key: {e}"));
+        Ok(match scheme {
+            SignatureScheme::Ed25519 => Self::Ed25519(Ed25519PublicKey::from_bytes(bytes).map_err(invalid)?),
+            SignatureScheme::Secp256k1 => Self::Secp256k1(Secp256k1PublicKey::from_bytes(bytes).map_err(invalid)?),
+            SignatureScheme::Secp256r1 => Self::Secp256r1(Secp256r1PublicKey::from_bytes(bytes).map_err(invalid)?),
+        })
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            Self::Ed25519(_) => SignatureScheme::Ed25519,
+            Self::Secp256k1(_) => SignatureScheme::Secp256k1,
+            Self::Secp256r1(_) => SignatureScheme::Secp256r1,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Ed25519(pk) => pk.as_bytes(),
+            Self::Secp256k1(pk) => pk.as_bytes(),
+            Self::Secp256r1(pk) => pk.as_bytes(),
+        }
+    }
+
+    /// Verify a raw signature over `msg`.
+    pub fn verify(&self, msg: &[u8], signature: &[u8]) -> Result<(), EnclaveError> {
+        let invalid = |e| EnclaveError::GenericError(format!("Invalid signature: {e}"));
+        let verified = match self {
+            Self::Ed25519(pk) => pk.verify(msg, &Ed25519Signature::from_bytes(signature).map_err(invalid)?),
+            Self::Secp256k1(pk) => pk.verify(msg, &Secp256k1Signature::from_bytes(signature).map_err(invalid)?),
+            Self::Secp256r1(pk) => pk.verify(msg, &Secp256r1Signature::from_bytes(signature).map_err(invalid)?),
+        };
+        verified.map_err(|_| EnclaveError::GenericError(format!("Bad {:?} signature", self.scheme())))
     }
 }
 
 /// ==== HEALTHCHECK, GET ATTESTASTION ENDPOINT IMPL ====
+/// Largest nonce or user data the NSM binds into a document.
+pub const MAX_ATTESTATION_FIELD_LEN: usize = 512;
+
+/// Query of get attestation.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GetAttestationParams {
+    /// Hex nonce chosen by the verifier, bound into the document for freshness.
+    pub nonce: Option<String>,
+}
+
+impl GetAttestationParams {
+    pub fn nonce_bytes(&self) -> Result<Option<Vec<u8>>, EnclaveError> {
+        let Some(nonce) = &self.nonce else {
+            return Ok(None);
+        };
+        let bytes = Hex::decode(nonce)
+            .map_err(|e| EnclaveError::GenericError(format!("Invalid nonce hex: {e}")))?;
+        if bytes.len() > MAX_ATTESTATION_FIELD_LEN {
+            return Err(EnclaveError::GenericError(format!(
+                "Nonce exceeds {MAX_ATTESTATION_FIELD_LEN} bytes"
+            )));
+        }
+        Ok(Some(bytes))
+    }
+}
+
+/// App data bound into the document as its BCS encoded user data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationUserData {
+    /// App the enclave serves.
+    pub app_id: String,
+    /// Build version of the server.
+    pub version: String,
+    /// Hash of the configuration the app runs with, empty if it has none.
+    pub config_hash: Vec<u8>,
+}
+
+impl AttestationUserData {
+    pub fn new(app_id: &str, config_hash: Vec<u8>) -> Self {
+        Self {
+            app_id: app_id.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash,
+        }
+    }
+}
+
 /// Response for get attestation.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetAttestationResponse {
     /// Attestation document serialized in Hex.
     pub attestation: String,
+    /// Scheme of the public key bound in the document.
+    pub scheme: SignatureScheme,
+    /// Hex nonce bound in the document, if one was given.
+    pub nonce: Option<String>,
+    /// User data bound in the document.
+    pub user_data: AttestationUserData,
 }
 
 /// Endpoint that returns an attestation committed
-/// to the enclave's public key.
+/// to the enclave's public key, the caller's nonce and the app's user data.
 pub async fn get_attestation(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<GetAttestationParams>,
 ) -> Result<Json<GetAttestationResponse>, EnclaveError> {
+    let user_data = AttestationUserData::new("nautilus", Vec::new());
+    Ok(Json(attest(&state, params.nonce_bytes()?, user_data)?))
+}
+
+/// Request an attestation document from the NSM binding the enclave's public
+/// key, `nonce` and `user_data`.
+pub fn attest(
+    state: &AppState,
+    nonce: Option<Vec<u8>>,
+    user_data: AttestationUserData,
+) -> Result<GetAttestationResponse, EnclaveError> {
     info!("get attestation called");
 
-    let pk = state.eph_kp.public();
-    let fd = driver::nsm_init();
+    let pk = state.eph_kp.public_key_bytes();
+    let user_data_bytes = bcs::to_bytes(&user_data).expect("should not fail");
+    if user_data_bytes.len() > MAX_ATTESTATION_FIELD_LEN {
+        return Err(EnclaveError::GenericError(format!(
+            "User data exceeds {MAX_ATTESTATION_FIELD_LEN} bytes"
+        )));
+    }
+    let document = state.nsm.attest(pk, Some(user_data_bytes), nonce.clone())?;
+    Ok(GetAttestationResponse {
+        attestation: Hex::encode(document),
+        scheme: state.eph_kp.scheme(),
+        nonce: nonce.map(Hex::encode),
+        user_data,
+    })
+}
 
-    // Send attestation request to NSM driver with public key set.
-    let request = NsmRequest::Attestation {
-        user_data: None,
-        nonce: None,
-        public_key: Some(ByteBuf::from(pk.as_bytes().to_vec())),
-    };
+/// Request to decode an attestation document.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DecodeAttestationRequest {
+    /// Attestation document serialized in Hex, as returned by get attestation.
+    pub attestation: String,
+    /// Hex nonce the document must bind, if any.
+    pub nonce: Option<String>,
+}
 
-    let response = driver::nsm_process_request(fd, request);
-    match response {
-        NsmResponse::Attestation { document } => {
-            driver::nsm_exit(fd);
-            Ok(Json(GetAttestationResponse {
-                attestation: Hex::encode(document),
-            }))
-        }
-        _ => {
-            driver::nsm_exit(fd);
-            Err(EnclaveError::GenericError(
-                "unexpected response".to_string(),
-            ))
+/// Endpoint that verifies an attestation document against the root of the
+/// server's NSM (the AWS Nitro root, unless it runs a mock) at the current time
+/// and returns its fields.
+pub async fn decode_attestation(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<DecodeAttestationRequest>,
+) -> Result<Json<DecodedAttestation>, EnclaveError> {
+    let bytes = Hex::decode(&request.attestation)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid attestation hex: {e}")))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64;
+    let document = attestation::verify(&bytes, &state.nsm.root_sha256(), now)?;
+    let decoded = DecodedAttestation::from(&document);
+    if let Some(nonce) = request.nonce {
+        if decoded.nonce.as_deref() != Some(nonce.to_lowercase().as_str()) {
+            return Err(EnclaveError::GenericError(
+                "Attestation does not bind the nonce".to_string(),
+            ));
         }
     }
+    Ok(Json(decoded))
 }
 
 /// Health check response.
@@ -117,6 +513,8 @@ pub async fn get_attestation(
 pub struct HealthCheckResponse {
     /// Hex encoded public key booted on enclave.
     pub pk: String,
+    /// Scheme of the public key.
+    pub scheme: SignatureScheme,
     /// Status of endpoint connectivity checks
     pub endpoints_status: HashMap<String, bool>,
 }
@@ -126,7 +524,7 @@ pub struct HealthCheckResponse {
 pub async fn health_check(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<HealthCheckResponse>, EnclaveError> {
-    let pk = state.eph_kp.public();
+    let pk = state.eph_kp.public_key_bytes();
 
     // Create HTTP client with timeout
     let client = Client::builder()
@@ -202,7 +600,51 @@ pub async fn health_check(
     };
 
     Ok(Json(HealthCheckResponse {
-        pk: Hex::encode(pk.as_bytes()),
+        pk: Hex::encode(pk),
+        scheme: state.eph_kp.scheme(),
         endpoints_status,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_scheme_signs_and_verifies() {
+        for scheme in [SignatureScheme::Ed25519, SignatureScheme::Secp256k1, SignatureScheme::Secp256r1] {
+            let kp = EnclaveKeyPair::generate(scheme);
+            let signed = to_signed_response(&kp, 42u64, 1744038900000, 0);
+            assert_eq!(signed.scheme, scheme);
+
+            let msg = bcs::to_bytes(&signed.response).unwrap();
+            let sig = Hex::decode(&signed.signature).unwrap();
+            let pk = EnclavePublicKey::from_bytes(scheme, &kp.public_key_bytes()).unwrap();
+            assert_eq!(pk, kp.public());
+            pk.verify(&msg, &sig).unwrap();
+            assert!(pk.verify(b"other message", &sig).is_err());
+            verify_signed_response(&pk, &signed).unwrap();
+
+            let mut tampered = signed.clone();
+            tampered.response.data += 1;
+            assert!(verify_signed_response(&pk, &tampered).is_err());
+            let other = EnclaveKeyPair::generate(scheme).public();
+            assert!(verify_signed_response(&other, &signed).is_err());
+        }
+        assert_eq!("Secp256K1".parse::<SignatureScheme>().unwrap(), SignatureScheme::Secp256k1);
+        assert!("rsa".parse::<SignatureScheme>().is_err());
+    }
+
+    #[test]
+    fn test_attestation_params() {
+        let params = |nonce: &str| GetAttestationParams { nonce: Some(nonce.to_string()) };
+        assert_eq!(GetAttestationParams::default().nonce_bytes().unwrap(), None);
+        assert_eq!(params("0a0b").nonce_bytes().unwrap(), Some(vec![10, 11]));
+        assert!(params("xyz").nonce_bytes().is_err());
+        assert!(params(&"00".repeat(MAX_ATTESTATION_FIELD_LEN + 1)).nonce_bytes().is_err());
+
+        let user_data = AttestationUserData::new("pm", vec![7; 32]);
+        let bytes = bcs::to_bytes(&user_data).unwrap();
+        assert_eq!(bcs::from_bytes::<AttestationUserData>(&bytes).unwrap(), user_data);
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Domain-separated intent messages.
+//!
+//! `IntentMessage` identifies what a signature is for by a one byte scope, and
+//! every app numbers its scopes from 0, so two apps sharing a key sign
+//! messages that only their payloads tell apart. A `DomainIntentMessage` opens
+//! with an `IntentHeader` instead: a fixed domain tag, the header version, the
+//! app id, the Sui chain id and the app's scope. Its BCS encoding matches
+//! `enclave::DomainIntentMessage` in Move, see the vectors in the tests.
+//!
+//! `SCOPE_REGISTRY` lists every app and scope; a header can only be built for a
+//! registered pair, and the registry is checked for duplicates at compile time.
+//! The domain tag is 15 bytes, so its BCS length prefix is 15, a value no
+//! registered scope takes: a domain message never reads as a legacy
+//! `IntentMessage` of a registered scope.
+//!
+//! Only the server's key handoffs and the pm app sign domain messages. The
+//! example apps still sign legacy `IntentMessage`s, which their contracts verify,
+//! each with the key of its own build; they register here when they move over.
+
+use crate::common::{keys_to_check, EnclavePublicKey, IntentSigner, ProcessedDataResponse, VerifyResponse};
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Tag every domain message opens with
+pub const INTENT_DOMAIN: &[u8] = b"nautilus-intent";
+/// Version of `IntentHeader`
+pub const INTENT_VERSION: u8 = 1;
+
+/// App ids of the registry (1 to 4 are kept for the example apps)
+pub const APP_NAUTILUS: u16 = 0; // Messages of the server itself
+pub const APP_PM: u16 = 5;
+
+/// A registered scope of an app
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeEntry {
+    pub app_id: u16,
+    pub app: &'static str,
+    pub scope: u8,
+    pub name: &'static str,
+}
+
+const fn entry(app_id: u16, app: &'static str, scope: u8, name: &'static str) -> ScopeEntry {
+    ScopeEntry { app_id, app, scope, name }
+}
+
+/// Every app and scope that signs domain messages. Add an app's scopes here
+/// before it moves over; scopes match the apps' `IntentScope` values.
+pub const SCOPE_REGISTRY: &[ScopeEntry] = &[
+    entry(APP_NAUTILUS, "nautilus", crate::rotation::INTENT_KEY_HANDOFF, "KeyHandoff"),
+    entry(APP_PM, "pm", 0, "PlaceBet"),
+    entry(APP_PM, "pm", 1, "Resolve"),
+    entry(APP_PM, "pm", 2, "PayoutBatch"),
+    entry(APP_PM, "pm", 3, "LedgerHead"),
+    entry(APP_PM, "pm", 4, "PositionsRoot"),
+    entry(APP_PM, "pm", 5, "BatchRoot"),
+];
+
+const _: () = check_registry(SCOPE_REGISTRY);
+
+const fn same_str(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Fail the build on a scope registered twice, an app id with two names, or a
+/// scope that would read as the domain tag
+const fn check_registry(registry: &[ScopeEntry]) {
+    let mut i = 0;
+    while i < registry.len() {
+        assert!(registry[i].scope as usize != INTENT_DOMAIN.len(), "scope collides with the domain tag");
+        let mut j = i + 1;
+        while j < registry.len() {
+            let (a, b) = (&registry[i], &registry[j]);
+            assert!(a.app_id != b.app_id || a.scope != b.scope, "scope registered twice");
+            assert!((a.app_id == b.app_id) == same_str(a.app, b.app), "app id and name disagree");
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// The registry entry of `scope` of `app_id`
+pub fn lookup(app_id: u16, scope: u8) -> Option<&'static ScopeEntry> {
+    SCOPE_REGISTRY.iter().find(|entry| entry.app_id == app_id && entry.scope == scope)
+}
+
+/// Sui chain identifier: the first 4 bytes of the genesis checkpoint digest
+pub type ChainId = [u8; 4];
+
+pub const SUI_MAINNET: ChainId = [0x35, 0x83, 0x4a, 0x8a];
+pub const SUI_TESTNET: ChainId = [0x4c, 0x78, 0xad, 0xac];
+
+/// Parse a chain id: `mainnet`, `testnet` or 4 bytes in Hex
+pub fn parse_chain_id(id: &str) -> Result<ChainId, EnclaveError> {
+    match id {
+        "mainnet" => Ok(SUI_MAINNET),
+        "testnet" => Ok(SUI_TESTNET),
+        _ => Hex::decode(id)
+            .ok()
+            .and_then(|bytes| ChainId::try_from(bytes).ok())
+            .ok_or_else(|| EnclaveError::GenericError(format!("Invalid chain id {id}"))),
+    }
+}
+
+/// Chain id from `SUI_CHAIN_ID`, see `parse_chain_id`
+pub fn chain_id_from_env() -> Result<Option<ChainId>, EnclaveError> {
+    std::env::var("SUI_CHAIN_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+        .map(|id| parse_chain_id(&id))
+        .transpose()
+}
+
+/// Header of a domain message - Match Move enclave::IntentHeader
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntentHeader {
+    pub domain: Vec<u8>,
+    pub version: u8,
+    pub app_id: u16,
+    pub chain_id: Vec<u8>,
+    pub scope: u8,
+}
+
+impl IntentHeader {
+    /// Header for `scope` of `app_id` on `chain_id`; the pair must be registered
+    pub fn new(app_id: u16, scope: u8, chain_id: ChainId) -> Result<Self, EnclaveError> {
+        lookup(app_id, scope)
+            .ok_or_else(|| EnclaveError::GenericError(format!("Scope {scope} of app {app_id} is not registered")))?;
+        Ok(Self {
+            domain: INTENT_DOMAIN.to_vec(),
+            version: INTENT_VERSION,
+            app_id,
+            chain_id: chain_id.to_vec(),
+            scope,
+        })
+    }
+
+    /// Check the header is of this version and a registered scope
+    pub fn validate(&self) -> Result<&'static ScopeEntry, EnclaveError> {
+        if self.domain != INTENT_DOMAIN || self.version != INTENT_VERSION {
+            return Err(EnclaveError::GenericError(format!("Unsupported intent header version {}", self.version)));
+        }
+        lookup(self.app_id, self.scope).ok_or_else(|| {
+            EnclaveError::GenericError(format!("Scope {} of app {} is not registered", self.scope, self.app_id))
+        })
+    }
+}
+
+/// Domain message wrapping signed data - Match Move enclave::DomainIntentMessage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainIntentMessage<T: Serialize> {
+    pub header: IntentHeader,
+    pub timestamp_ms: u64,
+    pub data: T,
+}
+
+/// Sign `payload` under `header`
+pub fn to_signed_domain_response<T: Serialize + Clone, S: IntentSigner + ?Sized>(
+    kp: &S,
+    header: IntentHeader,
+    payload: T,
+    timestamp_ms: u64,
+) -> ProcessedDataResponse<DomainIntentMessage<T>> {
+    let message = DomainIntentMessage { header, timestamp_ms, data: payload };
+    let sig = kp.sign_bytes(&bcs::to_bytes(&message).expect("should not fail"));
+    ProcessedDataResponse {
+        response: message,
+        signature: Hex::encode(sig),
+        scheme: kp.scheme(),
+    }
+}
+
+/// Check that `signed` was signed by `pk` under a supported, registered header
+/// for `app_id` on `chain_id`
+pub fn verify_signed_domain_response<T: Serialize>(
+    pk: &EnclavePublicKey,
+    signed: &ProcessedDataResponse<DomainIntentMessage<T>>,
+    app_id: u16,
+    chain_id: ChainId,
+) -> Result<&'static ScopeEntry, EnclaveError> {
+    let header = &signed.response.header;
+    let entry = header.validate()?;
+    if header.app_id != app_id || header.chain_id != chain_id {
+        return Err(EnclaveError::GenericError(format!(
+            "Signed for app {} on chain {}",
+            header.app_id,
+            Hex::encode(&header.chain_id)
+        )));
+    }
+    if signed.scheme != pk.scheme() {
+        return Err(EnclaveError::GenericError(format!("Signed with {:?}, key is {:?}", signed.scheme, pk.scheme())));
+    }
+    let sig = Hex::decode(&signed.signature)
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid signature hex: {e}")))?;
+    pk.verify(&bcs::to_bytes(&signed.response).expect("should not fail"), &sig)?;
+    Ok(entry)
+}
+
+/// Request to check a signed domain response as returned by the enclave (JSON)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DomainVerifyRequest {
+    pub signed: ProcessedDataResponse<DomainIntentMessage<serde_json::Value>>,
+    /// Hex public key to check against, of the response's scheme. Defaults to
+    /// this enclave's keys, current and recently retired.
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+impl DomainVerifyRequest {
+    /// Keys to check against: the provided one, or `enclave_keys`.
+    pub fn public_keys(&self, enclave_keys: &[EnclavePublicKey]) -> Result<Vec<EnclavePublicKey>, EnclaveError> {
+        keys_to_check(self.public_key.as_deref(), self.signed.scheme, enclave_keys)
+    }
+
+    /// Check the signed response for `app_id` on `chain_id` with its data read
+    /// as `T`, the payload type of its scope. Fails only if the data is not a `T`.
+    pub fn verify_as<T: Serialize + DeserializeOwned>(
+        &self,
+        pk: &EnclavePublicKey,
+        enclave_keys: &[EnclavePublicKey],
+        app_id: u16,
+        chain_id: ChainId,
+    ) -> Result<VerifyResponse, EnclaveError> {
+        let message = &self.signed.response;
+        let data: T = serde_json::from_value(message.data.clone()).map_err(|e| {
+            EnclaveError::GenericError(format!("Data does not match scope {}: {e}", message.header.scope))
+        })?;
+        let typed = ProcessedDataResponse {
+            response: DomainIntentMessage { header: message.header.clone(), timestamp_ms: message.timestamp_ms, data },
+            signature: self.signed.signature.clone(),
+            scheme: self.signed.scheme,
+        };
+        let result = verify_signed_domain_response(pk, &typed, app_id, chain_id);
+        Ok(VerifyResponse {
+            valid: result.is_ok(),
+            intent: message.header.scope,
+            scheme: self.signed.scheme,
+            public_key: Hex::encode(pk.as_bytes()),
+            enclave_key: enclave_keys.contains(pk),
+            error: result.err().map(|e| e.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{EnclaveKeyPair, IntentMessage, SignatureScheme};
+
+    #[derive(Clone, Serialize)]
+    struct SigningPayload {
+        location: String,
+        temperature: u64,
+    }
+
+    fn payload() -> SigningPayload {
+        SigningPayload { location: "San Francisco".to_string(), temperature: 13 }
+    }
+
+    #[test]
+    fn test_domain_serde() {
+        // Vectors of `test_domain_serde` in `move/enclave/sources/enclave.move`
+        let message = DomainIntentMessage {
+            header: IntentHeader {
+                domain: INTENT_DOMAIN.to_vec(),
+                version: INTENT_VERSION,
+                app_id: 1,
+                chain_id: SUI_TESTNET.to_vec(),
+                scope: 0,
+            },
+            timestamp_ms: 1744038900000,
+            data: payload(),
+        };
+        assert_eq!(
+            Hex::encode(bcs::to_bytes(&message).unwrap()),
+            "0f6e617574696c75732d696e74656e7401010004\
+             4c78adac0020b1d110960100000d53616e204672616e636973636f0d00000000000000"
+        );
+        let header = IntentHeader::new(APP_PM, 5, SUI_MAINNET).unwrap();
+        assert_eq!(
+            Hex::encode(bcs::to_bytes(&header).unwrap()),
+            "0f6e617574696c75732d696e74656e740105000435834a8a05"
+        );
+
+        // The same payload under the legacy message signs other bytes
+        let legacy = bcs::to_bytes(&IntentMessage::new(payload(), 1744038900000, 0)).unwrap();
+        assert_ne!(legacy, bcs::to_bytes(&message).unwrap());
+    }
+
+    #[test]
+    fn test_domain_signatures() {
+        let kp = EnclaveKeyPair::generate(SignatureScheme::Ed25519);
+        let header = IntentHeader::new(APP_PM, 0, SUI_TESTNET).unwrap();
+        let signed = to_signed_domain_response(&kp, header, payload(), 1);
+        let entry = verify_signed_domain_response(&kp.public(), &signed, APP_PM, SUI_TESTNET).unwrap();
+        assert_eq!((entry.app, entry.name), ("pm", "PlaceBet"));
+
+        // Same scope number, another app or chain
+        assert!(verify_signed_domain_response(&kp.public(), &signed, APP_NAUTILUS, SUI_TESTNET).is_err());
+        assert!(verify_signed_domain_response(&kp.public(), &signed, APP_PM, SUI_MAINNET).is_err());
+        let mut moved = signed.clone();
+        moved.response.header.app_id = APP_NAUTILUS;
+        assert!(verify_signed_domain_response(&kp.public(), &moved, APP_NAUTILUS, SUI_TESTNET).is_err());
+
+        // Unregistered apps and scopes, and other versions
+        assert!(IntentHeader::new(1, 0, SUI_TESTNET).is_err());
+        assert!(IntentHeader::new(APP_PM, 6, SUI_TESTNET).is_err());
+        let mut future = signed.clone();
+        future.response.header.version = 2;
+        assert!(verify_signed_domain_response(&kp.public(), &future, APP_PM, SUI_TESTNET).is_err());
+    }
+}
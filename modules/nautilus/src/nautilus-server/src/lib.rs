@@ -5,9 +5,12 @@ use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
-use fastcrypto::ed25519::Ed25519KeyPair;
+use nsm::Nsm;
+use repository::PositionRepository;
+use rotation::EphemeralKeys;
 use serde_json::json;
 use std::fmt;
+use std::sync::RwLock;
 
 mod apps {
     #[cfg(feature = "twitter-example")]
@@ -48,26 +51,47 @@ pub mod app {
     pub use crate::apps::pm::*;
 }
 
+pub mod attestation;
+pub mod batch;
 pub mod common;
+pub mod intent;
+pub mod merkle;
+pub mod nsm;
+pub mod repository;
+pub mod rotation;
+pub mod wallet;
+#[cfg(feature = "zklogin")]
+pub mod zklogin;
 
 /// App state, at minimum needs to maintain the ephemeral keypair.  
 pub struct AppState {
-    /// Ephemeral keypair on boot
-    pub eph_kp: Ed25519KeyPair,
+    /// Ephemeral keypair, of the scheme chosen by `ENCLAVE_SIGNATURE_SCHEME`, and
+    /// the keys it rotated out
+    pub eph_kp: EphemeralKeys,
     /// API key when querying api.weatherapi.com
     pub api_key: String,
+    /// Position storage for the prediction market apps
+    pub positions: RwLock<Box<dyn PositionRepository>>,
+    /// NSM attestations are requested from, chosen by `NSM_MODE`
+    pub nsm: Box<dyn Nsm>,
+    /// Market state of the PM app
+    #[cfg(feature = "pm")]
+    pub pm: app::PmState,
 }
 
 /// Implement IntoResponse for EnclaveError.
 impl IntoResponse for EnclaveError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            EnclaveError::GenericError(e) => (StatusCode::BAD_REQUEST, e),
+        let body = match self {
+            EnclaveError::GenericError(e) => json!({
+                "error": e,
+            }),
+            EnclaveError::Rejected { message, reason } => json!({
+                "error": message,
+                "reason": reason,
+            }),
         };
-        let body = Json(json!({
-            "error": error_message,
-        }));
-        (status, body).into_response()
+        (StatusCode::BAD_REQUEST, Json(body)).into_response()
     }
 }
 
@@ -75,12 +99,18 @@ impl IntoResponse for EnclaveError {
 #[derive(Debug)]
 pub enum EnclaveError {
     GenericError(String),
+    /// A request refused by policy, with a machine-readable reason
+    Rejected {
+        message: String,
+        reason: serde_json::Value,
+    },
 }
 
 impl fmt::Display for EnclaveError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             EnclaveError::GenericError(e) => write!(f, "{e}"),
+            EnclaveError::Rejected { message, .. } => write!(f, "{message}"),
         }
     }
 }
@@ -3,17 +3,39 @@
 
 use anyhow::Result;
 use axum::{routing::get, routing::post, Router};
-use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
-use nautilus_server::app::{process_data, resolve, get_positions_handler};
-use nautilus_server::common::{get_attestation, health_check};
+use nautilus_server::app::snapshot::{restore_snapshot, run_snapshot_loop, save_snapshot, SnapshotConfig};
+use nautilus_server::app::{
+    add_liquidity, apply_vault_event, check_signature_scheme, confirm_bet, get_attestation, get_balance, get_ledger, get_ledger_head, get_liquidity, get_payout_batch, get_pending_bets,
+    get_pool_head, get_portfolio, get_position_proof, get_positions_handler, process_data, process_data_batched, record_enclave_start, remove_liquidity, resolve, rotate_key, set_liquidity_schedule, set_pool_fees,
+    sync_pool, verify, PmSettings, PmState,
+};
+use nautilus_server::common::{decode_attestation, health_check, EnclaveKeyPair, SignatureScheme};
+use nautilus_server::nsm::NsmConfig;
+use nautilus_server::repository::{InMemoryPositionStore, PositionRepository};
+use nautilus_server::rotation::{get_keys, run_rotation_loop, EphemeralKeys, KeyRotationConfig};
 use nautilus_server::AppState;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let eph_kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
+    // NSM attestations come from, and in mock mode an optional seed for reproducible keys
+    let nsm_config = NsmConfig::from_env()?;
+    let scheme = SignatureScheme::from_env()?;
+    check_signature_scheme(scheme)?;
+    let eph_kp = match nsm_config.key_seed {
+        Some(seed) => EnclaveKeyPair::from_seed(scheme, seed),
+        None => EnclaveKeyPair::generate(scheme),
+    };
+    let rotation_config = KeyRotationConfig::from_env()?;
+    let eph_kp = match &rotation_config {
+        Some(config) => EphemeralKeys::new(eph_kp).with_overlap(config.overlap),
+        None => EphemeralKeys::new(eph_kp),
+    };
+
+    // JWKs zkLogin wallet signatures are checked against, from ZKLOGIN_JWKS_PATH
+    nautilus_server::zklogin::init_from_env()?;
 
     // This API_KEY value can be stored with secret-manager. To do that, follow the prompt `sh configure_enclave.sh`
     // Answer `y` to `Do you want to use a secret?` and finish. Otherwise, uncomment this code to use a hardcoded value.
@@ -26,7 +48,31 @@ async fn main() -> Result<()> {
     #[cfg(feature = "seal-example")]
     let api_key = String::new();
 
-    let state = Arc::new(AppState { eph_kp, api_key });
+    let state = Arc::new(AppState {
+        eph_kp,
+        api_key,
+        positions: RwLock::new(open_position_store()?),
+        nsm: nsm_config.open(),
+        pm: PmState::new(PmSettings::from_env()?),
+    });
+
+    // Restore market state sealed by a previous run, and keep sealing it periodically
+    let snapshot_config = SnapshotConfig::from_env()?.map(Arc::new);
+    match &snapshot_config {
+        Some(config) => {
+            if !restore_snapshot(&state, config)? {
+                info!("No snapshot found at {}, starting empty", config.path.display());
+            }
+            tokio::spawn(run_snapshot_loop(state.clone(), config.clone()));
+        }
+        None => info!("SNAPSHOT_KEY not set, market state will not be persisted"),
+    }
+    record_enclave_start(&state)?;
+
+    // Rotate the enclave key periodically, if configured
+    if let Some(config) = &rotation_config {
+        tokio::spawn(run_rotation_loop(state.clone(), config.interval, rotate_key));
+    }
 
     // Spawn host-only init server if seal-example feature is enabled
     #[cfg(feature = "seal-example")]
@@ -40,18 +86,75 @@ async fn main() -> Result<()> {
     let app = Router::new()
         .route("/", get(ping))
         .route("/get_attestation", get(get_attestation))
+        .route("/attestation/decoded", post(decode_attestation))
+        .route("/keys", get(get_keys))
         .route("/process_data", post(process_data))
+        .route("/process_data/batched", post(process_data_batched))
+        .route("/confirm", post(confirm_bet))
+        .route("/bets/pending", get(get_pending_bets))
+        .route("/pools/head", get(get_pool_head))
+        .route("/pools/sync", post(sync_pool))
+        .route("/pools/liquidity/add", post(add_liquidity))
+        .route("/pools/liquidity/remove", post(remove_liquidity))
+        .route("/pools/liquidity/schedule", post(set_liquidity_schedule))
+        .route("/pools/fees", post(set_pool_fees))
+        .route("/pools/liquidity", get(get_liquidity))
+        .route("/vault/event", post(apply_vault_event))
+        .route("/balance", get(get_balance))
         .route("/resolve", post(resolve))
+        .route("/resolve/batch", get(get_payout_batch))
+        .route("/ledger", get(get_ledger))
+        .route("/ledger/head", get(get_ledger_head))
         .route("/positions", get(get_positions_handler))
+        .route("/positions/proof", get(get_position_proof))
+        .route("/portfolio", get(get_portfolio))
+        .route("/verify", post(verify))
         .route("/health_check", get(health_check))
-        .with_state(state)
+        .with_state(state.clone())
         .layer(cors);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     info!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
         .await
-        .map_err(|e| anyhow::anyhow!("Server error: {e}"))
+        .map_err(|e| anyhow::anyhow!("Server error: {e}"))?;
+
+    // Final snapshot so nothing since the last periodic one is lost
+    if let Some(config) = &snapshot_config {
+        let head = save_snapshot(&state, config)?;
+        info!("Wrote shutdown snapshot {head} to {} (next SNAPSHOT_MIN_HEAD)", config.path.display());
+    }
+    Ok(())
+}
+
+/// Positions live in memory by default. Outside an enclave, build with `sled-store`
+/// and set POSITION_DB_PATH to keep them in an embedded on-disk database instead.
+fn open_position_store() -> Result<Box<dyn PositionRepository>> {
+    #[cfg(feature = "sled-store")]
+    if let Ok(path) = std::env::var("POSITION_DB_PATH") {
+        info!("Using on-disk position store at {path}");
+        return Ok(Box::new(nautilus_server::repository::SledPositionStore::open(path)?));
+    }
+    Ok(Box::new(InMemoryPositionStore::new()))
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 async fn ping() -> &'static str {
@@ -0,0 +1,158 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Binary SHA-256 Merkle tree with inclusion proofs.
+//!
+//! Leaves are hashed as `H(0x00 || data)` and inner nodes as `H(0x01 || left || right)`
+//! so a leaf can never be passed off as an inner node. A node without a sibling is
+//! carried up to the next level unchanged. The root of an empty tree is all zeros.
+
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Root of a tree with no leaves
+pub const EMPTY_ROOT: [u8; 32] = [0u8; 32];
+
+/// One step of an inclusion proof: the sibling hash and which side it sits on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    /// Hex encoded sibling hash
+    pub sibling: String,
+    pub sibling_on_left: bool,
+}
+
+/// Inclusion proof for the leaf at `leaf_index`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub leaf_index: u64,
+    pub steps: Vec<ProofStep>,
+}
+
+pub fn hash_leaf(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().digest
+}
+
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::default();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().digest
+}
+
+/// Merkle tree keeping every level, so proofs can be produced for any leaf
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build a tree over raw leaf data (each item is hashed with the leaf prefix)
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T]) -> Self {
+        let mut levels = vec![leaves.iter().map(|l| hash_leaf(l.as_ref())).collect::<Vec<_>>()];
+        while levels.last().expect("at least one level").len() > 1 {
+            let next = levels
+                .last()
+                .expect("at least one level")
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or(EMPTY_ROOT)
+    }
+
+    /// Inclusion proof for the leaf at `index`, or `None` if out of range
+    pub fn proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut steps = Vec::new();
+        let mut i = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = i ^ 1;
+            if sibling < level.len() {
+                steps.push(ProofStep {
+                    sibling: Hex::encode(level[sibling]),
+                    sibling_on_left: sibling < i,
+                });
+            }
+            i /= 2;
+        }
+        Some(MerkleProof {
+            leaf_index: index as u64,
+            steps,
+        })
+    }
+}
+
+/// Check that `leaf` is included under `root` according to `proof`
+pub fn verify_proof(root: &[u8], leaf: &[u8], proof: &MerkleProof) -> bool {
+    let mut current = hash_leaf(leaf);
+    for step in &proof.steps {
+        let sibling: [u8; 32] = match Hex::decode(&step.sibling).ok().and_then(|b| b.try_into().ok()) {
+            Some(sibling) => sibling,
+            None => return false,
+        };
+        current = if step.sibling_on_left {
+            hash_node(&sibling, &current)
+        } else {
+            hash_node(&current, &sibling)
+        };
+    }
+    current.as_slice() == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proofs_verify_for_every_leaf() {
+        for n in 1..=9 {
+            let leaves: Vec<Vec<u8>> = (0..n).map(|i| vec![i as u8; 4]).collect();
+            let tree = MerkleTree::new(&leaves);
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(verify_proof(&tree.root(), leaf, &proof), "n={n} i={i}");
+            }
+            assert!(tree.proof(n).is_none());
+        }
+    }
+
+    #[test]
+    fn test_proof_rejects_wrong_leaf() {
+        let leaves = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+        let tree = MerkleTree::new(&leaves);
+        let proof = tree.proof(1).unwrap();
+        assert!(!verify_proof(&tree.root(), b"c", &proof));
+        assert_eq!(MerkleTree::new::<Vec<u8>>(&[]).root(), EMPTY_ROOT);
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Nitro Secure Module access.
+//!
+//! Attestations come from an `Nsm`: `NitroNsm` asks the NSM driver of the
+//! enclave, `MockNsm` signs documents of the same shape in software, with PCRs of
+//! its own, under a self-signed root. Mock documents verify with
+//! `attestation::verify` against the mock's root only, so clients pinning the
+//! AWS root refuse them.
+//!
+//! `NSM_MODE` picks the module at startup: `nitro` (the default) or `mock`. In
+//! mock mode, `MOCK_PCRS` sets PCRs as comma separated `index=hex` pairs (the
+//! others are zero, as in a debug enclave), and `ENCLAVE_KEY_SEED`, 32 bytes in
+//! Hex, seeds the enclave key and the mock's certificates so local runs are
+//! reproducible. The seed is refused in nitro mode.
+
+use crate::attestation::{self, AttestationDocument, AWS_NITRO_ROOT_SHA256};
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use nsm_api::api::{Request as NsmRequest, Response as NsmResponse};
+use nsm_api::driver;
+use p384::ecdsa::{DerSignature, SigningKey};
+use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::der::asn1::UtcTime;
+use x509_cert::der::Encode;
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::{Time, Validity};
+
+const PCR_COUNT: u8 = 16;
+const PCR_LEN: usize = 48; // SHA-384
+const MOCK_MODULE_ID: &str = "i-00000000000000000-enc0000000000000000";
+const MOCK_CERT_VALIDITY: (u64, u64) = (1_577_836_800, 2_524_521_600); // 2020-01-01 to 2049-12-31
+
+/// Source of attestation documents
+pub trait Nsm: Send + Sync {
+    /// Attestation document binding `public_key`, `user_data` and `nonce`
+    fn attest(
+        &self,
+        public_key: Vec<u8>,
+        user_data: Option<Vec<u8>>,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, EnclaveError>;
+
+    /// SHA-256 of the root certificate the documents chain to
+    fn root_sha256(&self) -> [u8; 32];
+}
+
+/// The enclave's NSM, through its driver
+pub struct NitroNsm;
+
+impl Nsm for NitroNsm {
+    fn attest(
+        &self,
+        public_key: Vec<u8>,
+        user_data: Option<Vec<u8>>,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, EnclaveError> {
+        let fd = driver::nsm_init();
+        let request = NsmRequest::Attestation {
+            user_data: user_data.map(ByteBuf::from),
+            nonce: nonce.map(ByteBuf::from),
+            public_key: Some(ByteBuf::from(public_key)),
+        };
+        let response = driver::nsm_process_request(fd, request);
+        driver::nsm_exit(fd);
+        match response {
+            NsmResponse::Attestation { document } => Ok(document),
+            _ => Err(EnclaveError::GenericError("unexpected response".to_string())),
+        }
+    }
+
+    fn root_sha256(&self) -> [u8; 32] {
+        AWS_NITRO_ROOT_SHA256
+    }
+}
+
+/// Software NSM signing documents under its own root and instance certificates
+pub struct MockNsm {
+    pcrs: BTreeMap<u8, ByteBuf>,
+    root: Vec<u8>, // DER
+    leaf: Vec<u8>, // DER
+    leaf_key: SigningKey,
+}
+
+impl MockNsm {
+    /// Mock with `pcrs` set over zero PCRs, and certificates from `rng`
+    pub fn new<R: CryptoRng + RngCore>(pcrs: BTreeMap<u8, Vec<u8>>, rng: &mut R) -> Self {
+        let root_key = SigningKey::random(rng);
+        let leaf_key = SigningKey::random(rng);
+        let root_name = Name::from_str("CN=mock.nitro-enclaves,O=Nautilus").expect("should not fail");
+        let leaf_name = Name::from_str(&format!("CN={MOCK_MODULE_ID},O=Nautilus")).expect("should not fail");
+        let root = certificate(Profile::Root, 1, root_name.clone(), &root_key, &root_key);
+        let leaf = certificate(
+            Profile::Leaf {
+                issuer: root_name,
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            2,
+            leaf_name,
+            &leaf_key,
+            &root_key,
+        );
+
+        let mut all_pcrs: BTreeMap<u8, ByteBuf> =
+            (0..PCR_COUNT).map(|index| (index, ByteBuf::from(vec![0; PCR_LEN]))).collect();
+        all_pcrs.extend(pcrs.into_iter().map(|(index, pcr)| (index, ByteBuf::from(pcr))));
+        Self { pcrs: all_pcrs, root, leaf, leaf_key }
+    }
+
+    /// Mock whose certificates derive from `seed`
+    pub fn from_seed(pcrs: BTreeMap<u8, Vec<u8>>, seed: [u8; 32]) -> Self {
+        let mut input = b"nautilus-mock-nsm".to_vec();
+        input.extend_from_slice(&seed);
+        Self::new(pcrs, &mut StdRng::from_seed(Sha256::digest(input).digest))
+    }
+
+    /// DER encoded root certificate
+    pub fn root(&self) -> &[u8] {
+        &self.root
+    }
+}
+
+fn certificate(profile: Profile, serial: u32, subject: Name, key: &SigningKey, issuer_key: &SigningKey) -> Vec<u8> {
+    let time = |secs| Time::UtcTime(UtcTime::from_unix_duration(Duration::from_secs(secs)).expect("should not fail"));
+    let validity = Validity {
+        not_before: time(MOCK_CERT_VALIDITY.0),
+        not_after: time(MOCK_CERT_VALIDITY.1),
+    };
+    let spki = SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).expect("should not fail");
+    CertificateBuilder::new(profile, SerialNumber::from(serial), validity, subject, spki, issuer_key)
+        .and_then(|builder| builder.build::<DerSignature>())
+        .and_then(|cert| cert.to_der().map_err(Into::into))
+        .expect("should not fail")
+}
+
+impl Nsm for MockNsm {
+    fn attest(
+        &self,
+        public_key: Vec<u8>,
+        user_data: Option<Vec<u8>>,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, EnclaveError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+            .as_millis() as u64;
+        let document = AttestationDocument {
+            module_id: MOCK_MODULE_ID.to_string(),
+            digest: "SHA384".to_string(),
+            timestamp,
+            pcrs: self.pcrs.clone(),
+            certificate: ByteBuf::from(self.leaf.clone()),
+            cabundle: vec![ByteBuf::from(self.root.clone())],
+            public_key: Some(ByteBuf::from(public_key)),
+            user_data: user_data.map(ByteBuf::from),
+            nonce: nonce.map(ByteBuf::from),
+        };
+        Ok(attestation::sign(&document, &self.leaf_key))
+    }
+
+    fn root_sha256(&self) -> [u8; 32] {
+        Sha256::digest(&self.root).digest
+    }
+}
+
+/// Which NSM the server runs with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NsmMode {
+    Nitro,
+    Mock,
+}
+
+/// NSM settings from the environment
+#[derive(Debug, Clone)]
+pub struct NsmConfig {
+    pub mode: NsmMode,
+    pub pcrs: BTreeMap<u8, Vec<u8>>,
+    pub key_seed: Option<[u8; 32]>,
+}
+
+impl NsmConfig {
+    pub fn from_env() -> Result<Self, EnclaveError> {
+        let var = |name| std::env::var(name).ok().filter(|value: &String| !value.is_empty());
+        let mode = match var("NSM_MODE").as_deref() {
+            None | Some("nitro") => NsmMode::Nitro,
+            Some("mock") => NsmMode::Mock,
+            Some(mode) => return Err(EnclaveError::GenericError(format!("Unknown NSM_MODE {mode}"))),
+        };
+        let pcrs = var("MOCK_PCRS").map(|pcrs| parse_pcrs(&pcrs)).transpose()?.unwrap_or_default();
+        let key_seed = var("ENCLAVE_KEY_SEED")
+            .map(|seed| {
+                Hex::decode(&seed)
+                    .ok()
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .ok_or_else(|| EnclaveError::GenericError("ENCLAVE_KEY_SEED must be 32 bytes in Hex".into()))
+            })
+            .transpose()?;
+        let config = Self { mode, pcrs, key_seed };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), EnclaveError> {
+        if self.mode == NsmMode::Nitro && (self.key_seed.is_some() || !self.pcrs.is_empty()) {
+            return Err(EnclaveError::GenericError(
+                "ENCLAVE_KEY_SEED and MOCK_PCRS are only allowed with NSM_MODE=mock".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The NSM of this configuration
+    pub fn open(&self) -> Box<dyn Nsm> {
+        match self.mode {
+            NsmMode::Nitro => Box::new(NitroNsm),
+            NsmMode::Mock => {
+                let nsm = match self.key_seed {
+                    Some(seed) => MockNsm::from_seed(self.pcrs.clone(), seed),
+                    None => MockNsm::new(self.pcrs.clone(), &mut rand::thread_rng()),
+                };
+                info!("Using a mock NSM, root certificate {}", Hex::encode(nsm.root()));
+                Box::new(nsm)
+            }
+        }
+    }
+}
+
+fn parse_pcrs(pcrs: &str) -> Result<BTreeMap<u8, Vec<u8>>, EnclaveError> {
+    let invalid = |pcr: &str| EnclaveError::GenericError(format!("Invalid MOCK_PCRS entry {pcr}"));
+    pcrs.split(',')
+        .map(|pcr| {
+            let (index, value) = pcr.trim().split_once('=').ok_or_else(|| invalid(pcr))?;
+            let index: u8 = index.parse().map_err(|_| invalid(pcr))?;
+            let value = Hex::decode(value).map_err(|_| invalid(pcr))?;
+            if index >= PCR_COUNT || value.len() != PCR_LEN {
+                return Err(invalid(pcr));
+            }
+            Ok((index, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attestation::DecodedAttestation;
+    use crate::common::{EnclaveKeyPair, IntentSigner, SignatureScheme};
+
+    fn now_ms() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+
+    #[test]
+    fn test_mock_attestation() {
+        let pcrs = parse_pcrs(&format!("0={},2={}", "ab".repeat(48), "cd".repeat(48))).unwrap();
+        let nsm = MockNsm::from_seed(pcrs, [7; 32]);
+        let document = nsm.attest(vec![0x42; 32], Some(b"data".to_vec()), Some(b"nonce".to_vec())).unwrap();
+
+        let decoded = DecodedAttestation::from(&attestation::verify(&document, &nsm.root_sha256(), now_ms()).unwrap());
+        assert_eq!(decoded.pcrs.len(), 16);
+        assert_eq!(decoded.pcrs[&0], "ab".repeat(48));
+        assert_eq!(decoded.pcrs[&1], "00".repeat(48));
+        assert_eq!(decoded.pcrs[&2], "cd".repeat(48));
+        assert_eq!(decoded.public_key, Some("42".repeat(32)));
+        assert_eq!(decoded.nonce, Some(Hex::encode(b"nonce")));
+
+        // Not an AWS document, and the same seed gives the same root
+        assert!(attestation::verify(&document, &AWS_NITRO_ROOT_SHA256, now_ms()).is_err());
+        assert_eq!(MockNsm::from_seed(BTreeMap::new(), [7; 32]).root(), nsm.root());
+        assert_ne!(MockNsm::from_seed(BTreeMap::new(), [8; 32]).root(), nsm.root());
+    }
+
+    #[test]
+    fn test_nsm_config() {
+        assert!(parse_pcrs(&format!("16={}", "00".repeat(48))).is_err());
+        assert!(parse_pcrs("0=abcd").is_err());
+        assert!(parse_pcrs("0").is_err());
+
+        let seeded = NsmConfig { mode: NsmMode::Nitro, pcrs: BTreeMap::new(), key_seed: Some([1; 32]) };
+        assert!(seeded.validate().is_err());
+        assert!(NsmConfig { mode: NsmMode::Mock, ..seeded }.validate().is_ok());
+
+        // A seed gives the same enclave key on every run
+        let key = |seed| EnclaveKeyPair::from_seed(SignatureScheme::Secp256r1, seed).public_key_bytes();
+        assert_eq!(key([1; 32]), key([1; 32]));
+        assert_ne!(key([1; 32]), key([2; 32]));
+    }
+}
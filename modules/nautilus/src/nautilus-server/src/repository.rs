@@ -0,0 +1,318 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Position storage abstraction shared by the prediction market apps.
+//!
+//! Handlers reach positions through the `PositionRepository` held in `AppState`
+//! instead of a module-level static, so tests can use an isolated store and
+//! deployments outside an enclave can persist positions to disk.
+//! `InMemoryPositionStore` (and the prediction-market `PositionStore`) keep
+//! positions in memory; `SledPositionStore` (behind the `sled-store` feature) is
+//! an embedded on-disk implementation.
+
+use crate::EnclaveError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A single user position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub wallet: String,
+    pub pool_id: u64,
+    pub outcome: u8,    // Which world (0-7)
+    pub shares: u64,    // Scaled by 1000
+    pub cost: u64,      // Total amount paid (scaled by 10^6)
+}
+
+/// Storage backend for user positions. Mutations take `&mut self`; `AppState`
+/// wraps the repository in a lock so a handler can hold it across related updates.
+pub trait PositionRepository: Send + Sync {
+    /// Record `shares` bought by `wallet` on `outcome` of `pool_id` for `cost`
+    fn add_position(&mut self, wallet: String, pool_id: u64, outcome: u8, shares: u64, cost: u64) -> Result<(), EnclaveError>;
+
+    /// Remove up to `shares` from a position, releasing cost basis pro rata
+    fn remove_shares(&mut self, wallet: &str, pool_id: u64, outcome: u8, shares: u64) -> Result<(), EnclaveError>;
+
+    /// All positions of a pool
+    fn get_positions_by_pool(&self, pool_id: u64) -> Result<Vec<Position>, EnclaveError>;
+
+    /// Positions of one wallet in a pool
+    fn get_user_positions(&self, wallet: &str, pool_id: u64) -> Result<Vec<Position>, EnclaveError> {
+        Ok(self
+            .get_positions_by_pool(pool_id)?
+            .into_iter()
+            .filter(|p| p.wallet == wallet)
+            .collect())
+    }
+
+    /// Positions of one wallet across all pools
+    fn get_wallet_positions(&self, wallet: &str) -> Result<Vec<Position>, EnclaveError> {
+        Ok(self
+            .get_all_positions()?
+            .into_iter()
+            .filter(|p| p.wallet == wallet)
+            .collect())
+    }
+
+    /// Positions of a pool on the winning outcome
+    fn get_winning_positions(&self, pool_id: u64, winning_outcome: u8) -> Result<Vec<Position>, EnclaveError> {
+        Ok(self
+            .get_positions_by_pool(pool_id)?
+            .into_iter()
+            .filter(|p| p.outcome == winning_outcome)
+            .collect())
+    }
+
+    /// Drop every position of a pool (after resolution)
+    fn clear_pool(&mut self, pool_id: u64) -> Result<(), EnclaveError>;
+
+    /// Every position in the store
+    fn get_all_positions(&self) -> Result<Vec<Position>, EnclaveError>;
+
+    /// Replace every position (used when restoring a snapshot)
+    fn restore(&mut self, positions: Vec<Position>) -> Result<(), EnclaveError>;
+}
+
+/// Cost basis left after removing `removed` of `held` shares
+pub fn remaining_cost(cost: u64, held: u64, removed: u64) -> u64 {
+    if removed >= held {
+        return 0;
+    }
+    cost - ((cost as u128 * removed as u128) / held as u128) as u64
+}
+
+/// In-memory store. Shares for the same (wallet, pool_id, outcome) are merged.
+#[derive(Debug, Default)]
+pub struct InMemoryPositionStore {
+    // Key: (wallet, pool_id, outcome) -> (shares, cost)
+    positions: HashMap<(String, u64, u8), (u64, u64)>,
+}
+
+impl InMemoryPositionStore {
+    pub fn new() -> Self {
+        Self {
+            positions: HashMap::new(),
+        }
+    }
+
+    fn to_positions<'a>(entries: impl Iterator<Item = (&'a (String, u64, u8), &'a (u64, u64))>) -> Vec<Position> {
+        entries
+            .map(|((wallet, pool_id, outcome), (shares, cost))| Position {
+                wallet: wallet.clone(),
+                pool_id: *pool_id,
+                outcome: *outcome,
+                shares: *shares,
+                cost: *cost,
+            })
+            .collect()
+    }
+}
+
+impl PositionRepository for InMemoryPositionStore {
+    fn add_position(&mut self, wallet: String, pool_id: u64, outcome: u8, shares: u64, cost: u64) -> Result<(), EnclaveError> {
+        let key = (wallet, pool_id, outcome);
+        let entry = self.positions.entry(key).or_insert((0, 0));
+        entry.0 += shares;
+        entry.1 += cost;
+        Ok(())
+    }
+
+    fn remove_shares(&mut self, wallet: &str, pool_id: u64, outcome: u8, shares: u64) -> Result<(), EnclaveError> {
+        let key = (wallet.to_string(), pool_id, outcome);
+        if let Some((held, cost)) = self.positions.get_mut(&key) {
+            *cost = remaining_cost(*cost, *held, shares);
+            *held = held.saturating_sub(shares);
+            if *held == 0 {
+                self.positions.remove(&key);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_positions_by_pool(&self, pool_id: u64) -> Result<Vec<Position>, EnclaveError> {
+        Ok(Self::to_positions(
+            self.positions.iter().filter(|((_, pid, _), _)| *pid == pool_id),
+        ))
+    }
+
+    fn clear_pool(&mut self, pool_id: u64) -> Result<(), EnclaveError> {
+        self.positions.retain(|(_, pid, _), _| *pid != pool_id);
+        Ok(())
+    }
+
+    fn get_all_positions(&self) -> Result<Vec<Position>, EnclaveError> {
+        Ok(Self::to_positions(self.positions.iter()))
+    }
+
+    fn restore(&mut self, positions: Vec<Position>) -> Result<(), EnclaveError> {
+        self.positions = positions
+            .into_iter()
+            .map(|p| ((p.wallet, p.pool_id, p.outcome), (p.shares, p.cost)))
+            .collect();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sled-store")]
+pub use sled_store::SledPositionStore;
+
+#[cfg(feature = "sled-store")]
+mod sled_store {
+    use super::{remaining_cost, Position, PositionRepository};
+    use crate::EnclaveError;
+    use std::path::Path;
+
+    /// Positions persisted in an embedded sled database.
+    /// Key: pool_id (big endian) || outcome || wallet, value: shares || cost (big endian).
+    /// Shares for the same key are merged.
+    pub struct SledPositionStore {
+        tree: sled::Tree,
+    }
+
+    fn db_err(e: sled::Error) -> EnclaveError {
+        EnclaveError::GenericError(format!("Position DB error: {e}"))
+    }
+
+    fn key(wallet: &str, pool_id: u64, outcome: u8) -> Vec<u8> {
+        let mut key = pool_id.to_be_bytes().to_vec();
+        key.push(outcome);
+        key.extend_from_slice(wallet.as_bytes());
+        key
+    }
+
+    fn encode_value(shares: u64, cost: u64) -> Vec<u8> {
+        let mut value = shares.to_be_bytes().to_vec();
+        value.extend_from_slice(&cost.to_be_bytes());
+        value
+    }
+
+    fn decode_value(value: &[u8]) -> Option<(u64, u64)> {
+        if value.len() != 16 {
+            return None;
+        }
+        let shares = u64::from_be_bytes(value[..8].try_into().ok()?);
+        let cost = u64::from_be_bytes(value[8..].try_into().ok()?);
+        Some((shares, cost))
+    }
+
+    fn decode(key: &[u8], value: &[u8]) -> Result<Position, EnclaveError> {
+        let corrupt = || EnclaveError::GenericError("Corrupt position entry".into());
+        if key.len() < 9 {
+            return Err(corrupt());
+        }
+        let (shares, cost) = decode_value(value).ok_or_else(corrupt)?;
+        Ok(Position {
+            wallet: String::from_utf8(key[9..].to_vec()).map_err(|_| corrupt())?,
+            pool_id: u64::from_be_bytes(key[..8].try_into().map_err(|_| corrupt())?),
+            outcome: key[8],
+            shares,
+            cost,
+        })
+    }
+
+    impl SledPositionStore {
+        pub fn open(path: impl AsRef<Path>) -> Result<Self, EnclaveError> {
+            let db = sled::open(path).map_err(db_err)?;
+            let tree = db.open_tree("positions").map_err(db_err)?;
+            Ok(Self { tree })
+        }
+
+        /// Store backed by a temporary database that is deleted on drop
+        pub fn temporary() -> Result<Self, EnclaveError> {
+            let db = sled::Config::new().temporary(true).open().map_err(db_err)?;
+            let tree = db.open_tree("positions").map_err(db_err)?;
+            Ok(Self { tree })
+        }
+
+        fn scan(&self, prefix: &[u8]) -> Result<Vec<Position>, EnclaveError> {
+            self.tree
+                .scan_prefix(prefix)
+                .map(|item| {
+                    let (k, v) = item.map_err(db_err)?;
+                    decode(&k, &v)
+                })
+                .collect()
+        }
+    }
+
+    impl PositionRepository for SledPositionStore {
+        fn add_position(&mut self, wallet: String, pool_id: u64, outcome: u8, shares: u64, cost: u64) -> Result<(), EnclaveError> {
+            self.tree
+                .update_and_fetch(key(&wallet, pool_id, outcome), |old| {
+                    let (held, held_cost) = old.and_then(decode_value).unwrap_or((0, 0));
+                    Some(encode_value(held + shares, held_cost + cost))
+                })
+                .map_err(db_err)?;
+            self.tree.flush().map_err(db_err)?;
+            Ok(())
+        }
+
+        fn remove_shares(&mut self, wallet: &str, pool_id: u64, outcome: u8, shares: u64) -> Result<(), EnclaveError> {
+            self.tree
+                .update_and_fetch(key(wallet, pool_id, outcome), |old| {
+                    let (held, cost) = old.and_then(decode_value)?;
+                    match held.saturating_sub(shares) {
+                        0 => None,
+                        left => Some(encode_value(left, remaining_cost(cost, held, shares))),
+                    }
+                })
+                .map_err(db_err)?;
+            self.tree.flush().map_err(db_err)?;
+            Ok(())
+        }
+
+        fn get_positions_by_pool(&self, pool_id: u64) -> Result<Vec<Position>, EnclaveError> {
+            self.scan(&pool_id.to_be_bytes())
+        }
+
+        fn clear_pool(&mut self, pool_id: u64) -> Result<(), EnclaveError> {
+            let mut batch = sled::Batch::default();
+            for item in self.tree.scan_prefix(pool_id.to_be_bytes()) {
+                let (k, _) = item.map_err(db_err)?;
+                batch.remove(k);
+            }
+            self.tree.apply_batch(batch).map_err(db_err)?;
+            self.tree.flush().map_err(db_err)?;
+            Ok(())
+        }
+
+        fn get_all_positions(&self) -> Result<Vec<Position>, EnclaveError> {
+            self.scan(&[])
+        }
+
+        fn restore(&mut self, positions: Vec<Position>) -> Result<(), EnclaveError> {
+            self.tree.clear().map_err(db_err)?;
+            for p in positions {
+                self.add_position(p.wallet, p.pool_id, p.outcome, p.shares, p.cost)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_sled_store_roundtrip() {
+            let mut store = SledPositionStore::temporary().unwrap();
+            store.add_position("0xuser1".to_string(), 1, 3, 1000, 200_000).unwrap();
+            store.add_position("0xuser1".to_string(), 1, 3, 500, 100_000).unwrap();
+            store.add_position("0xuser2".to_string(), 1, 5, 700, 90_000).unwrap();
+            store.add_position("0xuser3".to_string(), 2, 3, 900, 150_000).unwrap();
+
+            let winners = store.get_winning_positions(1, 3).unwrap();
+            assert_eq!(winners.len(), 1);
+            assert_eq!(winners[0].shares, 1500);
+            assert_eq!(winners[0].cost, 300_000);
+            assert_eq!(store.get_user_positions("0xuser2", 1).unwrap().len(), 1);
+
+            store.remove_shares("0xuser2", 1, 5, 700).unwrap();
+            assert_eq!(store.get_positions_by_pool(1).unwrap().len(), 1);
+
+            store.clear_pool(1).unwrap();
+            assert!(store.get_positions_by_pool(1).unwrap().is_empty());
+            assert_eq!(store.get_all_positions().unwrap().len(), 1);
+        }
+    }
+}
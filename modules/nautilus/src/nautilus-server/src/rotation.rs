@@ -0,0 +1,318 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ephemeral key rotation.
+//!
+//! The enclave signs with one ephemeral key at a time. A rotation replaces it
+//! with a fresh key of the same scheme and signs a handoff `(old_pk, new_pk,
+//! timestamp)` with the old key, as a domain message of `APP_NAUTILUS` for the
+//! chain the app signs for, so anyone trusting the old key can follow it to
+//! the new one; `rotate_key` also attests the new key for re-registration. The
+//! old key keeps verifying signatures for an overlap window after it is retired,
+//! so responses signed just before a rotation stay checkable.
+//!
+//! Rotation runs every `KEY_ROTATION_SECS` when it is set, with retired keys
+//! accepted for `KEY_OVERLAP_SECS` (10 minutes by default).
+
+use crate::common::{
+    attest, AttestationUserData, EnclaveKeyPair, EnclavePublicKey, GetAttestationResponse, IntentSigner,
+    ProcessedDataResponse, SignatureScheme,
+};
+use crate::intent::{
+    to_signed_domain_response, verify_signed_domain_response, ChainId, DomainIntentMessage, IntentHeader,
+    APP_NAUTILUS,
+};
+use crate::{AppState, EnclaveError};
+use axum::extract::State;
+use axum::Json;
+use fastcrypto::encoding::{Encoding, Hex};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+/// Scope of key handoffs under `APP_NAUTILUS`
+pub const INTENT_KEY_HANDOFF: u8 = 100;
+
+/// Header key handoffs are signed under on `chain_id`
+pub fn handoff_header(chain_id: ChainId) -> IntentHeader {
+    IntentHeader::new(APP_NAUTILUS, INTENT_KEY_HANDOFF, chain_id).expect("registered scope")
+}
+
+const DEFAULT_OVERLAP_SECS: u64 = 600;
+
+/// Handoff from a retired key to its successor - Signed by the retired key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyHandoff {
+    pub old_pk: Vec<u8>,
+    pub new_pk: Vec<u8>,
+    pub timestamp_ms: u64,
+}
+
+pub type SignedHandoff = ProcessedDataResponse<DomainIntentMessage<KeyHandoff>>;
+
+struct RetiredKey {
+    public: EnclavePublicKey,
+    handoff: SignedHandoff,
+}
+
+struct Keys {
+    current: EnclaveKeyPair,
+    retired: Vec<RetiredKey>, // Oldest first
+}
+
+/// The enclave's signing key and the keys it retired within the overlap window
+pub struct EphemeralKeys {
+    keys: RwLock<Keys>,
+    overlap: Duration,
+}
+
+impl EphemeralKeys {
+    pub fn new(current: EnclaveKeyPair) -> Self {
+        Self {
+            keys: RwLock::new(Keys { current, retired: Vec::new() }),
+            overlap: Duration::from_secs(DEFAULT_OVERLAP_SECS),
+        }
+    }
+
+    /// How long retired keys keep verifying
+    pub fn with_overlap(mut self, overlap: Duration) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Keys> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn public(&self) -> EnclavePublicKey {
+        self.read().current.public()
+    }
+
+    /// Replace the current key with a fresh one of the same scheme, returning the
+    /// handoff the old key signs for `chain_id`
+    pub fn rotate(&self, timestamp_ms: u64, chain_id: ChainId) -> SignedHandoff {
+        let next = EnclaveKeyPair::generate(self.scheme());
+        let mut keys = self.keys.write().unwrap_or_else(|e| e.into_inner());
+        let handoff = KeyHandoff {
+            old_pk: keys.current.public_key_bytes(),
+            new_pk: next.public_key_bytes(),
+            timestamp_ms,
+        };
+        let signed = to_signed_domain_response(&keys.current, handoff_header(chain_id), handoff, timestamp_ms);
+        let public = keys.current.public();
+        keys.current = next;
+        keys.retired.push(RetiredKey { public, handoff: signed.clone() });
+
+        let overlap = self.overlap.as_millis() as u64;
+        keys.retired.retain(|key| key.handoff.response.timestamp_ms + overlap >= timestamp_ms);
+        signed
+    }
+
+    /// Keys signatures are accepted from at `now_ms`: the current one first, then
+    /// the retired ones still in their overlap window, newest first
+    pub fn verifying_keys(&self, now_ms: u64) -> Vec<EnclavePublicKey> {
+        let keys = self.read();
+        let overlap = self.overlap.as_millis() as u64;
+        std::iter::once(keys.current.public())
+            .chain(
+                keys.retired
+                    .iter()
+                    .rev()
+                    .filter(|key| key.handoff.response.timestamp_ms + overlap >= now_ms)
+                    .map(|key| key.public.clone()),
+            )
+            .collect()
+    }
+
+    /// Handoffs of the keys still in their overlap window, oldest first
+    pub fn handoffs(&self, now_ms: u64) -> Vec<SignedHandoff> {
+        let overlap = self.overlap.as_millis() as u64;
+        self.read()
+            .retired
+            .iter()
+            .filter(|key| key.handoff.response.timestamp_ms + overlap >= now_ms)
+            .map(|key| key.handoff.clone())
+            .collect()
+    }
+}
+
+impl IntentSigner for EphemeralKeys {
+    fn scheme(&self) -> SignatureScheme {
+        self.read().current.scheme()
+    }
+
+    fn public_key_bytes(&self) -> Vec<u8> {
+        self.read().current.public_key_bytes()
+    }
+
+    fn sign_bytes(&self, msg: &[u8]) -> Vec<u8> {
+        self.read().current.sign_bytes(msg)
+    }
+}
+
+/// Check that `signed` hands off from `old` on `chain_id` and return the key it
+/// hands off to
+pub fn verify_handoff(
+    old: &EnclavePublicKey,
+    signed: &SignedHandoff,
+    chain_id: ChainId,
+) -> Result<EnclavePublicKey, EnclaveError> {
+    if signed.response.header.scope != INTENT_KEY_HANDOFF {
+        return Err(EnclaveError::GenericError(format!(
+            "Scope {} is not a key handoff",
+            signed.response.header.scope
+        )));
+    }
+    if signed.response.data.old_pk != old.as_bytes() {
+        return Err(EnclaveError::GenericError("Handoff is from another key".into()));
+    }
+    verify_signed_domain_response(old, signed, APP_NAUTILUS, chain_id)?;
+    EnclavePublicKey::from_bytes(old.scheme(), &signed.response.data.new_pk)
+}
+
+/// A rotation: the handoff and an attestation of the new key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub handoff: SignedHandoff,
+    pub attestation: GetAttestationResponse,
+}
+
+/// Rotate the enclave key, signing the handoff for `chain_id`, and attest the new
+/// one with `user_data`
+pub fn rotate_key(
+    state: &AppState,
+    user_data: AttestationUserData,
+    chain_id: ChainId,
+) -> Result<KeyRotation, EnclaveError> {
+    let handoff = state.eph_kp.rotate(now_ms()?, chain_id);
+    let attestation = attest(state, None, user_data)?;
+    Ok(KeyRotation { handoff, attestation })
+}
+
+/// Current enclave key and the handoffs leading to it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeysResponse {
+    /// Hex encoded current public key.
+    pub public_key: String,
+    pub scheme: SignatureScheme,
+    /// Handoffs of the keys retired within the overlap window, oldest first.
+    pub handoffs: Vec<SignedHandoff>,
+}
+
+/// Endpoint that returns the current key and the handoffs from recently
+/// retired keys.
+pub async fn get_keys(State(state): State<Arc<AppState>>) -> Result<Json<KeysResponse>, EnclaveError> {
+    let now = now_ms()?;
+    Ok(Json(KeysResponse {
+        public_key: Hex::encode(state.eph_kp.public_key_bytes()),
+        scheme: state.eph_kp.scheme(),
+        handoffs: state.eph_kp.handoffs(now),
+    }))
+}
+
+fn now_ms() -> Result<u64, EnclaveError> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| EnclaveError::GenericError(format!("Time error: {e}")))?
+        .as_millis() as u64)
+}
+
+/// How often to rotate, and how long retired keys keep verifying
+pub struct KeyRotationConfig {
+    pub interval: Duration,
+    pub overlap: Duration,
+}
+
+impl KeyRotationConfig {
+    /// Load from `KEY_ROTATION_SECS` and `KEY_OVERLAP_SECS`. Returns `None`
+    /// (no rotation) when no interval is set.
+    pub fn from_env() -> Result<Option<Self>, EnclaveError> {
+        let secs = |name: &str| -> Result<Option<u64>, EnclaveError> {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse().map_err(|e| EnclaveError::GenericError(format!("Invalid {name}: {e}"))))
+                .transpose()
+        };
+        let Some(interval_secs) = secs("KEY_ROTATION_SECS")? else {
+            return Ok(None);
+        };
+        if interval_secs == 0 {
+            return Err(EnclaveError::GenericError("KEY_ROTATION_SECS must be positive".into()));
+        }
+        let overlap_secs = secs("KEY_OVERLAP_SECS")?.unwrap_or(DEFAULT_OVERLAP_SECS);
+        Ok(Some(Self {
+            interval: Duration::from_secs(interval_secs),
+            overlap: Duration::from_secs(overlap_secs),
+        }))
+    }
+}
+
+/// Rotate the enclave key every `interval` with the app's `rotate`, which attests
+/// the new key with the app's user data, logging each attestation for
+/// re-registration
+pub async fn run_rotation_loop(
+    state: Arc<AppState>,
+    interval: Duration,
+    rotate: fn(&AppState) -> Result<KeyRotation, EnclaveError>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match rotate(&state) {
+            Ok(rotation) => info!(
+                "Rotated enclave key to {}, attestation {}",
+                Hex::encode(&rotation.handoff.response.data.new_pk),
+                rotation.attestation.attestation
+            ),
+            Err(e) => error!("Key rotation failed: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{to_signed_response, verify_signed_response};
+    use crate::intent::{SUI_MAINNET, SUI_TESTNET};
+
+    #[test]
+    fn test_key_rotation() {
+        let keys = EphemeralKeys::new(EnclaveKeyPair::generate(SignatureScheme::Secp256r1))
+            .with_overlap(Duration::from_secs(60));
+        let first = keys.public();
+        let signed = to_signed_response(&keys, 7u64, 1_000, 0);
+
+        let handoff = keys.rotate(1_000, SUI_TESTNET);
+        let second = keys.public();
+        assert_ne!(first, second);
+        assert_eq!(keys.scheme(), SignatureScheme::Secp256r1);
+        assert_eq!(verify_handoff(&first, &handoff, SUI_TESTNET).unwrap(), second);
+        assert!(verify_handoff(&second, &handoff, SUI_TESTNET).is_err());
+        assert!(verify_handoff(&first, &handoff, SUI_MAINNET).is_err());
+
+        // The old key verifies until the overlap ends
+        assert_eq!(keys.verifying_keys(61_000), vec![second.clone(), first.clone()]);
+        assert_eq!(keys.verifying_keys(61_001), vec![second.clone()]);
+        assert!(verify_signed_response(&first, &signed).is_ok());
+        assert!(verify_signed_response(&second, &to_signed_response(&keys, 7u64, 1_000, 0)).is_ok());
+
+        // A chain of handoffs leads from the first key to the current one
+        keys.rotate(2_000, SUI_TESTNET);
+        let handoffs = keys.handoffs(2_000);
+        assert_eq!(handoffs.len(), 2);
+        let last = handoffs.iter().try_fold(first, |pk, handoff| verify_handoff(&pk, handoff, SUI_TESTNET)).unwrap();
+        assert_eq!(last, keys.public());
+
+        // A forged handoff
+        let mut forged = handoffs[1].clone();
+        forged.response.data.new_pk = EnclaveKeyPair::generate(SignatureScheme::Secp256r1).public_key_bytes();
+        assert!(verify_handoff(&second, &forged, SUI_TESTNET).is_err());
+
+        // Retired keys past their overlap are dropped at the next rotation
+        keys.rotate(100_000, SUI_TESTNET);
+        assert_eq!(keys.handoffs(0).len(), 1);
+    }
+}
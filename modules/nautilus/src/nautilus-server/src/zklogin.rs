@@ -0,0 +1,210 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! zkLogin signatures over requests.
+//!
+//! A zkLogin wallet signs with an ephemeral key and carries a Groth16 proof that
+//! the key was committed to in a JWT its OpenID provider issued for the wallet's
+//! address seed. The enclave checks the ephemeral signature, the proof against
+//! the provider's JWK and that the issuer and address seed derive the claimed
+//! address.
+//!
+//! JWKs are read from the JSON file at `ZKLOGIN_JWKS_PATH`; zkLogin signatures
+//! are refused when it is unset. The file maps each issuer to its JWKS document
+//! as the provider serves it:
+//!
+//! ```json
+//! {
+//!   "env": "prod",
+//!   "epoch": 750,
+//!   "providers": { "https://accounts.google.com": { "keys": [ ... ] } }
+//! }
+//! ```
+//!
+//! `env` picks the verifying key of the prover (`prod`, or `test` for
+//! prover-dev). The enclave has no view of the Sui epoch, so `epoch` stands in
+//! for it: signatures whose `max_epoch` is before it are refused, and refreshing
+//! the file retires expired ephemeral keys.
+
+use crate::common::EnclavePublicKey;
+use crate::EnclaveError;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto_zkp::bn254::utils::get_zk_login_address;
+use fastcrypto_zkp::bn254::zk_login::{JWKReader, JwkId, ZkLoginInputs, JWK};
+use fastcrypto_zkp::bn254::zk_login_api::{verify_zk_login, ZkLoginEnv};
+use im::hashmap::HashMap as ImHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+static VERIFIER: OnceLock<ZkLoginVerifier> = OnceLock::new();
+
+/// Load the JWKs from `ZKLOGIN_JWKS_PATH`, if set
+pub fn init_from_env() -> Result<(), EnclaveError> {
+    let Some(path) = std::env::var("ZKLOGIN_JWKS_PATH").ok().filter(|path| !path.is_empty()) else {
+        return Ok(());
+    };
+    let verifier = ZkLoginVerifier::load(path)?;
+    VERIFIER
+        .set(verifier)
+        .map_err(|_| EnclaveError::GenericError("zkLogin JWKs are already loaded".into()))
+}
+
+/// The verifier loaded by `init_from_env`
+pub fn verifier() -> Result<&'static ZkLoginVerifier, EnclaveError> {
+    VERIFIER
+        .get()
+        .ok_or_else(|| EnclaveError::GenericError("zkLogin is not configured (ZKLOGIN_JWKS_PATH)".into()))
+}
+
+/// A zkLogin signature after its flag - Match Sui ZkLoginAuthenticator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZkLoginAuthenticator {
+    pub inputs: ZkLoginInputs,
+    pub max_epoch: u64,
+    pub user_signature: Vec<u8>, // Ephemeral key's flag || signature || public key
+}
+
+#[derive(Deserialize)]
+struct JwksFile {
+    #[serde(default)]
+    env: Option<String>,
+    #[serde(default)]
+    epoch: u64,
+    providers: HashMap<String, JwksDocument>,
+}
+
+#[derive(Deserialize)]
+struct JwksDocument {
+    keys: Vec<serde_json::Value>,
+}
+
+/// JWKs and verifying key zkLogin signatures are checked against
+#[derive(Debug)]
+pub struct ZkLoginVerifier {
+    env: ZkLoginEnv,
+    epoch: u64,
+    jwks: ImHashMap<JwkId, JWK>,
+}
+
+impl ZkLoginVerifier {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, EnclaveError> {
+        let path = path.as_ref();
+        let json = std::fs::read(path)
+            .map_err(|e| EnclaveError::GenericError(format!("Cannot read {}: {e}", path.display())))?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self, EnclaveError> {
+        let invalid = |e: String| EnclaveError::GenericError(format!("Invalid JWKs file: {e}"));
+        let file: JwksFile = serde_json::from_slice(json).map_err(|e| invalid(e.to_string()))?;
+        let env = match file.env.as_deref() {
+            None | Some("prod") => ZkLoginEnv::Prod,
+            Some("test") => ZkLoginEnv::Test,
+            Some(env) => return Err(invalid(format!("unknown env {env}"))),
+        };
+        let mut jwks = ImHashMap::new();
+        for (iss, document) in file.providers {
+            for key in document.keys {
+                let kid = key["kid"].as_str().ok_or_else(|| invalid("key without kid".into()))?.to_string();
+                let reader: JWKReader = serde_json::from_value(key).map_err(|e| invalid(e.to_string()))?;
+                let jwk = JWK::from_reader(reader).map_err(|e| invalid(format!("{kid}: {e}")))?;
+                jwks.insert(JwkId::new(iss.clone(), kid), jwk);
+            }
+        }
+        Ok(Self { env, epoch: file.epoch, jwks })
+    }
+
+    /// Check a zkLogin signature (without its flag) by `address` over the personal
+    /// message `digest`
+    pub fn verify(&self, signature: &[u8], digest: &[u8; 32], address: &str) -> Result<(), EnclaveError> {
+        let invalid = |e: String| EnclaveError::GenericError(format!("Invalid zkLogin signature: {e}"));
+        let mut authenticator: ZkLoginAuthenticator = bcs::from_bytes(signature).map_err(|e| invalid(e.to_string()))?;
+        let inputs = authenticator.inputs.init().map_err(|e| invalid(e.to_string()))?;
+        if authenticator.max_epoch < self.epoch {
+            return Err(invalid(format!("ephemeral key expired at epoch {}", authenticator.max_epoch)));
+        }
+
+        let zk_address = zklogin_address(&inputs)?;
+        if !zk_address.eq_ignore_ascii_case(address) {
+            return Err(EnclaveError::GenericError(format!("Request is signed by {zk_address}, not {address}")));
+        }
+
+        // The ephemeral key signs the request, the proof binds it to the address
+        let (&flag, rest) = authenticator
+            .user_signature
+            .split_first()
+            .ok_or_else(|| invalid("empty ephemeral signature".into()))?;
+        if rest.len() <= 64 {
+            return Err(invalid("ephemeral signature is too short".into()));
+        }
+        let (ephemeral_signature, ephemeral_pk) = rest.split_at(64);
+        EnclavePublicKey::from_bytes(flag.try_into()?, ephemeral_pk)?.verify(digest, ephemeral_signature)?;
+        let mut ephemeral_pk_bytes = vec![flag];
+        ephemeral_pk_bytes.extend_from_slice(ephemeral_pk);
+        verify_zk_login(&inputs, authenticator.max_epoch, &ephemeral_pk_bytes, &self.jwks, &self.env)
+            .map_err(|e| invalid(e.to_string()))
+    }
+}
+
+/// Sui address of a zkLogin wallet, derived from its issuer and address seed
+pub fn zklogin_address(inputs: &ZkLoginInputs) -> Result<String, EnclaveError> {
+    let address = get_zk_login_address(inputs.get_address_seed(), inputs.get_iss())
+        .map_err(|e| EnclaveError::GenericError(format!("Invalid zkLogin inputs: {e}")))?;
+    Ok(format!("0x{}", Hex::encode(address)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::personal_message_digest;
+    use fastcrypto::ed25519::Ed25519KeyPair;
+    use fastcrypto::traits::{KeyPair, Signer, ToFromBytes};
+    use fastcrypto_zkp::bn254::utils::gen_address_seed;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Google test vector of fastcrypto-zkp: proof for the ephemeral key from seed 0, max epoch 10
+    const PROOF: &str = r#"{"proofPoints":{"a":["8247215875293406890829839156897863742504615191361518281091302475904551111016","6872980335748205979379321982220498484242209225765686471076081944034292159666","1"],"b":[["21419680064642047510915171723230639588631899775315750803416713283740137406807","21566716915562037737681888858382287035712341650647439119820808127161946325890"],["17867714710686394159919998503724240212517838710399045289784307078087926404555","21812769875502013113255155836896615164559280911997219958031852239645061854221"],["1","0"]],"c":["7530826803702928198368421787278524256623871560746240215547076095911132653214","16244547936249959771862454850485726883972969173921727256151991751860694123976","1"]},"issBase64Details":{"value":"yJpc3MiOiJodHRwczovL2FjY291bnRzLmdvb2dsZS5jb20iLC","indexMod4":1},"headerBase64":"eyJhbGciOiJSUzI1NiIsImtpZCI6IjZmNzI1NDEwMWY1NmU0MWNmMzVjOTkyNmRlODRhMmQ1NTJiNGM2ZjEiLCJ0eXAiOiJKV1QifQ"}"#;
+    const ADDRESS: &str = "0xa64ae946d5efd2dea396cb2fe81837f028c32f2b2f211176b65a3a152deb35a2";
+    const JWKS: &str = r#"{"env":"prod","epoch":5,"providers":{"https://accounts.google.com":{"keys":[{"kid":"6f7254101f56e41cf35c9926de84a2d552b4c6f1","kty":"RSA","e":"AQAB","alg":"RS256","use":"sig","n":"oUriU8GqbRw-avcMn95DGW1cpZR1IoM6L7krfrWvLSSCcSX6Ig117o25Yk7QWBiJpaPV0FbP7Y5-DmThZ3SaF0AXW-3BsKPEXfFfeKVc6vBqk3t5mKlNEowjdvNTSzoOXO5UIHwsXaxiJlbMRalaFEUm-2CKgmXl1ss_yGh1OHkfnBiGsfQUndKoHiZuDzBMGw8Sf67am_Ok-4FShK0NuR3-q33aB_3Z7obC71dejSLWFOEcKUVCaw6DGVuLog3x506h1QQ1r0FXKOQxnmqrRgpoHqGSouuG35oZve1vgCU4vLZ6EAgBAbC0KL35I7_0wUDSMpiAvf7iZxzJVbspkQ"}]}}}"#;
+
+    fn address_seed() -> String {
+        let aud = "25769832374-famecqrhe2gkebt5fvqms2263046lj96.apps.googleusercontent.com";
+        gen_address_seed("206703048842351542647799591018316385612", "sub", "106294049240999307923", aud).unwrap()
+    }
+
+    fn sign(digest: &[u8; 32], max_epoch: u64) -> Vec<u8> {
+        let kp = Ed25519KeyPair::generate(&mut StdRng::from_seed([0; 32]));
+        let mut user_signature = vec![0x00];
+        user_signature.extend_from_slice(kp.sign(digest).as_bytes());
+        user_signature.extend_from_slice(kp.public().as_bytes());
+        let authenticator = ZkLoginAuthenticator {
+            inputs: ZkLoginInputs::from_json(PROOF, &address_seed()).unwrap(),
+            max_epoch,
+            user_signature,
+        };
+        bcs::to_bytes(&authenticator).unwrap()
+    }
+
+    #[test]
+    fn test_zklogin_signature() {
+        let verifier = ZkLoginVerifier::from_json(JWKS.as_bytes()).unwrap();
+        let digest = personal_message_digest(b"place bet");
+        let signature = sign(&digest, 10);
+        verifier.verify(&signature, &digest, ADDRESS).unwrap();
+
+        // Another message, another address, or a max epoch the proof was not made for
+        let other = personal_message_digest(b"place another bet");
+        assert!(verifier.verify(&signature, &other, ADDRESS).is_err());
+        assert!(verifier.verify(&signature, &digest, &format!("0x{}", "00".repeat(32))).is_err());
+        assert!(verifier.verify(&sign(&digest, 11), &digest, ADDRESS).is_err());
+
+        // An expired ephemeral key, or a provider key the enclave does not know
+        let later = ZkLoginVerifier::from_json(JWKS.replace(r#""epoch":5"#, r#""epoch":11"#).as_bytes()).unwrap();
+        assert!(later.verify(&signature, &digest, ADDRESS).is_err());
+        let unknown = ZkLoginVerifier::from_json(JWKS.replace("6f7254", "000000").as_bytes()).unwrap();
+        assert!(unknown.verify(&signature, &digest, ADDRESS).is_err());
+    }
+}
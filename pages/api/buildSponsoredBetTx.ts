@@ -45,9 +45,7 @@ export default async function handler(
             outcome,
             maker,
             teeResponse,
-            teeSignature,
-            userNewBalance,
-            makerNewBalance
+            teeSignature
         } = body;
 
         if (!sender) {
@@ -71,12 +69,28 @@ export default async function handler(
                     typeArguments: [`${PM_CONFIG.PM_PACKAGE}::pm::PM`],
                     arguments: [
                         tx.object(PM_CONFIG.ENCLAVE_OBJECT_ID),
+                        tx.object(PM_CONFIG.BET_REGISTRY_ID),
+                        tx.object('0x6'), // Clock
                         tx.pure.u64(teeResponse.shares),
                         tx.pure.vector('u64', teeResponse.newProbs),
                         tx.pure.u64(poolId),
                         tx.pure.u8(outcome),
                         tx.pure.u64(teeResponse.debitAmount),
                         tx.pure.u64(teeResponse.creditAmount),
+                        tx.pure.string(sender),
+                        tx.pure.u64(teeResponse.nonce),
+                        tx.pure.u64(teeResponse.seq),
+                        tx.pure.vector('u8', teeResponse.prevStateHash),
+                        tx.pure.u64(teeResponse.userBalance),
+                        tx.pure.u64(teeResponse.makerBalance),
+                        tx.pure.u64(teeResponse.feeAmount),
+                        tx.pure.string(teeResponse.protocol),
+                        tx.pure.u64(teeResponse.protocolFee),
+                        tx.pure.u64(teeResponse.protocolBalance),
+                        tx.pure.string(teeResponse.referrer),
+                        tx.pure.u64(teeResponse.referrerFee),
+                        tx.pure.u64(teeResponse.referrerBalance),
+                        tx.pure.u64(teeResponse.b),
                         tx.pure.u64(teeResponse.timestampMs),
                         tx.pure.vector('u8', fromHex(teeSignature)),
                     ],
@@ -88,7 +102,7 @@ export default async function handler(
                     arguments: [
                         tx.object(VAULT_CONFIG.LEDGER_ID),
                         tx.pure.address(sender),
-                        tx.pure.u64(BigInt(userNewBalance)),
+                        tx.pure.u64(teeResponse.userBalance),
                     ],
                 });
 
@@ -98,10 +112,27 @@ export default async function handler(
                     arguments: [
                         tx.object(VAULT_CONFIG.LEDGER_ID),
                         tx.pure.address(maker),
-                        tx.pure.u64(BigInt(makerNewBalance)),
+                        tx.pure.u64(teeResponse.makerBalance),
                     ],
                 });
 
+                // Credit the protocol and referrer fee shares, if any
+                for (const [recipient, balance] of [
+                    [teeResponse.protocol, teeResponse.protocolBalance],
+                    [teeResponse.referrer, teeResponse.referrerBalance],
+                ] as const) {
+                    if (recipient) {
+                        tx.moveCall({
+                            target: `${VAULT_CONFIG.PACKAGE_ID}::${VAULT_CONFIG.MODULE_NAME}::set_withdrawable_balance`,
+                            arguments: [
+                                tx.object(VAULT_CONFIG.LEDGER_ID),
+                                tx.pure.address(recipient),
+                                tx.pure.u64(balance),
+                            ],
+                        });
+                    }
+                }
+
                 // 4. Update World Probabilities
                 tx.moveCall({
                     target: `${WORLD_CONFIG.PACKAGE_ID}::${WORLD_CONFIG.MODULE_NAME}::update_prob`,
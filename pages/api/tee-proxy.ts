@@ -11,21 +11,23 @@ export default async function handler(req: NextApiRequest, res: NextApiResponse)
         // Support dynamic endpoint: process_data, resolve, health_check, positions, etc.
         const endpoint = req.body.endpoint || 'process_data';
         const payload = req.body.payload || req.body;
+        // Wallet signature over the payload, for endpoints that act in a wallet's name
+        const signature = req.body.signature;
 
         // GET endpoints: health_check, positions, get_attestation
-        const isGetEndpoint = ['health_check', 'positions', 'get_attestation'].includes(endpoint);
+        const isGetEndpoint = ['health_check', 'positions', 'get_attestation', 'pools/head'].includes(endpoint);
 
         let url = `${TEE_URL}/${endpoint}`;
 
         // Add query params for GET endpoints that need them
-        if (endpoint === 'positions' && payload?.pool_id !== undefined) {
+        if ((endpoint === 'positions' || endpoint === 'pools/head') && payload?.pool_id !== undefined) {
             url += `?pool_id=${payload.pool_id}`;
         }
 
         const response = await fetch(url, {
             method: isGetEndpoint ? 'GET' : 'POST',
             headers: isGetEndpoint ? {} : { 'Content-Type': 'application/json' },
-            body: isGetEndpoint ? undefined : JSON.stringify({ payload }),
+            body: isGetEndpoint ? undefined : JSON.stringify({ payload, signature }),
         });
 
         const text = await response.text();
@@ -7,9 +7,9 @@ import { TradeCard } from "@/components/market/TradeCard";
 import { WalletConnect } from "@/components/WalletConnect";
 import { COMBINED_MARKETS, DEFAULT_MARKET_DATA } from "@/data/combined-markets";
 import React from 'react';
-import { useCurrentAccount, useSignTransaction, useSuiClient } from '@mysten/dapp-kit';
-import { PlaceBetRequest, PlaceBetResponse, PM_CONFIG } from '@/lib/tee';
-import { VAULT_CONFIG, WORLD_CONFIG } from '@/lib/config';
+import { useCurrentAccount, useSignPersonalMessage, useSignTransaction, useSuiClient } from '@mysten/dapp-kit';
+import { ConfirmBetRequest, PlaceBetRequest, PlaceBetResponse, PoolHead, PM_CONFIG, placeBetMessage } from '@/lib/tee';
+import { WORLD_CONFIG } from '@/lib/config';
 import type { BuildSponsoredBetTxRequest, BuildSponsoredTxResponse } from '@/lib/shinami-types';
 
 type MarketSelection = "yes" | "no" | "any" | null;
@@ -22,6 +22,7 @@ export default function MarketPage() {
     const account = useCurrentAccount();
     const client = useSuiClient();
     const { mutateAsync: signTransaction } = useSignTransaction();
+    const { mutateAsync: signPersonalMessage } = useSignPersonalMessage();
 
     // Backend State
     const [probabilities, setProbabilities] = React.useState<Record<string, number> | null>(null);
@@ -146,27 +147,6 @@ export default function MarketPage() {
         } catch (err) { console.error('Error fetching maker', err); }
     };
 
-    // Helper to get user balance
-    const getUserWithdrawableBalance = async (userAddress: string) => {
-        try {
-            const ledgerObj = await client.getObject({ id: VAULT_CONFIG.LEDGER_ID, options: { showContent: true } });
-            const accountsTableId = ledgerObj.data?.content && 'fields' in ledgerObj.data.content
-                ? (ledgerObj.data.content.fields as any).accounts?.fields?.id?.id
-                : null;
-            if (!accountsTableId) return BigInt(0);
-
-            const userField = await client.getDynamicFieldObject({
-                parentId: accountsTableId,
-                name: { type: 'address', value: userAddress }
-            });
-
-            if (userField.data?.content && 'fields' in userField.data.content) {
-                return BigInt((userField.data.content.fields as any).value.fields.withdrawable_amount);
-            }
-        } catch (e) { return BigInt(0); }
-        return BigInt(0);
-    };
-
     const fromHex = (hex: string) => {
         const bytes = new Uint8Array(hex.length / 2);
         for (let i = 0; i < hex.length; i += 2) bytes[i / 2] = parseInt(hex.substring(i, i + 2), 16);
@@ -190,6 +170,17 @@ export default function MarketPage() {
                 });
             }
 
+            // Bets are priced from the TEE's latest pool state, which may be ahead of World
+            const headResponse = await fetch('/api/tee-proxy', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ endpoint: 'pools/head', payload: { pool_id: 0 } }),
+            });
+            const poolHead: PoolHead = await headResponse.json();
+            if (poolHead.probs?.length === 8) {
+                poolHead.probs.forEach((p, i) => { currentProbsArray[i] = p; });
+            }
+
             const request: PlaceBetRequest = {
                 user: account.address,
                 pool_id: 0, // Hardcoded Pool 0
@@ -197,13 +188,17 @@ export default function MarketPage() {
                 amount: parseInt(amountStr.replace(/[^0-9]/g, "")) * 1_000_000,
                 maker: maker, // Needs to be fetched
                 current_probs: currentProbsArray,
+                nonce: Date.now(),
             };
 
+            // The TEE only prices bets signed by the bettor's wallet
+            const { signature } = await signPersonalMessage({ message: placeBetMessage(request) });
+
             // Step 1: Get TEE response
             const teeResponse = await fetch('/api/tee-proxy', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ endpoint: 'process_data', payload: request }),
+                body: JSON.stringify({ endpoint: 'process_data', payload: request, signature }),
             });
 
             const teeData = await teeResponse.json();
@@ -214,13 +209,7 @@ export default function MarketPage() {
 
             const betResponse: PlaceBetResponse = teeData.response.data;
 
-            // Calculate new balances
             const makerAddress = maker || account.address;
-            const makerCurrentBalance = await getUserWithdrawableBalance(makerAddress);
-            const makerNewBalance = makerCurrentBalance + BigInt(betResponse.credit_amount);
-
-            const userCurrentBalance = await getUserWithdrawableBalance(account.address);
-            const userNewBalance = userCurrentBalance - BigInt(betResponse.debit_amount);
 
             // Step 2: Build sponsored transaction via backend
             const buildRequest: BuildSponsoredBetTxRequest = {
@@ -235,11 +224,22 @@ export default function MarketPage() {
                     newProbs: betResponse.new_probs,
                     debitAmount: betResponse.debit_amount,
                     creditAmount: betResponse.credit_amount,
+                    nonce: betResponse.nonce,
+                    seq: betResponse.seq,
+                    prevStateHash: betResponse.prev_state_hash,
+                    userBalance: betResponse.user_balance,
+                    makerBalance: betResponse.maker_balance,
+                    feeAmount: betResponse.fee_amount,
+                    protocol: betResponse.protocol,
+                    protocolFee: betResponse.protocol_fee,
+                    protocolBalance: betResponse.protocol_balance,
+                    referrer: betResponse.referrer,
+                    referrerFee: betResponse.referrer_fee,
+                    referrerBalance: betResponse.referrer_balance,
+                    b: betResponse.b,
                     timestampMs: teeData.response.timestamp_ms,
                 },
                 teeSignature: teeData.signature,
-                userNewBalance: userNewBalance.toString(),
-                makerNewBalance: makerNewBalance.toString(),
             };
 
             const buildResponse = await fetch('/api/buildSponsoredBetTx', {
@@ -278,6 +278,19 @@ export default function MarketPage() {
 
             const result = await executeResponse.json();
             console.log('Sponsored transaction result:', result);
+
+            // Turn the pending bet into a position in the TEE
+            const confirmRequest: ConfirmBetRequest = {
+                user: account.address,
+                nonce: betResponse.nonce,
+                tx_digest: result.digest,
+            };
+            await fetch('/api/tee-proxy', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ endpoint: 'confirm', payload: confirmRequest }),
+            });
+
             alert("Bet Placed! (Gasless) TX: " + result.digest);
             fetchPoolData(); // Refresh
 
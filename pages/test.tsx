@@ -1,8 +1,8 @@
 // Integration Test Page - Test full user flow with TEE and Smart Contracts
 import { useState, useEffect } from 'react';
-import { useCurrentAccount, useSignAndExecuteTransaction, useSuiClient } from '@mysten/dapp-kit';
+import { useCurrentAccount, useSignAndExecuteTransaction, useSignPersonalMessage, useSuiClient } from '@mysten/dapp-kit';
 import { Transaction } from '@mysten/sui/transactions';
-import { PlaceBetRequest, PlaceBetResponse, ResolveRequest, ResolveResponse, AttestationRequest, AttestationResponse, PM_CONFIG } from '../lib/tee';
+import { ConfirmBetRequest, PlaceBetRequest, PlaceBetResponse, PoolHead, ResolveRequest, ResolveResponse, AttestationRequest, AttestationResponse, PM_CONFIG, placeBetMessage, resolveMessage } from '../lib/tee';
 import { VAULT_CONFIG, WORLD_CONFIG, USDC_CONFIG } from '../lib/config';
 import { buildMint1000UsdcTransaction, USDC_COIN_TYPE } from '../lib/usdc';
 import { buildDepositTransaction, buildSetWithdrawableBalanceTransaction, CoinData, parseUserAccountData, LEDGER_ID } from '../lib/vault';
@@ -11,6 +11,7 @@ export default function TestPage() {
     const account = useCurrentAccount();
     const client = useSuiClient();
     const { mutate: signAndExecute } = useSignAndExecuteTransaction();
+    const { mutateAsync: signPersonalMessage } = useSignPersonalMessage();
 
     // State
     const [logs, setLogs] = useState<string[]>([]);
@@ -246,7 +247,14 @@ export default function TestPage() {
 
         try {
             // Get current probs from World (or use default)
-            const currentProbs = [1250, 1250, 1250, 1250, 1250, 1250, 1250, 1250]; // 12.5% each
+            const defaultProbs = [1250, 1250, 1250, 1250, 1250, 1250, 1250, 1250]; // 12.5% each
+            const headResponse = await fetch('/api/tee-proxy', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ endpoint: 'pools/head', payload: { pool_id: parseInt(poolId) } }),
+            });
+            const poolHead: PoolHead = await headResponse.json();
+            const currentProbs = poolHead.probs?.length ? poolHead.probs : defaultProbs;
 
             const request: PlaceBetRequest = {
                 user: account.address,
@@ -255,9 +263,11 @@ export default function TestPage() {
                 amount: parseInt(betAmount) * 1_000_000, // Convert to smallest unit
                 maker: maker,
                 current_probs: currentProbs,
+                nonce: Date.now(),
             };
 
             log(`Sending to TEE: ${JSON.stringify(request)}`);
+            const { signature } = await signPersonalMessage({ message: placeBetMessage(request) });
 
             const response = await fetch('/api/tee-proxy', {
                 method: 'POST',
@@ -265,6 +275,7 @@ export default function TestPage() {
                 body: JSON.stringify({
                     endpoint: 'process_data',
                     payload: request,
+                    signature,
                 }),
             });
 
@@ -300,62 +311,11 @@ export default function TestPage() {
         return Array.from(bytes);
     };
 
-    // Helper: Get user's withdrawable balance from ledger
-    const getUserWithdrawableBalance = async (userAddress: string): Promise<bigint> => {
-        try {
-            // 1. Get Ledger object to find accounts table ID
-            const ledgerObj = await client.getObject({
-                id: VAULT_CONFIG.LEDGER_ID,
-                options: { showContent: true }
-            });
-
-            const accountsTableId = ledgerObj.data?.content && 'fields' in ledgerObj.data.content
-                ? (ledgerObj.data.content.fields as any).accounts?.fields?.id?.id
-                : null;
-
-            if (!accountsTableId) {
-                console.log('No accounts table found in ledger');
-                return BigInt(0);
-            }
-
-            // 2. Get dynamic fields from accounts table
-            const fields = await client.getDynamicFields({
-                parentId: accountsTableId,
-            });
-
-            for (const field of fields.data) {
-                if (field.name?.value === userAddress) {
-                    const accountObj = await client.getObject({
-                        id: field.objectId,
-                        options: { showContent: true },
-                    });
-
-                    if (accountObj.data?.content && 'fields' in accountObj.data.content) {
-                        const content = accountObj.data.content.fields as any;
-                        const value = content.value?.fields;
-                        if (value?.withdrawable_amount) {
-                            return BigInt(value.withdrawable_amount);
-                        }
-                    }
-                    break;
-                }
-            }
-        } catch (err) {
-            console.error('Error fetching user balance:', err);
-        }
-        return BigInt(0);
-    };
-
     // Execute PM contract submission
     const executePMSubmitBet = async (betResponse: PlaceBetResponse, timestamp: number, signature: string) => {
         if (!account) return;
 
-        // Fetch maker's current balance BEFORE building transaction
         const makerAddress = maker || account.address;
-        const makerCurrentBalance = await getUserWithdrawableBalance(makerAddress);
-        const makerNewBalance = makerCurrentBalance + BigInt(betResponse.credit_amount);
-
-        console.log(`Maker ${makerAddress.slice(0, 10)}... balance: ${makerCurrentBalance} + ${betResponse.credit_amount} = ${makerNewBalance}`);
 
         const tx = new Transaction();
 
@@ -365,6 +325,8 @@ export default function TestPage() {
             typeArguments: [`${PM_CONFIG.PM_PACKAGE}::pm::PM`],
             arguments: [
                 tx.object(PM_CONFIG.ENCLAVE_OBJECT_ID), // The registered enclave object
+                tx.object(PM_CONFIG.BET_REGISTRY_ID), // Replay protection
+                tx.object('0x6'), // Clock (rejects stale signed bets)
                 // Response data
                 tx.pure.u64(betResponse.shares),
                 tx.pure.vector('u64', betResponse.new_probs),
@@ -372,42 +334,63 @@ export default function TestPage() {
                 tx.pure.u8(betResponse.outcome),
                 tx.pure.u64(betResponse.debit_amount),
                 tx.pure.u64(betResponse.credit_amount),
+                tx.pure.string(betResponse.user),
+                tx.pure.u64(betResponse.nonce),
+                tx.pure.u64(betResponse.seq),
+                tx.pure.vector('u8', betResponse.prev_state_hash),
+                tx.pure.u64(betResponse.user_balance),
+                tx.pure.u64(betResponse.maker_balance),
+                tx.pure.u64(betResponse.fee_amount),
+                tx.pure.string(betResponse.protocol),
+                tx.pure.u64(betResponse.protocol_fee),
+                tx.pure.u64(betResponse.protocol_balance),
+                tx.pure.string(betResponse.referrer),
+                tx.pure.u64(betResponse.referrer_fee),
+                tx.pure.u64(betResponse.referrer_balance),
+                tx.pure.u64(betResponse.b),
                 // Signature
                 tx.pure.u64(timestamp),
                 tx.pure.vector('u8', fromHex(signature)),
             ],
         });
 
-        // 2. Debit user (Update Vault)
-        const currentMIST = BigInt(vaultBalance);
-        const betAmountMIST = BigInt(betResponse.debit_amount);
-        const newUserBalance = currentMIST - betAmountMIST;
-
-        // Safety check to prevent negative balance
-        if (newUserBalance < 0) {
-            log(`❌ Error: Insufficient balance. Current: ${currentMIST}, Debit: ${betAmountMIST}`);
-            return;
-        }
-
+        // 2. Debit user (Update Vault) - the enclave already checked the balance
         tx.moveCall({
             target: `${VAULT_CONFIG.PACKAGE_ID}::${VAULT_CONFIG.MODULE_NAME}::set_withdrawable_balance`,
             arguments: [
                 tx.object(VAULT_CONFIG.LEDGER_ID),
                 tx.pure.address(account.address),
-                tx.pure.u64(newUserBalance),
+                tx.pure.u64(betResponse.user_balance),
             ],
         });
 
-        // 3. Credit Maker (current balance + credit_amount)
+        // 3. Credit Maker
         tx.moveCall({
             target: `${VAULT_CONFIG.PACKAGE_ID}::${VAULT_CONFIG.MODULE_NAME}::set_withdrawable_balance`,
             arguments: [
                 tx.object(VAULT_CONFIG.LEDGER_ID),
                 tx.pure.address(makerAddress),
-                tx.pure.u64(makerNewBalance), // Now correctly adds to existing balance
+                tx.pure.u64(betResponse.maker_balance),
             ],
         });
 
+        // Credit the protocol and referrer fee shares, if any
+        for (const [recipient, balance] of [
+            [betResponse.protocol, betResponse.protocol_balance],
+            [betResponse.referrer, betResponse.referrer_balance],
+        ] as const) {
+            if (recipient) {
+                tx.moveCall({
+                    target: `${VAULT_CONFIG.PACKAGE_ID}::${VAULT_CONFIG.MODULE_NAME}::set_withdrawable_balance`,
+                    arguments: [
+                        tx.object(VAULT_CONFIG.LEDGER_ID),
+                        tx.pure.address(recipient),
+                        tx.pure.u64(balance),
+                    ],
+                });
+            }
+        }
+
         // 4. Update World Probs
         tx.moveCall({
             target: `${WORLD_CONFIG.PACKAGE_ID}::${WORLD_CONFIG.MODULE_NAME}::update_prob`,
@@ -421,8 +404,19 @@ export default function TestPage() {
         signAndExecute(
             { transaction: tx },
             {
-                onSuccess: (result) => {
+                onSuccess: async (result) => {
                     log(`✅ PM contract confirmed! Signature Verified. TX: ${result.digest}`);
+                    const confirmRequest: ConfirmBetRequest = {
+                        user: betResponse.user,
+                        nonce: betResponse.nonce,
+                        tx_digest: result.digest,
+                    };
+                    const confirmResponse = await fetch('/api/tee-proxy', {
+                        method: 'POST',
+                        headers: { 'Content-Type': 'application/json' },
+                        body: JSON.stringify({ endpoint: 'confirm', payload: confirmRequest }),
+                    });
+                    log(`TEE confirm: ${JSON.stringify(await confirmResponse.json())}`);
                     fetchUserData();
                 },
                 onError: (err) => log(`❌ PM submission failed: ${err.message}`),
@@ -470,6 +464,8 @@ export default function TestPage() {
             };
 
             log(`Sending resolve request: ${JSON.stringify(request)}`);
+            // Only the TEE's configured resolver wallet can resolve
+            const { signature } = await signPersonalMessage({ message: resolveMessage(request) });
 
             const response = await fetch('/api/tee-proxy', {
                 method: 'POST',
@@ -477,6 +473,7 @@ export default function TestPage() {
                 body: JSON.stringify({
                     endpoint: 'resolve',
                     payload: request,
+                    signature,
                 }),
             });
 